@@ -1,5 +1,158 @@
 
-from typing import Dict
+from typing import Any, Callable, Dict, List, Literal, Optional, Tuple
+
+# The parameters of a query: a list or tuple, or a single value for one parameter.
+# The values can also be enum.Enum members with an int or str value, stored as that value,
+# and datetime, date and time objects, stored as ISO 8601 text (`YYYY-MM-DD HH:MM:SS`).
+# NumPy scalars and other numbers.Integral and numbers.Real types are stored as ints and floats.
+Params = list | tuple | dict | int | float | str | bool | bytes | bytearray | memoryview
+
+
+class DatabaseError(RuntimeError):
+    """Base class of the errors raised by the SQLite engine."""
+
+    sqlite_errorcode: Optional[int]
+    """The extended SQLite result code, e.g. 2067 for SQLITE_CONSTRAINT_UNIQUE."""
+
+    sqlite_primary_errorcode: Optional[int]
+    """The primary SQLite result code, e.g. 19 for SQLITE_CONSTRAINT."""
+
+
+class IntegrityError(DatabaseError):
+    """A constraint was violated (UNIQUE, NOT NULL, FOREIGN KEY, CHECK...)."""
+
+
+class OperationalError(DatabaseError):
+    """
+    The database could not perform the operation: locked or busy database,
+    missing table, invalid SQL, read-only database...
+    """
+
+
+class QueryTimeout(OperationalError):
+    """The query was cancelled because it ran longer than its `timeout_ms`."""
+
+
+class RowLimitExceeded(DatabaseError):
+    """The query returned more rows than its `max_rows`."""
+
+
+# Return values of the authorizer callback, see Database.set_authorizer()
+SQLITE_OK: int
+SQLITE_DENY: int
+SQLITE_IGNORE: int
+
+# Action codes passed to the authorizer callback
+SQLITE_CREATE_INDEX: int
+SQLITE_CREATE_TABLE: int
+SQLITE_CREATE_TEMP_INDEX: int
+SQLITE_CREATE_TEMP_TABLE: int
+SQLITE_CREATE_TEMP_TRIGGER: int
+SQLITE_CREATE_TEMP_VIEW: int
+SQLITE_CREATE_TRIGGER: int
+SQLITE_CREATE_VIEW: int
+SQLITE_DELETE: int
+SQLITE_DROP_INDEX: int
+SQLITE_DROP_TABLE: int
+SQLITE_DROP_TEMP_INDEX: int
+SQLITE_DROP_TEMP_TABLE: int
+SQLITE_DROP_TEMP_TRIGGER: int
+SQLITE_DROP_TEMP_VIEW: int
+SQLITE_DROP_TRIGGER: int
+SQLITE_DROP_VIEW: int
+SQLITE_INSERT: int
+SQLITE_PRAGMA: int
+SQLITE_READ: int
+SQLITE_SELECT: int
+SQLITE_TRANSACTION: int
+SQLITE_UPDATE: int
+SQLITE_ATTACH: int
+SQLITE_DETACH: int
+SQLITE_ALTER_TABLE: int
+SQLITE_REINDEX: int
+SQLITE_ANALYZE: int
+SQLITE_CREATE_VTABLE: int
+SQLITE_DROP_VTABLE: int
+SQLITE_FUNCTION: int
+SQLITE_SAVEPOINT: int
+SQLITE_RECURSIVE: int
+SQLITE_COPY: int
+
+
+class Blob:
+    """
+    A handle on a BLOB returned by `Database.open_blob()`, used like a binary file.
+    The BLOB cannot be resized: writes must fit in its current size.
+    """
+
+    def read(self, size: int = -1) -> bytes:
+        """Read up to `size` bytes from the current position, or until the end if `size` is negative."""
+        ...
+
+    def write(self, data: bytes | bytearray | memoryview) -> int:
+        """
+        Write `data` at the current position and return the number of bytes written.
+
+        Raises:
+            RuntimeError: If the BLOB is read-only or the data goes past its end.
+        """
+        ...
+
+    def seek(self, offset: int, whence: int = 0) -> int:
+        """Move the position from the start (0), the current position (1) or the end (2) and return it."""
+        ...
+
+    def tell(self) -> int:
+        """Return the current position."""
+        ...
+
+    def __len__(self) -> int:
+        """Return the size of the BLOB in bytes."""
+        ...
+
+    def close(self) -> None:
+        """Close the handle, later operations raise an error."""
+        ...
+
+    def __enter__(self) -> "Blob": ...
+
+    def __exit__(self, exc_type: Any = None, exc_value: Any = None, traceback: Any = None) -> bool: ...
+
+
+class Transactional:
+    """A function wrapped by `Database.transactional()`, each call runs in a transaction."""
+
+    __wrapped__: Callable[..., Any]
+
+    def __call__(self, *args: Any, **kwargs: Any) -> Any: ...
+
+
+class Transaction:
+    """The context manager returned by `Database.transaction()` and `Database.read_transaction()`."""
+
+    def __enter__(self) -> None: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool: ...
+
+
+class Cursor:
+    """The cursor returned by `Database.cursor()`."""
+
+    def fetchmany(self, size: int) -> List[Any]:
+        """
+        Return up to `size` of the next rows, converted like in `Database.fetch_all()`,
+        or an empty list once they have all been returned.
+
+        Raises:
+            RuntimeError: If `size` is 0 or the query execution fails.
+        """
+        ...
+
+
+class BulkLoad:
+    """The context manager returned by `Database.bulk_load()`."""
+
+    def __enter__(self) -> None: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool: ...
 
 
 class Database:
@@ -7,14 +160,69 @@ class Database:
     A small wrapper around an SQLite database connection to perform basic operations.
     """
 
-    def __new__(cls, db_path: str) -> "Database":
+    def __new__(
+        cls,
+        db_path: str = "database.sqlite",
+        read_only: bool = False,
+        connect_retries: int = 0,
+        connect_retry_delay_ms: int = 100,
+        allow_extensions: bool = False,
+        extra_extensions: List[str] = [],
+        non_finite_floats: Literal["error", "null", "text"] = "error",
+        uri: bool = False,
+        max_retries: int = 0,
+        retry_backoff_ms: int = 50,
+        cache_size: Optional[int] = None,
+        param_style: Optional[Literal["qmark", "named"]] = None,
+        large_ints: Literal["error", "text"] = "error",
+        key: Optional[str] = None,
+        reals: Literal["float", "decimal", "text"] = "float",
+        surrogates: Literal["error", "replace", "blob"] = "error",
+        create_dirs: bool = False,
+    ) -> "Database":
         """
         Create a new Database instance.
 
         Args:
-            db_path (str): The path to the SQLite database file.
+            db_path (str): The path to the SQLite database file, "database.sqlite" by default.
             It should end with one of these extensions:
-            `.sqlite` `.sql` `.db`
+            `.sqlite` `.sqlite3` `.sql` `.db`
+            read_only (bool): Open an existing database in read-only mode,
+            any write attempt then raises a RuntimeError.
+            connect_retries (int): How many times opening the database is retried
+            before giving up, useful when the file may be temporarily unavailable.
+            connect_retry_delay_ms (int): The delay between two attempts.
+            allow_extensions (bool): Allow loading SQLite extensions with `load_extension()`.
+            extra_extensions (List[str]): More extensions accepted for `db_path`, e.g. `[".data"]`.
+            non_finite_floats (str): What to do with NaN and infinite floats, which SQLite
+            cannot store reliably: "error" raises a RuntimeError, "null" stores NULL and
+            "text" stores "nan", "inf" or "-inf", fetched back as that string.
+            uri (bool): `db_path` is an SQLite URI filename like `file:data.db?mode=ro`,
+            its parameters are honored and the extension check is skipped.
+            max_retries (int): How many times `execute_raw_query()`, `insert()` and the fetch
+            methods run a query again when the database stays locked by another connection.
+            retry_backoff_ms (int): The wait before the first retry, doubled at each new one.
+            cache_size (Optional[int]): The page cache of the connection (`PRAGMA cache_size`).
+            A negative value is a size in KiB (`-65536` for 64 MB), a positive one a number
+            of pages. SQLite's default is `-2000`, about 2 MB.
+            param_style (Optional[str]): Enforce one kind of parameters: "qmark" for a list,
+            a tuple or a single value with `?` placeholders, "named" for a dict with `:name`
+            placeholders. Both are accepted by default.
+            large_ints (str): What to do with ints outside SQLite's 64-bit range
+            (-2**63 to 2**63 - 1): "error" raises a RuntimeError and "text" stores their
+            digits as TEXT, which `int()` converts back. Columns declared INTEGER, REAL or
+            NUMERIC convert such TEXT to a rounded REAL, so use a TEXT column for them.
+            key (Optional[str]): The key of a database encrypted with SQLCipher. Requires
+            a build with the `sqlcipher` feature, otherwise a RuntimeError is raised.
+            reals (str): The type of the REAL values of the typed fetches (`typed=True`,
+            `fetch_value()`): "float", "decimal" for a `decimal.Decimal` of their shortest
+            exact text (`Decimal("0.1")`), or "text" for that text as a str.
+            surrogates (str): What to do with strings holding lone surrogates, which
+            cannot be encoded in UTF-8: "error" raises a RuntimeError showing the string,
+            "replace" stores them with each surrogate replaced by U+FFFD and "blob" stores
+            their bytes, which `decode("utf-8", "surrogatepass")` converts back.
+            create_dirs (bool): Create the missing parent directories of `db_path` before
+            opening it. Ignored with `read_only` or `uri`.
 
         Returns:
             Database: An instance of the Database class.
@@ -22,18 +230,92 @@ class Database:
         ...
 
 
-    def create_table(self, table_name: str, dict_columns: Dict[str, type]) -> int:
+    @staticmethod
+    def from_bytes(data: bytes | bytearray | memoryview, read_only: bool = False) -> "Database":
+        """
+        Open an in-memory database holding a copy of `data`, the content of an SQLite file,
+        e.g. returned by `serialize()`. Its `path` is ":memory:".
+
+        Args:
+            data (bytes | bytearray | memoryview): The content of the SQLite file.
+            read_only (bool): Make any write attempt fail.
+
+        Raises:
+            DatabaseError: If the data is not an SQLite database.
+
+        Returns:
+            Database: The in-memory database.
+        """
+        ...
+
+
+    @property
+    def path(self) -> str:
+        """The path the database was opened with."""
+        ...
+
+
+    @property
+    def last_fetch_truncated(self) -> bool:
+        """Whether the last `fetch_all()` with `max_rows` and `truncate=True` left rows out."""
+        ...
+
+
+    param_style: Optional[Literal["qmark", "named"]]
+    """The kind of parameters the queries accept, None to accept both."""
+
+
+    row_factory: Optional[Callable[[List[str], List[Any]], Any]]
+    """
+    If set, called with `(column_names, values)` for each fetched row, and its result
+    is returned instead of the list of values, e.g. `lambda c, v: dict(zip(c, v))`.
+    """
+
+
+    def close(self) -> None:
+        """
+        Close the connection. Any later operation raises a DatabaseError.
+        Closing an already closed database does nothing.
+
+        Raises:
+            DatabaseError: If SQLite refuses to close the connection.
+        """
+        ...
+
+
+    def __enter__(self) -> "Database":
+        """Use the database in a with-block, it is closed at the end of the block."""
+        ...
+
+
+    def __exit__(self, exc_type, exc_value, traceback) -> bool:
+        """Close the database. Exceptions raised inside the block are not suppressed."""
+        ...
+
+
+    def create_table(
+        self,
+        table_name: str,
+        dict_columns: Dict[str, type | str],
+        without_rowid: bool = False,
+        strict: bool = False,
+    ) -> int:
         """
         Creates a new table in the SQLite database.
 
         Args:
             table_name (str): The name of the table to be created.
-            dict_columns (Dict[str, type]): A dictionary where keys are column names 
+            dict_columns (Dict[str, type | str]): A dictionary where keys are column names 
                 and values are Python types (str, int, float, bool) representing the column types.
+                A value can also be a string used verbatim as the SQL type,
+                e.g. `{"id": "INTEGER PRIMARY KEY", "data": "BLOB"}`.
+            without_rowid (bool): Create a WITHOUT ROWID table, stored by its PRIMARY KEY.
+            strict (bool): Create a STRICT table, which rejects the values that do not match
+                their column type. Only INT, INTEGER, REAL, TEXT, BLOB and ANY are allowed.
 
         Raises:
             RuntimeError: If a column type is not one of the allowed built-in Python types 
-                (str, int, float, bool).
+                (str, int, float, bool), or not allowed in a STRICT table.
             Exception: If an internal SQLite error occurs.
 
         Returns:
@@ -42,18 +324,1407 @@ class Database:
         ...
 
     
-    def execute_raw_query(self, query: str, params: tuple | list) -> int:
+    def create_table_from_class(self, cls: type, table_name: Optional[str] = None) -> int:
+        """
+        Creates a new table from a class with type annotations, like a dataclass.
+
+        Args:
+            cls (type): A class whose annotations are str, int, float or bool.
+            table_name (str, optional): The name of the table, defaults to the class name.
+
+        Raises:
+            RuntimeError: If the class has no annotations or an annotation type is not supported.
+
+        Returns:
+            int: The updated rows number
+        """
+        ...
+
+
+    def rename_table(self, old_name: str, new_name: str) -> None:
+        """
+        Rename a table with `ALTER TABLE ... RENAME TO`.
+
+        Args:
+            old_name (str): The current name of the table.
+            new_name (str): The new name: letters, digits and underscores.
+
+        Raises:
+            RuntimeError: If a name is not valid or the table cannot be renamed.
+        """
+        ...
+
+
+    def add_column(
+        self,
+        table: str,
+        column_name: str,
+        column_type: type | str,
+        constraints: Optional[str] = None,
+    ) -> None:
+        """
+        Add a column to a table with `ALTER TABLE ... ADD COLUMN`.
+
+        Args:
+            table (str): The name of the table.
+            column_name (str): The name of the new column.
+            column_type (type | str): str, int, float or bool, or a SQL type as a string,
+                like in `create_table()`, e.g. `"INTEGER NOT NULL DEFAULT 0"`.
+            constraints (str, optional): SQL appended after the type, e.g. `"NOT NULL DEFAULT 0"`.
+
+        Raises:
+            RuntimeError: If a name or the type is not valid or the column cannot be added.
+        """
+        ...
+
+
+    def drop_column(self, table: str, column_name: str) -> None:
+        """
+        Remove a column from a table with `ALTER TABLE ... DROP COLUMN` (SQLite 3.35+).
+
+        Args:
+            table (str): The name of the table.
+            column_name (str): The name of the column to remove.
+
+        Raises:
+            RuntimeError: If a name is not valid, SQLite is older than 3.35, or the column
+                cannot be dropped (e.g. it is indexed or part of the PRIMARY KEY).
+        """
+        ...
+
+    def create_index(
+        self,
+        table: str,
+        columns: List[str],
+        name: Optional[str] = None,
+        unique: bool = False,
+        where: Optional[str] = None,
+        if_not_exists: bool = True,
+    ) -> None:
+        """
+        Create an index on columns of a table.
+
+        A column entry that is not a plain column name is an expression, e.g.
+        `"lower(email)"`. With `where`, the index is partial and only holds the rows
+        matching the condition. The expressions and the condition are passed as SQL.
+
+        Args:
+            table (str): The name of the table.
+            columns (List[str]): The columns or expressions to index.
+            name (Optional[str]): The name of the index, `idx_<table>_<columns>` by
+                default. Required when indexing an expression.
+            unique (bool): Whether to create a UNIQUE index.
+            where (Optional[str]): The condition of a partial index.
+            if_not_exists (bool): Whether an existing index with the same name is
+                left as it is instead of raising.
+
+        Raises:
+            RuntimeError: If a name is not valid, `columns` is empty, or an expression
+                is indexed without a `name`.
+            DatabaseError: If SQLite rejects the index.
+        """
+        ...
+
+    
+    def execute_raw_query(
+        self, query: str, params: Params, timeout_ms: Optional[int] = None
+    ) -> int:
         """
         Execute a raw SQL query on the database.
 
         Args:
             query (str): The SQL query to execute.
-            params (tuple | list): The parameters to pass to the query.
+            params (Params): The parameters to pass to the query, or a single value.
+                A list, tuple or set inside it is expanded for IN clauses,
+                e.g. `WHERE id IN (?)` with `[[1, 2, 3]]`.
+            timeout_ms (Optional[int]): Cancel the query if it runs longer than this.
 
         Raises:
+            QueryTimeout: If the query ran longer than `timeout_ms`.
             RuntimeError: If the query execution fails.
         
         Returns:
             int: The number of rows affected by the query.
         """
         ...
+
+    def execute_many_statements(self, statements: List[Tuple[str, Params]]) -> List[int]:
+        """
+        Execute several statements with their own parameters in a single transaction:
+        either all of them are applied or none. Inside `begin()`/`commit()`, a failure
+        only undoes the statements of this call.
+
+        Args:
+            statements (List[Tuple[str, Params]]): The `(query, params)` of each statement.
+
+        Raises:
+            RuntimeError: If the parameters are not valid or a statement fails.
+
+        Returns:
+            List[int]: The number of rows affected by each statement.
+        """
+        ...
+
+
+    def serialize(self) -> bytes:
+        """
+        Return the content of the database, the same bytes as its file would have.
+        Open them again with `Database.from_bytes()`.
+
+        Raises:
+            RuntimeError: If the database is closed.
+        """
+        ...
+
+
+    def backup(
+        self,
+        dest_path: str,
+        progress: Optional[Callable[[int, int], None]] = None,
+        pages_per_step: int = 100,
+        timeout_ms: int = 5000,
+    ) -> None:
+        """
+        Copy the live database into another file using SQLite's online backup API.
+        This is safe to run while the database is in use.
+
+        Args:
+            dest_path (str): The path of the backup file.
+            progress (Callable[[int, int], None], optional): Called after each step
+                with the remaining and total number of pages. The database stays locked
+                during the backup, so it must not use the database.
+            pages_per_step (int): The number of pages copied at each step.
+            timeout_ms (int): How long the steps are retried while the source or the
+                destination is busy or locked by another connection.
+
+        Raises:
+            DatabaseError: If `progress` uses the database.
+            OperationalError: If the databases stay busy or locked for `timeout_ms`.
+            RuntimeError: If the destination cannot be opened or the backup fails.
+        """
+        ...
+
+
+    def vacuum(self) -> None:
+        """
+        Rebuild the database file to reclaim free pages.
+
+        Raises:
+            RuntimeError: If a transaction is open (VACUUM cannot run inside one)
+                or if the statement fails.
+        """
+        ...
+
+    def vacuum_into(self, dest_path: str) -> None:
+        """
+        Write a compacted copy of the database to a new file, without touching the
+        live database. A cheaper alternative to `backup()` for snapshots.
+
+        Args:
+            dest_path (str): The path of the copy, which must not exist yet.
+
+        Raises:
+            RuntimeError: If `dest_path` already exists, a transaction is open,
+                or the statement fails.
+        """
+        ...
+
+
+    def analyze(self) -> None:
+        """
+        Refresh the statistics used by the query planner.
+
+        Raises:
+            RuntimeError: If the statement fails.
+        """
+        ...
+
+
+    def integrity_check(self) -> Literal[True] | List[str]:
+        """
+        Run `PRAGMA integrity_check` on the database.
+
+        Raises:
+            RuntimeError: If the check cannot be run.
+
+        Returns:
+            True | List[str]: True if the database is healthy, otherwise
+                the list of problems reported by SQLite.
+        """
+        ...
+
+
+    def create_function(self, name: str, num_args: int, callable: Callable[..., Any]) -> None:
+        """
+        Register a Python callable as a scalar SQL function.
+
+        Args:
+            name (str): The name of the function in SQL.
+            num_args (int): The number of arguments, or -1 for any number.
+            callable (Callable): Receives the SQL arguments as Python values and
+                returns None, str, int, float, bool or bytes. It runs while the query
+                keeps the database locked, so it must not use the database.
+
+        Raises:
+            RuntimeError: If the function cannot be registered.
+        """
+        ...
+
+
+    def create_aggregate(self, name: str, num_args: int, agg_class: type) -> None:
+        """
+        Register a Python class as an aggregate SQL function, like `SUM()`.
+
+        Args:
+            name (str): The name of the function in SQL.
+            num_args (int): The number of arguments, or -1 for any number.
+            agg_class (type): Instantiated for each group of rows. Its `step(*args)` method
+                receives the arguments of each row and `finalize()` returns None, str, int,
+                float, bool or bytes. They must not use the database, which the query keeps
+                locked.
+
+        Raises:
+            RuntimeError: If agg_class is not callable or the function cannot be registered.
+        """
+        ...
+
+
+    def create_collation(self, name: str, callable: Callable[[str, str], int]) -> None:
+        """
+        Register a Python callable as a collation, used with `COLLATE name`.
+
+        Args:
+            name (str): The name of the collation in SQL.
+            callable (Callable[[str, str], int]): Compares two strings and returns a negative
+                number, 0 or a positive number, like the `cmp` functions. Its exceptions are
+                reported with `sys.unraisablehook` and the strings are then considered equal.
+                It must not use the database, which the query keeps locked.
+
+        Raises:
+            RuntimeError: If the collation cannot be registered.
+        """
+        ...
+
+
+    def get_user_version(self) -> int:
+        """
+        Read `PRAGMA user_version`, an integer stored in the database header,
+        typically used to track the last applied migration.
+
+        Raises:
+            RuntimeError: If the pragma cannot be read.
+        """
+        ...
+
+
+    def set_user_version(self, version: int) -> None:
+        """
+        Set `PRAGMA user_version`.
+
+        Args:
+            version (int): The new version number.
+
+        Raises:
+            RuntimeError: If the pragma cannot be set.
+        """
+        ...
+
+
+    def execute_returning(
+        self, query: str, params: Params, typed: bool = False
+    ) -> Tuple[List[List[Any]], int]:
+        """
+        Run a statement that both changes rows and returns data, like `UPDATE ... RETURNING`.
+
+        Args:
+            query (str): The SQL statement to execute.
+            params (Params): The parameters to pass to the statement.
+            typed (bool): Convert the values like in `fetch_all()`.
+
+        Raises:
+            RuntimeError: If the statement execution fails.
+
+        Returns:
+            Tuple[List[List[Any]], int]: The returned rows, like in `fetch_all()`, and the
+                number of rows inserted, updated or deleted.
+        """
+        ...
+
+
+    def execute_script(self, sql: str) -> int:
+        """
+        Run several SQL statements separated by semicolons, without parameters.
+        They are not wrapped in a transaction: if one fails, the previous ones stay applied.
+
+        Args:
+            sql (str): The SQL script.
+
+        Raises:
+            RuntimeError: If a statement fails.
+
+        Returns:
+            int: The number of rows inserted, updated or deleted by the whole script,
+                including the rows changed by triggers.
+        """
+        ...
+
+
+    def fetch_all(
+        self,
+        query: str,
+        params: Params,
+        json_columns: List[str] = [],
+        timeout_ms: Optional[int] = None,
+        typed: bool = False,
+        converters: Dict[str, Callable[[Any], Any]] = {},
+        max_rows: Optional[int] = None,
+        truncate: bool = False,
+    ) -> List[List[Any]]:
+        """
+        Run a SELECT query and return all the rows.
+
+        Args:
+            query (str): The SQL query to execute.
+            params (Params): The parameters to pass to the query.
+            json_columns (List[str]): Columns parsed back into Python objects with
+                `json.loads`, their NULLs are returned as None.
+            timeout_ms (Optional[int]): Cancel the query if it runs longer than this.
+            typed (bool): Convert the values from the declared type of their column:
+                INTEGER gives int, REAL float, TEXT str and NULL None. Other columns
+                keep the type SQLite stored.
+            converters (Dict[str, Callable]): Callables converting the values of some
+                columns, e.g. `{"created_at": datetime.fromisoformat}`. They receive the
+                value SQLite stored (int, float, str or bytes) and are not called for NULL.
+                An enum class turns the stored values back into members, e.g.
+                `{"status": Status}`.
+            max_rows (Optional[int]): The maximum number of rows to read.
+            truncate (bool): Return the first `max_rows` rows instead of raising when the
+                query has more, and set `last_fetch_truncated`.
+
+        Raises:
+            QueryTimeout: If the query ran longer than `timeout_ms`.
+            RowLimitExceeded: If the query has more than `max_rows` rows and `truncate` is False.
+            RuntimeError: If the query execution fails.
+
+        Returns:
+            List[List[Any]]: The rows. Values are returned as strings (NULL as "NULL"),
+                except BOOLEAN columns which are returned as booleans, BLOBs which are
+                returned as bytes and the JSON columns. If `row_factory` is set, each
+                row is what it returns.
+        """
+        ...
+
+    def cursor(self, query: str, params: Params) -> Cursor:
+        """
+        Return a cursor over the rows of a SELECT query, read in batches with
+        `cursor.fetchmany(size)`. Each cursor has its own position. Each `fetchmany()`
+        runs the query again for its batch with `LIMIT` and `OFFSET`, so only one batch
+        is in memory. Rows inserted or deleted between two calls shift the position, and
+        the query needs an `ORDER BY` for a stable order.
+
+        Args:
+            query (str): The SQL query to execute.
+            params (Params): The parameters to pass to the query.
+
+        Raises:
+            RuntimeError: If the parameters do not match `param_style`.
+
+        Returns:
+            Cursor: The cursor, used as `while rows := cursor.fetchmany(500):`.
+        """
+        ...
+
+
+    def fetch_all_batched(
+        self,
+        query: str,
+        param_sets: List[Params],
+        json_columns: List[str] = [],
+        timeout_ms: Optional[int] = None,
+        typed: bool = False,
+        converters: Dict[str, Callable[[Any], Any]] = {},
+    ) -> List[List[Any]]:
+        """
+        Run the same SELECT query for each parameter set, preparing it only once.
+
+        Args:
+            query (str): The SQL query to execute.
+            param_sets (List[Params]): The parameters of each run.
+            json_columns (List[str]): Same as in `fetch_all()`.
+            timeout_ms (Optional[int]): Cancel the batch if it runs longer than this.
+            typed (bool): Same as in `fetch_all()`.
+            converters (Dict[str, Callable]): Same as in `fetch_all()`.
+
+        Raises:
+            QueryTimeout: If the batch ran longer than `timeout_ms`.
+            RuntimeError: If a query execution fails.
+
+        Returns:
+            List[List[Any]]: One list of rows per parameter set, converted like in `fetch_all()`.
+        """
+        ...
+
+
+    def fetch_all_with_columns(
+        self, query: str, params: Params, json_columns: List[str] = []
+    ) -> Tuple[List[str], List[List[Any]]]:
+        """
+        Same as `fetch_all()`, but also returns the column names.
+
+        Returns:
+            Tuple[List[str], List[List[Any]]]: The column names and the rows.
+        """
+        ...
+
+
+    def wal_checkpoint(
+        self, mode: Literal["PASSIVE", "FULL", "RESTART", "TRUNCATE"] = "PASSIVE"
+    ) -> Tuple[int, int, int]:
+        """
+        Copy the WAL file content back into the database with `PRAGMA wal_checkpoint`.
+
+        Args:
+            mode (str): PASSIVE, FULL, RESTART or TRUNCATE.
+
+        Raises:
+            RuntimeError: If the mode is not valid or the checkpoint fails.
+
+        Returns:
+            Tuple[int, int, int]: busy (1 if the checkpoint could not complete),
+                the number of pages in the WAL and the number of pages written back.
+                The counts are -1 when the database is not in WAL mode.
+        """
+        ...
+
+
+    def wal_autocheckpoint(self, n_pages: int) -> int:
+        """
+        Set `PRAGMA wal_autocheckpoint`, the number of pages in the WAL after which a
+        checkpoint runs automatically at commit (1000 by default).
+
+        Args:
+            n_pages (int): The number of pages, `0` disables the automatic checkpoints.
+
+        Raises:
+            RuntimeError: If `n_pages` is negative or the pragma cannot be set.
+
+        Returns:
+            int: The new value.
+        """
+        ...
+
+
+    def page_count(self) -> int:
+        """
+        Read `PRAGMA page_count`, the number of pages of the database.
+
+        Raises:
+            RuntimeError: If the pragma cannot be read.
+        """
+        ...
+
+
+    def page_size(self) -> int:
+        """
+        Read `PRAGMA page_size`, the size of a page in bytes.
+
+        Raises:
+            RuntimeError: If the pragma cannot be read.
+        """
+        ...
+
+
+    def database_size(self) -> int:
+        """
+        Return the size of the database in bytes, `page_count * page_size`. Unlike the
+        file size, it includes the pages in the WAL that are not checkpointed yet.
+
+        Raises:
+            RuntimeError: If the pragmas cannot be read.
+        """
+        ...
+
+
+    def load_extension(self, path: str, entry_point: Optional[str] = None) -> None:
+        """
+        Load a SQLite extension from a shared library. Extensions run native code,
+        so the database must be created with `allow_extensions=True`.
+
+        Args:
+            path (str): The path of the shared library.
+            entry_point (str, optional): The name of the init function,
+                guessed by SQLite when not given.
+
+        Raises:
+            RuntimeError: If extension loading is not allowed or the extension fails to load.
+        """
+        ...
+
+
+    def rekey(self, new_key: str) -> None:
+        """
+        Change the encryption key of a database opened with `key`.
+
+        Args:
+            new_key (str): The new key.
+
+        Raises:
+            RuntimeError: If the build has no encryption support (the `sqlcipher` feature)
+                or the key cannot be changed.
+        """
+        ...
+
+
+    def begin(self, mode: Literal["DEFERRED", "IMMEDIATE", "EXCLUSIVE"] = "DEFERRED") -> None:
+        """
+        Start a transaction.
+
+        Args:
+            mode (str): When the locks are taken. DEFERRED on the first read or write,
+                IMMEDIATE takes the write lock right away (avoids "database is locked"
+                errors in concurrent writes), EXCLUSIVE also prevents other connections from reading.
+
+        Raises:
+            RuntimeError: If the mode is not valid or a transaction is already open.
+        """
+        ...
+
+
+    def commit(self) -> None:
+        """
+        Commit the current transaction.
+
+        Raises:
+            RuntimeError: If no transaction is open or the commit fails.
+        """
+        ...
+
+
+    def rollback(self) -> None:
+        """
+        Roll back the current transaction.
+
+        Raises:
+            RuntimeError: If no transaction is open.
+        """
+        ...
+
+    def in_transaction(self) -> bool:
+        """
+        Check whether a transaction is open, e.g. to avoid starting a nested one.
+
+        Returns:
+            bool: True between a `BEGIN` and its `COMMIT` or `ROLLBACK`.
+        """
+        ...
+
+    def transaction(
+        self, mode: Literal["DEFERRED", "IMMEDIATE", "EXCLUSIVE"] = "DEFERRED"
+    ) -> Transaction:
+        """
+        Context manager running its block in a transaction, committed when the block
+        ends and rolled back when it raises. Inside a transaction, the block runs in a
+        savepoint with a unique name instead, so only its own changes are rolled back.
+
+        Args:
+            mode (str): The transaction mode, like in `begin()`.
+
+        Raises:
+            RuntimeError: If the mode is not valid.
+
+        Returns:
+            Transaction: The context manager, used as `with db.transaction():`.
+        """
+        ...
+
+
+    def bulk_load(self) -> BulkLoad:
+        """
+        Context manager for large imports: its block runs in one transaction with
+        `synchronous = OFF`, `journal_mode = MEMORY` and the foreign keys checked at the
+        commit. The block is committed when it ends and rolled back when it raises, then
+        the previous `synchronous` and `journal_mode` are restored in both cases.
+
+        A crash of the OS or a power loss during the load can corrupt the database, so
+        use it for loads that can be started over.
+
+        Raises:
+            RuntimeError: On enter, if a transaction is open or the pragmas cannot be set.
+
+        Returns:
+            BulkLoad: The context manager, used as `with db.bulk_load():`.
+        """
+        ...
+
+
+    def read_transaction(self) -> Transaction:
+        """
+        Context manager running its block in a read-only DEFERRED transaction, so all
+        its queries see the snapshot of the database taken when the block starts.
+        Writes inside the block raise an error (`PRAGMA query_only`).
+
+        Returns:
+            Transaction: The context manager, used as `with db.read_transaction():`.
+        """
+        ...
+
+
+    def transactional(
+        self,
+        func: Optional[Callable[..., Any]] = None,
+        *,
+        mode: Literal["DEFERRED", "IMMEDIATE", "EXCLUSIVE"] = "DEFERRED",
+    ) -> Any:
+        """
+        Decorator running each call of a function in a transaction, committed when it
+        returns and rolled back when it raises. Inside a transaction, the call runs in a
+        savepoint instead. Use `@db.transactional` or `@db.transactional(mode="IMMEDIATE")`.
+
+        Args:
+            func (Callable, optional): The function to wrap.
+            mode (str): The transaction mode, like in `begin()`.
+
+        Raises:
+            RuntimeError: If the mode is not valid.
+
+        Returns:
+            Transactional: The wrapped function, or the decorator if `func` is not given.
+        """
+        ...
+
+
+    def export_csv(self, query: str, params: Params, path: str) -> int:
+        """
+        Run a query and write the results to a CSV file, with the column names as header.
+        NULL values are written as empty fields and BLOBs are base64 encoded.
+
+        Args:
+            query (str): The SQL query to execute.
+            params (Params): The parameters to pass to the query.
+            path (str): The path of the CSV file, overwritten if it exists.
+
+        Raises:
+            RuntimeError: If the query fails or the file cannot be written.
+
+        Returns:
+            int: The number of rows written.
+        """
+        ...
+
+
+    def fetch_json(self, query: str, params: Params) -> str:
+        """
+        Run a query and return its rows as a JSON array of objects, built in Rust.
+        Numbers stay numbers (NaN and infinities become null), NULL is null and BLOBs
+        are base64 strings.
+
+        Args:
+            query (str): The SQL query to execute.
+            params (Params): The parameters to pass to the query.
+
+        Raises:
+            RuntimeError: If the query fails.
+
+        Returns:
+            str: The JSON text, e.g. `[{"name":"Ray","age":27}]`.
+        """
+        ...
+
+
+    def dump(self) -> str:
+        """
+        Return an SQL script recreating the schema and the data, like the `.dump`
+        command of the sqlite3 shell. Apply it to another database with `load_dump()`.
+
+        Raises:
+            RuntimeError: If the database cannot be read.
+
+        Returns:
+            str: The SQL script.
+        """
+        ...
+
+
+    def load_dump(self, sql: str) -> None:
+        """
+        Run an SQL script made by `dump()`, e.g. to restore a backup into an empty database.
+        If a statement fails, nothing is applied. The `foreign_keys` setting is kept.
+
+        Args:
+            sql (str): The SQL script.
+
+        Raises:
+            RuntimeError: If a statement of the script fails.
+        """
+        ...
+
+
+    def migrate(self, migrations: List[Tuple[int, str]]) -> List[int]:
+        """
+        Apply the migrations whose version is greater than the current `user_version`.
+        Each migration runs in its own transaction and bumps `user_version` when it succeeds.
+        If one fails, it is rolled back and the following ones are not applied.
+
+        Args:
+            migrations (List[Tuple[int, str]]): `(version, sql)` tuples, the SQL can
+                contain several statements.
+
+        Raises:
+            RuntimeError: If a version is used twice or a migration fails.
+
+        Returns:
+            List[int]: The versions that were applied.
+        """
+        ...
+
+
+    def get_rowid(self, table: str, where: Dict[str, Any]) -> Optional[int]:
+        """
+        Return the rowid of the most recent row (highest rowid) matching the values of
+        `where`, e.g. to give it to `open_blob()` or to update the row by rowid.
+
+        Args:
+            table (str): The name of the table.
+            where (Dict[str, Any]): The values the columns must have, None matching NULL.
+                An empty dict matches every row.
+
+        Raises:
+            RuntimeError: If a name is not valid or the query fails.
+
+        Returns:
+            Optional[int]: The rowid, or None if no row matches.
+        """
+        ...
+
+
+    def open_blob(self, table: str, column: str, rowid: int, read_only: bool = True) -> Blob:
+        """
+        Open a BLOB for incremental I/O, to stream large values without loading them in memory.
+        Use `zeroblob(n)` in an INSERT or UPDATE to reserve the space before writing.
+
+        Args:
+            table (str): The table containing the BLOB.
+            column (str): The column containing the BLOB.
+            rowid (int): The rowid of the row.
+            read_only (bool): Whether the BLOB is opened read-only. Defaults to True.
+
+        Raises:
+            RuntimeError: If the row, column or table does not exist.
+
+        Returns:
+            Blob: A file-like handle on the BLOB.
+        """
+        ...
+
+
+    def insert(
+        self, table: str, values: Dict[str, Any], dry_run: bool = False
+    ) -> int | Tuple[str, List[Any]]:
+        """
+        Insert a row.
+
+        Args:
+            table (str): The name of the table.
+            values (Dict[str, Any]): The columns of the row and their values.
+            dry_run (bool): Return the INSERT statement and its parameters, converted like
+                they would be bound, as a tuple `(sql, params)` instead of running it.
+
+        Raises:
+            RuntimeError: If a value has an unsupported type or the insertion fails.
+
+        Returns:
+            int | Tuple[str, List[Any]]: The number of inserted rows, or with `dry_run`,
+                the statement and its parameters.
+        """
+        ...
+
+    def insert_json(self, table: str, values: Dict[str, Any]) -> int:
+        """
+        Same as `insert()`, but dict and list values are serialized to JSON with `json.dumps`.
+
+        Args:
+            table (str): The name of the table.
+            values (Dict[str, Any]): The column names and their values.
+
+        Raises:
+            RuntimeError: If a value cannot be serialized or the insertion fails.
+
+        Returns:
+            int: The number of inserted rows.
+        """
+        ...
+
+
+    @staticmethod
+    def json_extract(column: str, path: str) -> str:
+        """
+        Build a `json_extract(column, '$.path')` expression to use in a query.
+
+        Args:
+            column (str): The column containing the JSON.
+            path (str): The JSON path, with or without the leading `$.`.
+
+        Returns:
+            str: The SQL expression.
+        """
+        ...
+
+
+    @staticmethod
+    def quote_identifier(name: str) -> str:
+        """
+        Quote a table or column name to put it in an SQL query, e.g. `a"b` gives `"a""b"`.
+        `create_table()` and the insert methods quote their names the same way.
+
+        Args:
+            name (str): The table or column name.
+
+        Returns:
+            str: The quoted name.
+        """
+        ...
+
+
+    @staticmethod
+    def quote_literal(value: Any) -> str:
+        """
+        Write a value as an SQL literal, e.g. `it's` gives `'it''s'` and None gives `NULL`.
+        Prefer query parameters whenever possible.
+
+        Args:
+            value (Any): None, int, float, str, bool or bytes.
+
+        Raises:
+            RuntimeError: If the value has another type or is a NaN or infinite float.
+
+        Returns:
+            str: The SQL literal.
+        """
+        ...
+
+
+    def create_view(self, name: str, query: str, if_not_exists: bool = True) -> None:
+        """
+        Create a view from a SELECT query, to reuse it like a table in other queries.
+
+        Args:
+            name (str): The name of the view: letters, digits and underscores.
+            query (str): The SELECT query of the view.
+            if_not_exists (bool): Do nothing if the view already exists. Defaults to True.
+
+        Raises:
+            RuntimeError: If the name is not valid or the view cannot be created.
+        """
+        ...
+
+
+    def drop_view(self, name: str, if_exists: bool = True) -> None:
+        """
+        Drop a view.
+
+        Args:
+            name (str): The name of the view.
+            if_exists (bool): Do nothing if the view does not exist. Defaults to True.
+
+        Raises:
+            RuntimeError: If the name is not valid or the view cannot be dropped.
+        """
+        ...
+
+
+    def with_cte(
+        self,
+        ctes: Dict[str, str],
+        main_query: str,
+        params: Params,
+        typed: bool = False,
+    ) -> List[List[Any]]:
+        """
+        Run a SELECT query preceded by common table expressions (`WITH name AS (...)`).
+
+        Args:
+            ctes (Dict[str, str]): The name and SELECT query of each CTE, in order, so a CTE
+                can use the ones before it. A CTE referring to itself is recursive.
+            main_query (str): The query using the CTEs.
+            params (Params): The parameters of the full query, the CTEs' placeholders first.
+            typed (bool): Convert the values like in `fetch_all()`.
+
+        Raises:
+            RuntimeError: If a CTE name is not valid or the query fails.
+
+        Returns:
+            List[List[Any]]: The rows, like in `fetch_all()`.
+        """
+        ...
+
+
+    def ping(self) -> bool:
+        """
+        Check that the database is open and answers `SELECT 1`, e.g. for readiness checks.
+
+        Returns:
+            bool: True if the database answers, False if it is closed or broken.
+        """
+        ...
+
+
+    def insert_returning(
+        self, table: str, values: Dict[str, Any], returning: List[str]
+    ) -> List[List[str | bool | bytes]]:
+        """
+        Insert a row and return some of its columns with a `RETURNING` clause,
+        e.g. to get back a generated id or default values.
+
+        Args:
+            table (str): The name of the table.
+            values (Dict[str, Any]): The column names and their values, bound like in
+                `insert()`.
+            returning (List[str]): The columns to return, or `["*"]` for all of them.
+
+        Raises:
+            RuntimeError: If a column name is not valid, a value has an unsupported type
+                or the insertion fails.
+
+        Returns:
+            List[List[str | bool | bytes]]: The returned rows, converted like in `fetch_all()`.
+        """
+        ...
+
+
+    def insert_many(self, table: str, rows: List[Dict[str, Any]]) -> int:
+        """
+        Insert a list of rows in a single transaction: either all of them are inserted or none.
+        Inside `begin()`/`commit()`, a failure only undoes the rows of this call.
+
+        Args:
+            table (str): The name of the table.
+            rows (List[Dict[str, Any]]): The rows, all the dicts must have the same keys.
+
+        Raises:
+            RuntimeError: If the rows do not have the same columns or an insertion fails.
+
+        Returns:
+            int: The number of inserted rows.
+        """
+        ...
+
+    def upsert_many(
+        self, table: str, rows: List[Dict[str, Any]], conflict_columns: List[str]
+    ) -> Tuple[int, int]:
+        """
+        Insert a list of rows, or update the existing row conflicting with one on
+        `conflict_columns`, with `INSERT ... ON CONFLICT DO UPDATE`. The rows are written
+        in a single transaction: either all of them or none.
+
+        Args:
+            table (str): The name of the table.
+            rows (List[Dict[str, Any]]): The rows, all the dicts must have the same keys.
+                Their columns other than `conflict_columns` are the ones updated.
+            conflict_columns (List[str]): The columns of a UNIQUE constraint or index.
+
+        Raises:
+            RuntimeError: If a name is not valid, a conflict column is not in the rows,
+                the rows do not have the same columns or a write fails.
+
+        Returns:
+            Tuple[int, int]: The number of inserted rows and of updated rows.
+        """
+        ...
+
+    def select(
+        self,
+        table: str,
+        columns: Optional[List[str]] = None,
+        where: Optional[Dict[str, Any]] = None,
+        order_by: Optional[List[Tuple[str, str]]] = None,
+        limit: Optional[int] = None,
+        offset: Optional[int] = None,
+        dry_run: bool = False,
+    ) -> List[Any] | Tuple[str, List[Any]]:
+        """
+        Select the rows of a table. Names are validated and `limit` and `offset` are
+        bound as parameters, so user input can be used for sorting and pagination.
+
+        Args:
+            table (str): The name of the table.
+            columns (Optional[List[str]]): The columns to return, all of them by default.
+            where (Optional[Dict[str, Any]]): Column values the rows must all match,
+                None matching NULL.
+            order_by (Optional[List[Tuple[str, str]]]): `(column, "ASC" | "DESC")` tuples.
+            limit (Optional[int]): The maximum number of rows to return.
+            offset (Optional[int]): The number of rows to skip.
+            dry_run (bool): Return the query and its parameters as a tuple `(sql, params)`
+                instead of running it, like `insert()`.
+
+        Raises:
+            RuntimeError: If a name or a sort direction is not valid, or the query fails.
+
+        Returns:
+            List[Any] | Tuple[str, List[Any]]: The rows, like fetch_all(), or with
+                `dry_run`, the query and its parameters.
+        """
+        ...
+
+    def update(
+        self,
+        table: str,
+        values: Dict[str, Any],
+        where: Dict[str, Any],
+        returning: Optional[List[str]] = None,
+        dry_run: bool = False,
+    ) -> int | List[Any] | Tuple[str, List[Any]]:
+        """
+        Set the columns of `values` on the rows matching `where`.
+
+        Args:
+            table (str): The name of the table.
+            values (Dict[str, Any]): The columns to set and their new values.
+            where (Dict[str, Any]): Column values the rows must all match, None matching
+                NULL. An empty dict matches every row.
+            returning (Optional[List[str]]): Columns to return from the updated rows,
+                or `["*"]` for all of them.
+            dry_run (bool): Return the statement and its parameters as a tuple
+                `(sql, params)` instead of running it, like `insert()`.
+
+        Raises:
+            RuntimeError: If a name is not valid, `values` is empty, a value has an
+                unsupported type (a list included) or the update fails.
+
+        Returns:
+            int | List[Any] | Tuple[str, List[Any]]: The number of updated rows, or with
+                `returning`, the returned columns of the updated rows, like fetch_all().
+                With `dry_run`, the statement and its parameters.
+        """
+        ...
+
+    def delete(
+        self,
+        table: str,
+        where: Dict[str, Any],
+        returning: Optional[List[str]] = None,
+        dry_run: bool = False,
+    ) -> int | List[Any] | Tuple[str, List[Any]]:
+        """
+        Delete the rows matching `where`. Use truncate() to delete all the rows.
+
+        Args:
+            table (str): The name of the table.
+            where (Dict[str, Any]): Column values the rows must all match, None matching
+                NULL. An empty dict matches every row.
+            returning (Optional[List[str]]): Columns to return from the deleted rows,
+                or `["*"]` for all of them.
+            dry_run (bool): Return the statement and its parameters as a tuple
+                `(sql, params)` instead of running it, like `insert()`.
+
+        Raises:
+            RuntimeError: If a name is not valid, a value has an unsupported type (a list
+                included) or the deletion fails.
+
+        Returns:
+            int | List[Any] | Tuple[str, List[Any]]: The number of deleted rows, or with
+                `returning`, the returned columns of the deleted rows, like fetch_all().
+                With `dry_run`, the statement and its parameters.
+        """
+        ...
+
+    def copy_rows(
+        self,
+        source_table: str,
+        dest_table: str,
+        where: Optional[Dict[str, Any]] = None,
+    ) -> int:
+        """
+        Copy rows from a table into another one with `INSERT INTO ... SELECT *`. The
+        columns are copied by position, so both tables should have the same columns.
+
+        Args:
+            source_table (str): The table to copy the rows from.
+            dest_table (str): The table to copy the rows into.
+            where (Optional[Dict[str, Any]]): Column values the copied rows must all
+                match, None matching NULL. All the rows are copied by default.
+
+        Raises:
+            RuntimeError: If a name is not valid, or the tables do not have the same
+                number of columns or the copy fails.
+
+        Returns:
+            int: The number of copied rows.
+        """
+        ...
+
+    def truncate(self, table: str, reset_sequence: bool = False) -> int:
+        """
+        Delete all the rows of a table with a `DELETE` without `WHERE`, which SQLite
+        optimizes. Inside `begin()`/`commit()`, it is undone with the transaction.
+
+        Args:
+            table (str): The name of the table.
+            reset_sequence (bool): Also reset the AUTOINCREMENT counter of the table,
+                so the next id starts at 1 again.
+
+        Raises:
+            RuntimeError: If the table name is not valid or the deletion fails.
+
+        Returns:
+            int: The number of deleted rows.
+        """
+        ...
+
+
+    def total_changes(self) -> int:
+        """
+        Return the number of rows inserted, updated or deleted since the database
+        was opened, including the rows changed by triggers.
+
+        Raises:
+            RuntimeError: If the database is closed.
+        """
+        ...
+
+
+    def explain(self, query: str, params: Params) -> List[Dict[str, int | str]]:
+        """
+        Return the query plan of a query with `EXPLAIN QUERY PLAN`, e.g. to check
+        that an index is used. The query itself is not run.
+
+        Args:
+            query (str): The SQL query to explain.
+            params (Params): The parameters to pass to the query.
+
+        Raises:
+            RuntimeError: If the query is not valid.
+
+        Returns:
+            List[Dict[str, int | str]]: The steps of the plan, with the keys
+                "id", "parent", "notused" and "detail".
+        """
+        ...
+
+    def list_foreign_keys(self, table: str) -> List[Dict[str, Any]]:
+        """
+        Return the foreign keys of a table, from `PRAGMA foreign_key_list`.
+
+        Args:
+            table (str): The name of the table.
+
+        Raises:
+            RuntimeError: If the table name is not valid or the table does not exist.
+
+        Returns:
+            List[Dict[str, Any]]: One dict per key, with the keys "referenced_table",
+                "columns", "referenced_columns" (None for the columns of a key pointing
+                to the PRIMARY KEY without naming them), "on_update", "on_delete" and
+                "match".
+        """
+        ...
+
+    def foreign_key_check(self, table: Optional[str] = None) -> List[Dict[str, Any]]:
+        """
+        Return the rows violating a foreign key constraint, from `PRAGMA foreign_key_check`,
+        e.g. after a bulk load done with the foreign keys disabled.
+
+        Args:
+            table (Optional[str]): The table to check, all the tables by default.
+
+        Raises:
+            RuntimeError: If the table name is not valid or the table does not exist.
+
+        Returns:
+            List[Dict[str, Any]]: One dict per violation, with the keys "table", "rowid"
+                (None for a WITHOUT ROWID table), "referenced_table" and "fk_index" (the
+                position of the failing key in `list_foreign_keys(table)`).
+        """
+        ...
+
+
+    def fetch_value(self, query: str, params: Params, row: int = 0, column: int = 0) -> Any:
+        """
+        Run a SELECT query and return a single cell as a native Python value,
+        without building the rest of the results.
+
+        Args:
+            query (str): The SQL query to execute.
+            params (Params): The parameters to pass to the query.
+            row (int): The 0-based index of the row. Defaults to the first one.
+            column (int): The 0-based index of the column. Defaults to the first one.
+
+        Raises:
+            IndexError: If the query returns fewer columns.
+            RuntimeError: If the query execution fails.
+
+        Returns:
+            Any: The value (int, float, str, bytes or None), or None if the query returns fewer rows.
+        """
+        ...
+
+
+    def fetch_columns(
+        self, query: str, params: Params, json_columns: List[str] = [], typed: bool = False
+    ) -> Dict[str, List[Any]]:
+        """
+        Run a SELECT query and return the results by column, e.g. to build a DataFrame
+        with `pandas.DataFrame(db.fetch_columns(...))`. The row factory is not used.
+
+        Args:
+            query (str): The SQL query to execute.
+            params (Params): The parameters to pass to the query.
+            json_columns (List[str]): Columns parsed back into Python objects with `json.loads`.
+            typed (bool): Convert the values from the declared type of their column,
+                see `fetch_all()`.
+
+        Raises:
+            RuntimeError: If the query execution fails.
+
+        Returns:
+            Dict[str, List[Any]]: The column names and their values, converted like in `fetch_all()`.
+        """
+        ...
+
+
+    def set_trace(self, callback: Optional[Callable[[str], Any]]) -> None:
+        """
+        Call `callback(sql)` with the SQL of each statement before it runs, its parameters
+        replaced by their values. The callback must not use the database itself, and its
+        exceptions are reported with `sys.unraisablehook` instead of stopping the query.
+
+        Args:
+            callback (Optional[Callable[[str], Any]]): The callable, or None to disable tracing.
+
+        Raises:
+            RuntimeError: If the database is closed.
+        """
+        ...
+
+
+    def interrupt(self) -> None:
+        """
+        Cancel the query running on this database, e.g. from a watchdog thread.
+        The query then raises an OperationalError. Does nothing if no query is running.
+        The GIL is released while `fetch_all()` and `execute_raw_query()` run,
+        so other Python threads can call it during these queries.
+        """
+        ...
+
+
+    def set_performance_profile(self, profile: Literal["safe", "fast", "bulk"]) -> str:
+        """
+        Apply a coherent set of pragmas trading durability for speed.
+
+        - "safe": `journal_mode=DELETE`, `synchronous=FULL`, 2 MB cache (SQLite's defaults).
+          Committed transactions survive a crash or a power loss.
+        - "fast": `journal_mode=WAL`, `synchronous=NORMAL`, 64 MB cache. The database cannot
+          be corrupted, but the last transactions can be lost on a power loss.
+        - "bulk": `journal_mode=WAL`, `synchronous=OFF`, 256 MB cache. A power loss or OS crash
+          can corrupt the database: only for initial loads that can be redone.
+
+        Args:
+            profile (str): "safe", "fast" or "bulk".
+
+        Raises:
+            RuntimeError: If the profile is not valid or a pragma cannot be applied,
+                e.g. inside a transaction.
+
+        Returns:
+            str: The journal mode actually used ("memory" for an in-memory database).
+        """
+        ...
+
+
+    def set_authorizer(
+        self,
+        callback: Optional[Callable[[int, Optional[str], Optional[str], Optional[str], Optional[str]], int]],
+    ) -> None:
+        """
+        Call `callback(action, arg1, arg2, db_name, trigger_or_view)` for each action of the
+        statements SQLite compiles, e.g. `(SQLITE_INSERT, "users", None, "main", None)`.
+        The callback returns SQLITE_OK to allow the action, SQLITE_DENY to make the statement
+        fail, or SQLITE_IGNORE to skip it (a column read then returns NULL). An exception
+        or any other return value denies the action and is reported with `sys.unraisablehook`.
+        The callback must not use the database itself.
+
+        Args:
+            callback (Optional[Callable]): The callable, or None to remove the authorizer.
+
+        Raises:
+            RuntimeError: If the database is closed.
+        """
+        ...
+
+
+    def set_commit_hook(self, callback: Optional[Callable[[], Any]]) -> None:
+        """
+        Call `callback()` each time a transaction is about to commit, including the implicit
+        transaction of a single statement. A true return value, or an exception, turns the
+        commit into a rollback and the statement raises an IntegrityError.
+        The callback must not use the database itself.
+
+        Args:
+            callback (Optional[Callable[[], Any]]): The callable, or None to remove the hook.
+
+        Raises:
+            RuntimeError: If the database is closed.
+        """
+        ...
+
+
+    def set_rollback_hook(self, callback: Optional[Callable[[], Any]]) -> None:
+        """
+        Call `callback()` each time a transaction is rolled back. Its exceptions are
+        reported with `sys.unraisablehook`.
+
+        Args:
+            callback (Optional[Callable[[], Any]]): The callable, or None to remove the hook.
+
+        Raises:
+            RuntimeError: If the database is closed.
+        """
+        ...
+
+
+    def set_update_hook(self, callback: Optional[Callable[[Literal["INSERT", "UPDATE", "DELETE"], str, int], Any]]) -> None:
+        """
+        Call `callback(operation, table, rowid)` for each row inserted, updated or deleted
+        on this connection, e.g. `("INSERT", "users", 1)`. The hook runs before the change
+        is committed and must not use the database. Its exceptions are reported with
+        `sys.unraisablehook`.
+
+        Args:
+            callback (Optional[Callable]): The callable, or None to remove the hook.
+
+        Raises:
+            RuntimeError: If the database is closed.
+        """
+        ...
+
+    def set_progress_handler(self, n_ops: int, callback: Optional[Callable[[], Any]]) -> None:
+        """
+        Call `callback()` about every `n_ops` virtual machine instructions while a query
+        runs. If it returns a true value or raises, the query stops with an
+        OperationalError ("interrupted"). The exceptions are reported with
+        `sys.unraisablehook`. The callback must not use the database.
+
+        Args:
+            n_ops (int): The number of instructions between two calls.
+            callback (Optional[Callable]): The callable, or None to remove the handler.
+
+        Raises:
+            RuntimeError: If `n_ops` is not positive or the database is closed.
+        """
+        ...
+
+
+    @staticmethod
+    def sqlite_version() -> str:
+        """
+        Return the version of the SQLite library bundled with the module, e.g. "3.47.0".
+        """
+        ...
+
+
+    @staticmethod
+    def sqlite_version_number() -> int:
+        """
+        Return the version of the SQLite library as a number, e.g. 3047000 for "3.47.0",
+        to check whether a feature is available.
+        """
+        ...
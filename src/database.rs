@@ -1,11 +1,48 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::CStr,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Deref,
+    os::raw::{c_char, c_int, c_uint, c_void},
+    panic::AssertUnwindSafe,
+    path::Path,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, MutexGuard, PoisonError, TryLockError,
+    },
+    time::{Duration, Instant},
+};
 
+use base64::prelude::{Engine, BASE64_STANDARD};
 use pyo3::{
-    exceptions::PyRuntimeError,
+    exceptions::{PyIndexError, PyRuntimeError},
     prelude::*,
-    types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple},
+    sync::GILOnceCell,
+    types::{
+        PyBool, PyByteArray, PyBytes, PyDate, PyDateTime, PyDict, PyFloat, PyFrozenSet, PyInt,
+        PyList, PyMemoryView, PySet, PyString, PyTime, PyTuple, PyType,
+    },
+};
+use rusqlite::{
+    backup::{Backup, StepResult},
+    fallible_iterator::FallibleIterator,
+    ffi,
+    functions::{Aggregate, Context, FunctionFlags},
+    hooks::Action,
+    params_from_iter,
+    serialize::OwnedData,
+    types::{Null, Value, ValueRef},
+    Batch, Connection, DatabaseName, ErrorCode, InterruptHandle, LoadExtensionGuard, OpenFlags,
+    OptionalExtension, ToSql,
+};
+
+use crate::errors::{
+    sqlite_error, DatabaseError, IntegrityError, OperationalError, QueryTimeout, RowLimitExceeded,
 };
-use rusqlite::{params_from_iter, Connection, ToSql};
 
 // https://doc.rust-lang.org/stable/book/
 // https://pyo3.rs/v0.23.4/types.html
@@ -13,337 +50,5525 @@ use rusqlite::{params_from_iter, Connection, ToSql};
 // We create the database class
 #[pyclass]
 struct Database {
-    connection: Arc<Mutex<Connection>>, // Connection is async, it cannot be safely shared between Python threads.
-                                        // That's why we use Arc<Mutex<Connection>> to enforce sync
+    // Connection is async, it cannot be safely shared between Python threads.
+    // That's why we use Arc<Mutex<Connection>> to enforce sync.
+    // The Option is None once the database has been closed.
+    connection: Arc<Mutex<Option<Connection>>>,
+    // The path the database was opened with, kept for debugging and logging
+    path: String,
+    // Whether load_extension() is allowed, opt-in because extensions run native code
+    allow_extensions: bool,
+    // How the parameters that SQLite cannot store as they are get converted
+    param_options: ParamOptions,
+    // The Python type of the REAL values of typed fetches
+    reals: Reals,
+    // Optional callable shaping the fetched rows, called with (column_names, values)
+    #[pyo3(get, set)]
+    row_factory: Option<PyObject>,
+    // Interrupts the running query from any thread, without waiting for the connection lock
+    interrupt_handle: InterruptHandle,
+    // The callable registered with set_trace(). SQLite only keeps a raw pointer to it,
+    // so it is boxed to stay at the same address until the trace is cleared.
+    tracer: Option<Box<PyObject>>,
+    // The callable registered with set_authorizer(), boxed for the same reason
+    authorizer: Option<Box<PyObject>>,
+    // How many times a query failing with SQLITE_BUSY or SQLITE_LOCKED is run again,
+    // waiting retry_backoff_ms, then twice as long at each new attempt
+    max_retries: u32,
+    retry_backoff_ms: u64,
+    // The kind of parameters the queries accept, None accepts both
+    param_style: Option<ParamStyle>,
+    // Whether the last fetch_all() with max_rows and truncate=True left rows out. Atomic so
+    // the fetch methods can keep taking &self while a row factory uses the database.
+    last_fetch_truncated: AtomicBool,
 }
 
-#[pymethods]
-impl Database {
-    /// Method to instanciate a new database. We verify if path ends with the right extension
-    /// and we return the Database object with its connection
-    #[new]
-    #[pyo3(signature = (db_path = None))] // Using signature here because we use the Option<> type
-    fn new(db_path: Option<&str>) -> PyResult<Self> {
-        let db_path = match db_path {
-            Some(path) => path,
-            None => "database.sqlite",
-        };
+/// A locked connection that is guaranteed to be open, with the address of its mutex
+struct ConnectionGuard<'a>(MutexGuard<'a, Option<Connection>>, usize);
 
-        const ALLOWED_EXTENSIONS: [&str; 3] = [".sqlite", ".db", ".sql"];
+impl Deref for ConnectionGuard<'_> {
+    type Target = Connection;
 
-        // If db_path does not end by one of the allowed extensions
-        if !ALLOWED_EXTENSIONS
-            .iter()
-            .any(|ext| db_path.to_lowercase().ends_with(ext))
-        {
-            return Err(PyRuntimeError::new_err(format!(
-                "\"db_path\" must end with one of the following extensions: {:?}.\n\"{}\" is not correct.",
-                ALLOWED_EXTENSIONS.join(", "),
-                db_path
-            )));
+    fn deref(&self) -> &Connection {
+        // The guard is only created by lock_connection() after checking the connection is open
+        self.0.as_ref().expect("connection checked as open")
+    }
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        LOCKED_CONNECTIONS.with_borrow_mut(|locked| locked.retain(|&mutex| mutex != self.1));
+    }
+}
+
+thread_local! {
+    /// The connections locked by the current thread, see lock_open_connection()
+    static LOCKED_CONNECTIONS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The database opened when no path is given to Database()
+const DEFAULT_DB_PATH: &str = "database.sqlite";
+
+/// The column types allowed in a STRICT table
+const STRICT_TYPES: [&str; 6] = ["INT", "INTEGER", "REAL", "TEXT", "BLOB", "ANY"];
+
+/// The extensions accepted for the database path, more can be added with `extra_extensions`
+const ALLOWED_EXTENSIONS: [&str; 4] = [".sqlite", ".sqlite3", ".db", ".sql"];
+
+/// Numbers the savepoints of nested transactions, so each one has its own name
+static NEXT_SAVEPOINT: AtomicU64 = AtomicU64::new(0);
+
+/// What to do with NaN and infinite floats, see the `non_finite_floats` option of Database()
+#[derive(Clone, Copy)]
+enum NonFiniteFloats {
+    Error,
+    Null,
+    Text,
+}
+
+impl NonFiniteFloats {
+    fn parse(option: &str) -> PyResult<Self> {
+        match option {
+            "error" => Ok(Self::Error),
+            "null" => Ok(Self::Null),
+            "text" => Ok(Self::Text),
+            _ => Err(PyRuntimeError::new_err(format!(
+                "Invalid \"non_finite_floats\" option \"{}\". Expected \"error\", \"null\" or \"text\".",
+                option
+            ))),
+        }
+    }
+
+    /// Converts a float parameter, finite floats are stored as REAL
+    fn convert(self, value: f64) -> PyResult<Value> {
+        if value.is_finite() {
+            return Ok(Value::Real(value));
+        }
+
+        match self {
+            Self::Error => Err(PyRuntimeError::new_err(format!(
+                "Cannot store the float {} in SQLite. Use non_finite_floats=\"null\" or \"text\" to store it anyway.",
+                value
+            ))),
+            Self::Null => Ok(Value::Null),
+            // The same spelling as Python's repr(), so float() parses it back
+            Self::Text if value.is_nan() => Ok(Value::Text("nan".to_string())),
+            Self::Text if value > 0.0 => Ok(Value::Text("inf".to_string())),
+            Self::Text => Ok(Value::Text("-inf".to_string())),
         }
+    }
+}
 
-        // If for some reason we cannot open database, I map the SQLite
-        // error into a PyRuntimeError
-        let connection = Connection::open(db_path)
-            .map_err(|e| PyRuntimeError::new_err(format!("Failed to open DB: {}", e)))?;
+/// The kind of parameters the queries accept, see the `param_style` option of Database()
+#[derive(Clone, Copy)]
+enum ParamStyle {
+    // A list, a tuple or a single value, for `?` placeholders
+    Qmark,
+    // A dict, for `:name` placeholders
+    Named,
+}
 
-        Ok(Database {
-            connection: Arc::new(Mutex::new(connection)),
-        })
+impl ParamStyle {
+    fn parse(option: &str) -> PyResult<Self> {
+        match option {
+            "qmark" => Ok(Self::Qmark),
+            "named" => Ok(Self::Named),
+            _ => Err(PyRuntimeError::new_err(format!(
+                "Invalid \"param_style\" option \"{}\". Expected \"qmark\" or \"named\".",
+                option
+            ))),
+        }
     }
 
-    /// Creates a new table in the SQLite database by mapping some Python builtin types
-    /// to SQLite types.
-    fn create_table<'py>(
-        &self,
-        table_name: String,
-        dict_columns: &Bound<'py, PyDict>,
-    ) -> PyResult<usize> {
-        // We create the column definition that will be executed by the database engine.
-        // We iter() through the PyDict sent by Python and check if the column
-        // type is a valid python builtin type and is supported.
-        // A type returns class "type" so we use its attribute "__name__"
+    fn name(self) -> &'static str {
+        match self {
+            Self::Qmark => "qmark",
+            Self::Named => "named",
+        }
+    }
+}
 
-        let table_name_lowercase = table_name.to_lowercase();
-        let column_definitions: Vec<String> = dict_columns
-            .iter()
-            .map(|(column_name, column_type)| {
-                let column_type_name: String = column_type
-                    .getattr("__name__")
-                    .map_err(|_| {
-                        PyRuntimeError::new_err(format!(
-                            "Wrong type for the creation of the table \"{}\". Allowed types are valid Python builtin types: str, int, float, and bool.",
-                            table_name
-                        ))
-                    })?
+/// What to do with Python ints that do not fit in SQLite's 64-bit integers,
+/// see the `large_ints` option of Database()
+#[derive(Clone, Copy)]
+enum LargeInts {
+    Error,
+    Text,
+}
+
+impl LargeInts {
+    fn parse(option: &str) -> PyResult<Self> {
+        match option {
+            "error" => Ok(Self::Error),
+            "text" => Ok(Self::Text),
+            _ => Err(PyRuntimeError::new_err(format!(
+                "Invalid \"large_ints\" option \"{}\". Expected \"error\" or \"text\".",
+                option
+            ))),
+        }
+    }
+
+    /// Converts an int parameter, the ones that fit in 64 bits are stored as INTEGER
+    fn convert(self, item: &Bound<'_, PyAny>) -> PyResult<Value> {
+        if let Ok(value) = item.extract::<i64>() {
+            return Ok(Value::Integer(value));
+        }
+
+        match self {
+            Self::Error => Err(PyRuntimeError::new_err(format!(
+                "Cannot store the integer {} in SQLite, which only stores integers from {} to {}. Use large_ints=\"text\" to store it as TEXT.",
+                item,
+                i64::MIN,
+                i64::MAX
+            ))),
+            // Stored as its decimal digits, so int() converts it back
+            Self::Text => Ok(Value::Text(item.str()?.to_string())),
+        }
+    }
+}
+
+/// What to do with Python strings holding lone surrogates, which cannot be encoded in
+/// UTF-8, see the `surrogates` option of Database()
+#[derive(Clone, Copy)]
+enum Surrogates {
+    Error,
+    Replace,
+    Blob,
+}
+
+impl Surrogates {
+    fn parse(option: &str) -> PyResult<Self> {
+        match option {
+            "error" => Ok(Self::Error),
+            "replace" => Ok(Self::Replace),
+            "blob" => Ok(Self::Blob),
+            _ => Err(PyRuntimeError::new_err(format!(
+                "Invalid \"surrogates\" option \"{}\". Expected \"error\", \"replace\" or \"blob\".",
+                option
+            ))),
+        }
+    }
+
+    /// Converts a str parameter, the ones that are valid UTF-8 are stored as TEXT
+    fn convert(self, item: &Bound<'_, PyString>) -> PyResult<Value> {
+        if let Ok(text) = item.to_str() {
+            return Ok(Value::Text(text.to_string()));
+        }
+
+        match self {
+            Self::Error => Err(PyRuntimeError::new_err(format!(
+                "Cannot store the string {} in SQLite, it contains a lone surrogate which cannot be encoded in UTF-8. Use surrogates=\"replace\" or \"blob\" to store it anyway.",
+                item.repr()?
+            ))),
+            // Each surrogate becomes one U+FFFD. Decoded from UTF-16, since the lossy
+            // UTF-8 decoding would give one per byte of the surrogate.
+            Self::Replace => {
+                let bytes: Vec<u8> = item
+                    .call_method1("encode", ("utf-16-le", "surrogatepass"))?
                     .extract()?;
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .collect();
+                Ok(Value::Text(String::from_utf16_lossy(&units)))
+            }
+            // Stored with the surrogates encoded as is, decode("utf-8", "surrogatepass")
+            // gives the same string back
+            Self::Blob => Ok(Value::Blob(
+                item.call_method1("encode", ("utf-8", "surrogatepass"))?
+                    .extract()?,
+            )),
+        }
+    }
+}
 
-                let sql_type_mapping = match column_type_name.as_str() {
-                    "str" => "TEXT",
-                    "int" => "INTEGER",
-                    "float" => "REAL",
-                    "bool" => "BOOLEAN",
-                    _ => {
-                        return Err(PyRuntimeError::new_err(format!(
-                            "Wrong type for the creation of the table \"{}\". Allowed types are valid Python builtin types: str, int, float, and bool.",
-                            table_name
-                        )));
-                    }
-                };
+/// How the parameters that SQLite cannot store as they are get converted,
+/// set by the options of Database()
+#[derive(Clone, Copy)]
+struct ParamOptions {
+    non_finite_floats: NonFiniteFloats,
+    large_ints: LargeInts,
+    surrogates: Surrogates,
+}
 
-                // Return the formatted column definition
-                Ok(format!("{} {}", column_name, sql_type_mapping))
-            })
-            // After generating the string we collect it in the vector
-            .collect::<PyResult<Vec<String>>>()?;
+impl Default for ParamOptions {
+    fn default() -> Self {
+        ParamOptions {
+            non_finite_floats: NonFiniteFloats::Error,
+            large_ints: LargeInts::Error,
+            surrogates: Surrogates::Error,
+        }
+    }
+}
 
-        let columns = column_definitions.join(", ");
-        let sql = format!(
-            "CREATE TABLE IF NOT EXISTS {} ({})",
-            table_name_lowercase, columns
-        );
+/// The Python type of the REAL values returned by the typed fetches,
+/// see the `reals` option of Database()
+#[derive(Clone, Copy)]
+enum Reals {
+    Float,
+    Decimal,
+    Text,
+}
 
-        // Finally we execute the query to create the table if it doesn't exist.
-        Ok(self.__execute(sql, None)?)
+impl Reals {
+    fn parse(option: &str) -> PyResult<Self> {
+        match option {
+            "float" => Ok(Self::Float),
+            "decimal" => Ok(Self::Decimal),
+            "text" => Ok(Self::Text),
+            _ => Err(PyRuntimeError::new_err(format!(
+                "Invalid \"reals\" option \"{}\". Expected \"float\", \"decimal\" or \"text\".",
+                option
+            ))),
+        }
     }
 
-    fn insert<'py>(&self, table: String, values: &Bound<'py, PyDict>) -> PyResult<usize> {
-        // Extract column names and values from the dictionary
-        let columns: Vec<String> = values
-            .keys()
-            .iter()
-            .map(|k| k.extract::<String>().unwrap())
-            .collect();
+    /// Converts a REAL value. The text is the shortest one reading back as the same
+    /// double, without exponent, e.g. "0.1" and not 0.1000000000000000055511151231257827.
+    fn convert(self, py: Python<'_>, value: f64) -> PyResult<PyObject> {
+        Ok(match self {
+            Self::Float => value.into_pyobject(py)?.into_any().unbind(),
+            Self::Decimal => py
+                .import("decimal")?
+                .getattr("Decimal")?
+                .call1((value.to_string(),))?
+                .unbind(),
+            Self::Text => PyString::new(py, &value.to_string()).into_any().unbind(),
+        })
+    }
+}
 
-        let values_vec: Vec<String> = values
-            .values()
+/// Lets the values of a query cross py.allow_threads(), which requires Send.
+/// The closure runs on the same thread with the GIL released, so the values are
+/// never actually shared with another thread.
+struct WithoutGil<T>(T);
+
+unsafe impl<T> Send for WithoutGil<T> {}
+
+impl<T> WithoutGil<T> {
+    /// Unwraps the value. Taking self makes closures capture the whole wrapper,
+    /// and not only the non-Send field.
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// The converted values of each row of a query, before the row factory is applied
+type Rows = Vec<Vec<PyObject>>;
+
+/// The options of the fetch methods, see fetch_all()
+#[derive(Default)]
+struct FetchOptions<'a> {
+    json_columns: &'a [String],
+    // (column name, callable) pairs, see fetch_all()
+    converters: &'a [(String, PyObject)],
+    timeout_ms: Option<u64>,
+    typed: bool,
+    // At most this many rows per run, see fetch_all()
+    max_rows: Option<usize>,
+    truncate: bool,
+}
+
+/// How fetch_rows() converts the values of a column
+enum ColumnKind {
+    Plain,
+    Bool,
+    Json,
+    // The index of the column's converter in FetchOptions::converters
+    Converted(usize),
+    // With typed=True, the values are converted from the declared type of the column,
+    // following SQLite's type affinity rules: https://www.sqlite.org/datatype3.html
+    Integer,
+    Real,
+    Text,
+    Native,
+}
+
+impl ColumnKind {
+    fn from_decl_type(decl_type: Option<&str>, typed: bool) -> Self {
+        let decl_type = decl_type.unwrap_or_default().to_uppercase();
+
+        // BOOLEAN columns store 0/1 integers, we detect them from their declared type
+        // so they can be returned as Python booleans
+        if matches!(decl_type.as_str(), "BOOLEAN" | "BOOL") {
+            ColumnKind::Bool
+        } else if !typed {
+            ColumnKind::Plain
+        } else if decl_type.contains("INT") {
+            ColumnKind::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
             .iter()
-            .map(|v| {
-                if let Ok(s) = v.extract::<String>() {
-                    Ok(s)
-                } else if let Ok(i) = v.extract::<i64>() {
-                    Ok(format!("{}", i))
-                } else if let Ok(f) = v.extract::<f64>() {
-                    Ok(format!("{}", f))
-                } else if let Ok(b) = v.extract::<bool>() {
-                    Ok(format!("{}", if b { 1 } else { 0 }))
-                } else {
-                    Err(PyRuntimeError::new_err(format!(
-                        "Unsupported type for \"{}\". Supported types are: str, int, bool, float.",
-                        v
-                    )))
-                }
+            .any(|t| decl_type.contains(t))
+        {
+            ColumnKind::Text
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| decl_type.contains(t))
+        {
+            ColumnKind::Real
+        } else {
+            // BLOB, NUMERIC, or an expression without a declared type
+            ColumnKind::Native
+        }
+    }
+}
+
+/// Locks the mutex of a shared connection. When another thread holds it, the wait is done
+/// with the GIL released: that thread may be running a query without the GIL that calls
+/// back into Python (a function, a hook...) and needs the GIL to finish. The GIL is thus
+/// never held while waiting for the connection, which rules out a lock-order deadlock.
+///
+/// If another thread panicked while holding the lock, the mutex is "poisoned". The
+/// SQLite connection itself is still valid (SQLite rolls back any unfinished statement),
+/// so we recover the guard instead of refusing every following query.
+fn lock_mutex(connection: &Mutex<Option<Connection>>) -> MutexGuard<'_, Option<Connection>> {
+    match connection.try_lock() {
+        Ok(guard) => guard,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            let lock = || connection.lock().unwrap_or_else(PoisonError::into_inner);
+            // Safety: only reads the GIL state of the current thread
+            if unsafe { pyo3::ffi::PyGILState_Check() } == 1 {
+                Python::with_gil(|py| py.allow_threads(|| WithoutGil(lock())).into_inner())
+            } else {
+                lock()
+            }
+        }
+    }
+}
+
+/// Locks a shared connection, see Database::lock_connection()
+///
+/// SQLite runs the Python callbacks (functions, collations, hooks, the progress of
+/// backup()...) on the thread of the query, which holds the lock. A callback using the
+/// database would wait for that lock forever, so it raises an error instead.
+fn lock_open_connection(connection: &Mutex<Option<Connection>>) -> PyResult<ConnectionGuard<'_>> {
+    let mutex = connection as *const _ as usize;
+    if LOCKED_CONNECTIONS.with_borrow(|locked| locked.contains(&mutex)) {
+        return Err(DatabaseError::new_err(
+            "The database is in use by the query running this callback, a callback cannot use the database.",
+        ));
+    }
+    let guard = lock_mutex(connection);
+
+    if guard.is_none() {
+        return Err(DatabaseError::new_err(
+            "Cannot operate on a closed database.",
+        ));
+    }
+    LOCKED_CONNECTIONS.with_borrow_mut(|locked| locked.push(mutex));
+    Ok(ConnectionGuard(guard, mutex))
+}
+
+impl Database {
+    /// Wraps an open connection, with the default options of Database()
+    fn with_connection(connection: Connection, path: &str) -> Self {
+        Database {
+            interrupt_handle: connection.get_interrupt_handle(),
+            connection: Arc::new(Mutex::new(Some(connection))),
+            path: path.to_string(),
+            allow_extensions: false,
+            param_options: ParamOptions::default(),
+            reals: Reals::Float,
+            row_factory: None,
+            tracer: None,
+            authorizer: None,
+            max_retries: 0,
+            retry_backoff_ms: 50,
+            param_style: None,
+            last_fetch_truncated: AtomicBool::new(false),
+        }
+    }
+
+    /// Locks the connection for the current thread, or returns an error if the database is closed.
+    /// See lock_mutex() for how the wait and a poisoned lock are handled.
+    fn lock_connection(&self) -> PyResult<ConnectionGuard<'_>> {
+        lock_open_connection(&self.connection)
+    }
+
+    /// Executes a statement with its parameters, used by __execute(), insert() and copy_rows()
+    fn execute_values(&self, query: &str, values: Vec<Value>) -> PyResult<usize> {
+        // The GIL is released so that waiting for a busy database does not block
+        // the other Python threads, see retry_busy()
+        Python::with_gil(|py| {
+            py.allow_threads(|| {
+                let conn = self.lock_connection()?;
+                self.retry_busy(|| conn.execute(query, params_from_iter(values.iter())))
+                    .map_err(|e| sqlite_error("Failed to execute query", e))
             })
-            .collect::<Result<Vec<String>, PyErr>>()?;
+        })
+    }
 
-        let placeholders = vec!["?"; columns.len()].join(", ");
-        let sql = format!(
-            "INSERT INTO {} ({}) VALUES ({})",
-            table,
-            columns.join(", "),
-            placeholders
-        );
+    /// Converts a value given to insert(). The values are bound as text, NULL is only
+    /// produced for NaN and infinite floats with non_finite_floats="null" and BLOB for
+    /// strings with surrogates="blob".
+    fn insert_value(&self, v: &Bound<'_, PyAny>) -> PyResult<Value> {
+        if let Ok(s) = v.downcast::<PyString>() {
+            self.param_options.surrogates.convert(s)
+        } else if v.is_instance_of::<PyInt>() {
+            // An int too large for f64 would otherwise be stored rounded as a float
+            match self.param_options.large_ints.convert(v)? {
+                Value::Integer(i) => Ok(Value::Text(format!("{}", i))),
+                text => Ok(text),
+            }
+        } else if v.is_instance_of::<PyFloat>() {
+            self.insert_float(v.extract::<f64>()?)
+        } else if let Some(text) = datetime_text(v)? {
+            Ok(Value::Text(text))
+        } else if let Some(value) = enum_value(v)? {
+            // Enum members are stored as their value, NumPy scalars as a Python number
+            self.insert_value(&value)
+        } else if let Some(number) = number_value(v)? {
+            self.insert_value(&number)
+        } else if let Ok(f) = v.extract::<f64>() {
+            self.insert_float(f)
+        } else {
+            Err(PyRuntimeError::new_err(format!(
+                "Unsupported type for \"{}\". Supported types are: str, int, bool, float, datetime, date, time, enum.Enum.",
+                v
+            )))
+        }
+    }
 
-        Ok(self.__execute(sql, Some(values_vec))?)
+    /// Converts a float given to insert(), see insert_value()
+    fn insert_float(&self, f: f64) -> PyResult<Value> {
+        match self.param_options.non_finite_floats.convert(f)? {
+            Value::Real(f) => Ok(Value::Text(format!("{}", f))),
+            other => Ok(other),
+        }
     }
 
-    /// Executes a SQL query with the given parameters.
-    /// Accepts Python arguments
-    ///
-    /// # Arguments
-    /// * `query` - The SQL query string to execute
-    /// * `params` - A Python list or tuple containing query parameters
-    ///
-    /// # Returns
-    /// * `PyResult<()>` - Ok(()) on successful execution, or Err with a PyRuntimeError
-    ///
-    /// # Supported Parameter Types
-    /// * Integer (i64)
-    /// * Float (f64)
-    /// * String
-    /// * Boolean
+    /// Checks that the parameters of a query match `param_style`, if it is set
+    fn check_param_style(&self, params: &Bound<'_, PyAny>) -> PyResult<()> {
+        let named = params.is_instance_of::<PyDict>();
+        match self.param_style {
+            Some(ParamStyle::Named) if !named => Err(PyRuntimeError::new_err(
+                "This database uses param_style=\"named\": give the parameters as a dict, e.g. {\"name\": \"Ray\"} for \":name\".",
+            )),
+            Some(ParamStyle::Qmark) if named => Err(PyRuntimeError::new_err(
+                "This database uses param_style=\"qmark\": give the parameters as a list or a tuple for \"?\" placeholders, not a dict.",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `run` again while it fails because the database is busy or locked,
+    /// at most `max_retries` times with an exponential backoff.
     ///
-    /// # Examples
-    /// ```python
-    /// db.execute("INSERT INTO users (name, age) VALUES (?, ?)", ["John", 30])
-    /// db.execute("UPDATE users SET active = ? WHERE id = ?", (True, 1))
-    /// ```
-    fn execute_raw_query<'py>(&self, query: &str, params: &Bound<'py, PyAny>) -> PyResult<usize> {
-        // Convert Python list/tuple to Vec of PyAny
-        // Raise an error if it is neither
-        let params: Vec<Bound<'_, PyAny>> = match params.get_type().name()?.to_str()? {
-            "list" => params.downcast::<PyList>()?.iter().collect::<Vec<_>>(),
-            "tuple" => params.downcast::<PyTuple>()?.iter().collect::<Vec<_>>(),
-            _ => {
-                return Err(PyRuntimeError::new_err(
-                    "Unsupported parameter type. Expected a list or tuple.",
-                ));
+    /// It sleeps between attempts, so it must be called with the GIL released.
+    fn retry_busy<T>(&self, mut run: impl FnMut() -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+        let mut attempt = 0;
+        loop {
+            match run() {
+                Err(rusqlite::Error::SqliteFailure(failure, _))
+                    if attempt < self.max_retries
+                        && matches!(
+                            failure.code,
+                            ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked
+                        ) =>
+                {
+                    let backoff = self.retry_backoff_ms.saturating_mul(1 << attempt.min(16));
+                    std::thread::sleep(Duration::from_millis(backoff));
+                    attempt += 1;
+                }
+                result => return result,
             }
+        }
+    }
+
+    /// Runs a query, interrupting it from a timer thread if it takes more than `timeout_ms`.
+    /// An interrupted query raises QueryTimeout instead of the generic "interrupted" error.
+    ///
+    /// It must be called with the connection locked: the interrupt cancels whatever runs
+    /// on the connection, so the timer cannot start while another thread holds the lock.
+    fn with_timeout<T>(
+        &self,
+        timeout_ms: Option<u64>,
+        run: impl FnOnce() -> PyResult<T>,
+    ) -> PyResult<T> {
+        let Some(timeout_ms) = timeout_ms else {
+            return run();
         };
 
-        // Convert all parameters to SQL-compatible types
-        // Box<T> is a smart pointer that puts data on the heap rather than the stack.
-        //We need it here because:
-
-        // - Different parameter types have different sizes (String vs i64)
-        // - We need to store them in a Vec together
-
-        // dyn is used for dynamic dispatch with traits. In our case:
-
-        // ToSql is a trait implemented by various types (String, i64, etc.)
-        // dyn ToSql means "any type that implements ToSql"
-        // We need Box<dyn ToSql> to store different types that implement ToSql in our Vec
-        let sql_params: Vec<Box<dyn ToSql>> = params
-            .iter() // Iterate over Python parameters
-            .map(|item| -> PyResult<Box<dyn ToSql>> {
-                // For each parameter, try to convert it to a SQL type:
-                if item.is_instance_of::<PyInt>() {
-                    // Python int -> Rust i64 -> Box<dyn ToSql>
-                    Ok(Box::new(item.extract::<i64>()?))
-                } else if item.is_instance_of::<PyFloat>() {
-                    // Python float -> Rust f64 -> Box<dyn ToSql>
-                    Ok(Box::new(item.extract::<f64>()?))
-                } else if item.is_instance_of::<PyString>() {
-                    // Python str -> Rust String -> Box<dyn ToSql>
-                    Ok(Box::new(item.extract::<String>()?))
-                } else if item.is_instance_of::<PyBool>() {
-                    // Python bool -> Rust bool -> Box<dyn ToSql>
-                    Ok(Box::new(item.extract::<bool>()?))
-                } else {
-                    // Unsupported type -> PyErr
-                    Err(PyRuntimeError::new_err(
-                        "Unsupported parameter type in query.",
-                    ))
+        let (finished, wait_finished) = mpsc::channel::<()>();
+        let timed_out = AtomicBool::new(false);
+
+        let (timed_out, interrupt_handle) = (&timed_out, &self.interrupt_handle);
+
+        let result = std::thread::scope(|scope| {
+            scope.spawn(move || {
+                // Dropping the sender when the query ends wakes the timer up early,
+                // the scope then joins the thread before returning
+                if let Err(RecvTimeoutError::Timeout) =
+                    wait_finished.recv_timeout(Duration::from_millis(timeout_ms))
+                {
+                    timed_out.store(true, Ordering::SeqCst);
+                    interrupt_handle.interrupt();
                 }
-            })
-            .collect::<PyResult<Vec<_>>>()?; // Collect into Result<Vec<Box<dyn ToSql>>>
-                                             // Final ? operator unwraps the PyResult
+            });
 
-        // Execute the query with thread-safe connection handling
-        // and return the result
-        Ok(self
-            .connection
-            .lock()
-            .map_err(|_| {
-                PyRuntimeError::new_err(
-                    "Failed to acquire database lock, another thread might use it.",
-                )
-            })?
-            .execute(query, params_from_iter(sql_params.iter()))
-            .map_err(|e| PyRuntimeError::new_err(format!("Failed to execute query: {}", e)))?)
+            let result = run();
+            drop(finished);
+            result
+        });
+
+        match result {
+            Err(_) if timed_out.load(Ordering::SeqCst) => Err(QueryTimeout::new_err(format!(
+                "The query was cancelled after {} ms.",
+                timeout_ms
+            ))),
+            result => result,
+        }
     }
 
-    fn fetch_all<'py>(
+    /// Runs a SELECT query and returns the column names and the rows.
+    /// Used by fetch_all() and the other fetch methods.
+    ///
+    /// Each row is a list of values, or what the row factory returns if one is set.
+    fn fetch_rows<'py>(
         &self,
+        py: Python<'py>,
         query: &str,
         params: &Bound<'py, PyAny>,
-    ) -> PyResult<Vec<Vec<String>>> {
-        // Convert Python list/tuple to Vec of PyAny
-        let params: Vec<Bound<'_, PyAny>> = match params.get_type().name()?.to_str()? {
-            "list" => params.downcast::<PyList>()?.iter().collect::<Vec<_>>(),
-            "tuple" => params.downcast::<PyTuple>()?.iter().collect::<Vec<_>>(),
-            _ => {
-                return Err(PyRuntimeError::new_err(
-                    "Unsupported parameter type. Expected a list or tuple.",
-                ));
-            }
+        options: FetchOptions<'_>,
+    ) -> PyResult<(Vec<String>, Vec<PyObject>)> {
+        let (column_names, rows) = self.fetch_values(py, query, params, options)?;
+        let rows = self.apply_row_factory(py, &column_names, rows)?;
+        Ok((column_names, rows))
+    }
+
+    /// Returns the columns and the values of rows given as dicts to insert_many() and
+    /// upsert_many(). All the dicts must have the same keys.
+    fn rows_values(&self, rows: &[Bound<'_, PyDict>]) -> PyResult<(Vec<String>, Vec<Vec<Value>>)> {
+        let Some(first) = rows.first() else {
+            return Ok((Vec::new(), Vec::new()));
         };
 
-        // Convert parameters to SQL types
-        let sql_params: Vec<Box<dyn ToSql>> = params
+        // The column order is taken from the first row, the values of the
+        // other rows are looked up by name so their key order does not matter
+        let columns: Vec<String> = first
+            .keys()
             .iter()
-            .map(|item| -> PyResult<Box<dyn ToSql>> {
-                if item.is_instance_of::<PyInt>() {
-                    Ok(Box::new(item.extract::<i64>()?))
-                } else if item.is_instance_of::<PyFloat>() {
-                    Ok(Box::new(item.extract::<f64>()?))
-                } else if item.is_instance_of::<PyString>() {
-                    Ok(Box::new(item.extract::<String>()?))
-                } else if item.is_instance_of::<PyBool>() {
-                    Ok(Box::new(item.extract::<bool>()?))
-                } else {
-                    Err(PyRuntimeError::new_err(
-                        "Unsupported parameter type in query.",
-                    ))
-                }
-            })
-            .collect::<PyResult<Vec<_>>>()?;
+            .map(|k| k.extract::<String>())
+            .collect::<PyResult<_>>()?;
 
-        let conn = self.connection.lock().map_err(|_| {
-            PyRuntimeError::new_err("Failed to acquire database lock, another thread might use it.")
-        })?;
+        let mut values = Vec::with_capacity(rows.len());
+        for (index, row) in rows.iter().enumerate() {
+            if row.len() != columns.len() {
+                return Err(PyRuntimeError::new_err(format!(
+                    "Row {} does not have the same columns as the first row: {:?}.",
+                    index, columns
+                )));
+            }
+            let row_values = columns
+                .iter()
+                .map(|column| match row.get_item(column)? {
+                    Some(value) => py_to_value(&value, self.param_options),
+                    None => Err(PyRuntimeError::new_err(format!(
+                        "Row {} does not have the same columns as the first row: {:?}.",
+                        index, columns
+                    ))),
+                })
+                .collect::<PyResult<Vec<Value>>>()?;
+            values.push(row_values);
+        }
+        Ok((columns, values))
+    }
 
-        let mut stmt = conn
-            .prepare(query)
-            .map_err(|e| PyRuntimeError::new_err(format!("Failed to prepare query: {}", e)))?;
+    /// Runs the UPDATE or DELETE of update() and delete(), and returns the number of changed
+    /// rows, or the `returning` columns of these rows when given.
+    /// With `dry_run`, returns the statement and its parameters instead, see dry_run_result().
+    fn change_rows<'py>(
+        &self,
+        py: Python<'py>,
+        mut sql: String,
+        params: &Bound<'py, PyList>,
+        returning: Option<&[String]>,
+        dry_run: bool,
+    ) -> PyResult<PyObject> {
+        if let Some(returning) = returning {
+            sql.push_str(&returning_clause(returning)?);
+        }
+        let values = self.helper_params(params)?;
+        if dry_run {
+            return dry_run_result(py, &sql, &values);
+        }
+        let (column_names, mut batches, changes) =
+            self.fetch_bound_batches(py, vec![(sql, values)], FetchOptions::default())?;
 
-        let column_count = stmt.column_count();
+        match returning {
+            Some(_) => {
+                let rows = batches.pop().unwrap_or_default();
+                Ok(
+                    PyList::new(py, self.apply_row_factory(py, &column_names, rows)?)?
+                        .into_any()
+                        .unbind(),
+                )
+            }
+            None => Ok(changes.into_pyobject(py)?.into_any().unbind()),
+        }
+    }
 
-        let rows: Vec<Vec<String>> = stmt
-            .query_map(
-                params_from_iter(sql_params.iter().map(|p| p.as_ref())),
-                |row| {
-                    let mut values = Vec::new();
-                    for i in 0..column_count {
-                        let value: rusqlite::types::Value = row.get(i)?;
-                        values.push(match value {
-                            rusqlite::types::Value::Integer(i) => i.to_string(),
-                            rusqlite::types::Value::Real(f) => f.to_string(),
-                            rusqlite::types::Value::Text(ref s) => s.clone(),
-                            rusqlite::types::Value::Blob(ref b) => format!("{:?}", b),
-                            rusqlite::types::Value::Null => "NULL".to_string(),
-                        });
-                    }
-                    Ok(values)
-                },
-            )
-            .map_err(|e| PyRuntimeError::new_err(format!("Query execution error: {}", e)))?
-            .collect::<Result<Vec<Vec<String>>, _>>()
-            .map_err(|e| PyRuntimeError::new_err(format!("Query execution error: {}", e)))?; // Collect Vec<Vec<String>>
+    /// Converts the parameters of a query built by a helper like select() or update().
+    /// Unlike in execute_raw_query(), a list is not expanded for an IN clause, the helper
+    /// wrote one placeholder per value, so it raises an error like in insert().
+    fn helper_params(&self, params: &Bound<'_, PyList>) -> PyResult<Vec<Value>> {
+        params
+            .iter()
+            .map(|param| py_to_value(&param, self.param_options))
+            .collect()
+    }
 
-        Ok(rows)
+    /// Turns converted rows into lists, or into what the row factory returns if one is set
+    fn apply_row_factory(
+        &self,
+        py: Python<'_>,
+        column_names: &[String],
+        rows: Rows,
+    ) -> PyResult<Vec<PyObject>> {
+        match &self.row_factory {
+            Some(factory) => {
+                let columns = PyList::new(py, column_names)?;
+                rows.into_iter()
+                    .map(|row| factory.call1(py, (&columns, PyList::new(py, row)?)))
+                    .collect()
+            }
+            None => rows
+                .into_iter()
+                .map(|row| Ok(PyList::new(py, row)?.into_any().unbind()))
+                .collect(),
+        }
     }
 
-    //// INTERNALS ////
+    /// Runs a SELECT query and returns the column names and the converted values
+    /// of each row, without applying the row factory.
+    fn fetch_values<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        options: FetchOptions<'_>,
+    ) -> PyResult<(Vec<String>, Rows)> {
+        let (column_names, mut batches, _) =
+            self.fetch_value_batches(py, query, std::slice::from_ref(params), options)?;
+        Ok((column_names, batches.pop().unwrap_or_default()))
+    }
 
-    /// Method to execute queries. Used inside the create_table() and insert() methods
-    #[pyo3(signature = (query, values=None))]
-    fn __execute(&self, query: String, values: Option<Vec<String>>) -> PyResult<usize> {
-        match values {
-            Some(vals) => {
-                let values: Vec<&dyn rusqlite::ToSql> =
-                    vals.iter().map(|v| v as &dyn rusqlite::ToSql).collect();
-
-                Ok(self
-                    .connection
-                    .lock()
-                    .map_err(|_| {
-                        PyRuntimeError::new_err(
-                            "Failed to acquire database lock, another thread might use it.",
-                        )
-                    })?
-                    .execute(&query, params_from_iter(values))
-                    .map_err(|e| {
-                        PyRuntimeError::new_err(format!("Failed to execute query: {}", e))
-                    })?)
-            }
-            None => Ok(self
-                .connection
-                .lock()
-                .map_err(|_| {
-                    PyRuntimeError::new_err(
-                        "Failed to acquire database lock, another thread might use it.",
-                    )
-                })?
-                .execute(&query, [])
-                .map_err(|e| PyRuntimeError::new_err(format!("Failed to execute query: {}", e)))?),
-        }
+    /// Runs a SELECT query once per parameter set, holding the connection lock for all
+    /// of them, and returns the column names, the converted rows of each run and the
+    /// number of rows the runs inserted, updated or deleted (for DML with RETURNING).
+    fn fetch_value_batches<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        param_sets: &[Bound<'py, PyAny>],
+        options: FetchOptions<'_>,
+    ) -> PyResult<(Vec<String>, Vec<Rows>, u64)> {
+        // A list parameter expands into several placeholders, so each set can have its own SQL
+        let batches = param_sets
+            .iter()
+            .map(|params| query_params(query, params, self.param_options))
+            .collect::<PyResult<Vec<_>>>()?;
+        self.fetch_bound_batches(py, batches, options)
     }
-}
 
-#[pymodule]
-fn rust_sqlite_wrapper(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_class::<Database>()?;
-    Ok(())
+    /// Runs each query of `batches` with its converted parameters, like fetch_value_batches()
+    fn fetch_bound_batches<P: ToSql>(
+        &self,
+        py: Python<'_>,
+        batches: Vec<(String, Vec<P>)>,
+        options: FetchOptions<'_>,
+    ) -> PyResult<(Vec<String>, Vec<Rows>, u64)> {
+        let FetchOptions {
+            json_columns,
+            converters,
+            timeout_ms,
+            typed,
+            max_rows,
+            truncate,
+        } = options;
+
+        if let Some((column, _)) = converters.iter().find(|(_, c)| !c.bind(py).is_callable()) {
+            return Err(PyRuntimeError::new_err(format!(
+                "The converter of the column \"{}\" must be a Python callable.",
+                column
+            )));
+        }
+        let batches = WithoutGil(batches);
+
+        // The GIL is released while SQLite runs the query, so other Python threads can run
+        // in the meantime, e.g. a watchdog calling interrupt()
+        let (column_names, kinds, batches, changes) = py.allow_threads(|| -> PyResult<_> {
+            let batches = batches.into_inner();
+            let conn = self.lock_connection()?;
+            self.with_timeout(timeout_ms, || {
+                let mut columns: Option<(Vec<String>, Vec<ColumnKind>)> = None;
+                let mut results = Vec::with_capacity(batches.len());
+                let mut changes = 0;
+                for (query, sql_params) in &batches {
+                    // Parsing the SQL is a significant part of the cost of small queries, so
+                    // the prepared statements are kept in the connection's cache (the 16 most
+                    // recently used ones) and reused when the same query runs again.
+                    let mut stmt = self
+                        .retry_busy(|| conn.prepare_cached(query))
+                        .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+                    check_param_count(stmt.parameter_count(), sql_params.len())?;
+
+                    if columns.is_none() {
+                        let column_names: Vec<String> = stmt
+                            .column_names()
+                            .iter()
+                            .map(|name| name.to_string())
+                            .collect();
+
+                        let mut kinds: Vec<ColumnKind> = stmt
+                            .columns()
+                            .iter()
+                            .map(|column| ColumnKind::from_decl_type(column.decl_type(), typed))
+                            .collect();
+
+                        let column_index = |column: &str, kind: &str| {
+                            column_names
+                                .iter()
+                                .position(|name| name == column)
+                                .ok_or_else(|| {
+                                    PyRuntimeError::new_err(format!(
+                                        "The {} column \"{}\" is not in the results of the query.",
+                                        kind, column
+                                    ))
+                                })
+                        };
+                        for json_column in json_columns {
+                            kinds[column_index(json_column, "JSON")?] = ColumnKind::Json;
+                        }
+                        for (converter, (column, _)) in converters.iter().enumerate() {
+                            kinds[column_index(column, "converted")?] =
+                                ColumnKind::Converted(converter);
+                        }
+                        columns = Some((column_names, kinds));
+                    }
+                    let column_count = stmt.column_count();
+
+                    let changes_before = conn.total_changes();
+
+                    // One row more than max_rows is read to know if the results were cut,
+                    // SQLite stops computing the others when the statement is reset
+                    let limit = max_rows.map_or(usize::MAX, |max_rows| max_rows.saturating_add(1));
+
+                    // A busy error can come from any step, so the whole query is run again
+                    let mut rows: Vec<Vec<Value>> = self
+                        .retry_busy(|| {
+                            stmt.query_map(params_from_iter(sql_params.iter()), |row| {
+                                (0..column_count).map(|column| row.get(column)).collect()
+                            })?
+                            .take(limit)
+                            .collect()
+                        })
+                        .map_err(|e| sqlite_error("Query execution error", e))?;
+
+                    // changes() keeps the count of the last INSERT, UPDATE or DELETE, so it
+                    // is only read if this statement changed rows, not after a SELECT
+                    if conn.total_changes() != changes_before {
+                        changes += conn.changes();
+                    }
+
+                    if let Some(max_rows) = max_rows.filter(|&max_rows| rows.len() > max_rows) {
+                        if !truncate {
+                            return Err(RowLimitExceeded::new_err(format!(
+                                "The query returned more than {} row(s).",
+                                max_rows
+                            )));
+                        }
+                        rows.truncate(max_rows);
+                        self.last_fetch_truncated.store(true, Ordering::SeqCst);
+                    }
+                    results.push(rows);
+                }
+
+                // The connection is released before converting the rows, so the row factory
+                // can use the database without waiting on the lock we hold
+                let (column_names, kinds) = columns.unwrap_or_default();
+                Ok((column_names, kinds, results, changes))
+            })
+        })?;
+
+        // The JSON columns are parsed with Python's json module, so the objects are the
+        // same as the ones json.loads() would give
+        let json_loads = if json_columns.is_empty() {
+            None
+        } else {
+            Some(py.import("json")?.getattr("loads")?)
+        };
+
+        let text = |s: &str| PyString::new(py, s).into_any().unbind();
+
+        let convert_row = |row: Vec<Value>| {
+            row.into_iter()
+                .zip(&kinds)
+                .map(|(value, kind)| {
+                    Ok(match (value, kind) {
+                        (Value::Integer(i), ColumnKind::Bool) => {
+                            PyBool::new(py, i != 0).to_owned().into_any().unbind()
+                        }
+                        (Value::Text(s), ColumnKind::Json) => match &json_loads {
+                            Some(loads) => loads.call1((s,))?.unbind(),
+                            None => text(&s),
+                        },
+                        (Value::Null, ColumnKind::Json) => py.None(),
+                        // Like in Python's sqlite3 module, the converters are not called for NULL
+                        (Value::Null, ColumnKind::Converted(_)) => py.None(),
+                        (value, ColumnKind::Converted(converter)) => converters[*converter]
+                            .1
+                            .call1(py, (value_ref_to_py(py, ValueRef::from(&value))?,))?,
+                        // Typed columns
+                        (Value::Integer(i), ColumnKind::Real) => {
+                            self.reals.convert(py, i as f64)?
+                        }
+                        (Value::Integer(i), ColumnKind::Text) => text(&i.to_string()),
+                        (Value::Real(f), ColumnKind::Text) => text(&f.to_string()),
+                        (Value::Real(f), _) if typed => self.reals.convert(py, f)?,
+                        (value, _) if typed => value_ref_to_py(py, ValueRef::from(&value))?,
+                        // Values as strings
+                        (Value::Integer(i), _) => text(&i.to_string()),
+                        (Value::Real(f), _) => text(&f.to_string()),
+                        (Value::Text(s), _) => text(&s),
+                        (Value::Blob(b), _) => PyBytes::new(py, &b).into_any().unbind(),
+                        (Value::Null, _) => text("NULL"),
+                    })
+                })
+                .collect::<PyResult<Vec<PyObject>>>()
+        };
+
+        let batches = batches
+            .into_iter()
+            .map(|rows| rows.into_iter().map(convert_row).collect())
+            .collect::<PyResult<Vec<Rows>>>()?;
+
+        Ok((column_names, batches, changes))
+    }
+}
+
+#[pymethods]
+impl Database {
+    /// Method to instanciate a new database. We verify if path ends with the right extension
+    /// and we return the Database object with its connection
+    ///
+    /// With `read_only=True` the database must already exist and any write attempt fails.
+    ///
+    /// If opening fails (e.g. a network mount that is not ready yet), it is retried
+    /// `connect_retries` times, waiting `connect_retry_delay_ms` between attempts.
+    ///
+    /// `allow_extensions=True` is required to use load_extension().
+    ///
+    /// With `uri=True`, `db_path` is an SQLite URI filename whose parameters are honored,
+    /// e.g. `file:data.db?mode=ro` or `file:shared?mode=memory&cache=shared`.
+    /// The extension check does not apply to URIs.
+    ///
+    /// `non_finite_floats` chooses what happens to NaN and infinite floats, which SQLite
+    /// cannot store reliably (a NaN REAL silently becomes NULL):
+    /// * "error" (default) - raise an error
+    /// * "null" - store NULL
+    /// * "text" - store the text "nan", "inf" or "-inf", fetched back as that string,
+    ///   which `float()` converts back
+    ///
+    /// `large_ints` chooses what happens to ints that do not fit in SQLite's 64-bit
+    /// integers (from -2**63 to 2**63 - 1), like big identifiers or cryptographic numbers:
+    /// * "error" (default) - raise an error
+    /// * "text" - store their digits as TEXT, which `int()` converts back. The columns
+    ///   with an INTEGER, REAL or NUMERIC type convert such TEXT to a rounded REAL
+    ///   (https://www.sqlite.org/datatype3.html), so the column should be TEXT.
+    ///
+    /// `surrogates` chooses what happens to the strings holding lone surrogates, e.g. file
+    /// names decoded by `os.fsdecode()`, which cannot be encoded in UTF-8:
+    /// * "error" (default) - raise an error showing the string
+    /// * "replace" - store them as TEXT with each surrogate replaced by U+FFFD
+    /// * "blob" - store their bytes as a BLOB, which `decode("utf-8", "surrogatepass")`
+    ///   converts back to the same string
+    ///
+    /// `reals` chooses the Python type of the REAL values returned by the typed fetches
+    /// (`typed=True`, fetch_value()), for reports where a float displaying with rounding
+    /// artifacts is not acceptable:
+    /// * "float" (default) - a float, the fastest
+    /// * "decimal" - a `decimal.Decimal` of the shortest text reading back as the same
+    ///   double, e.g. `Decimal("0.1")`, so sums in Python do not drift
+    /// * "text" - that text as a str
+    ///
+    /// Other naming conventions can be accepted with `extra_extensions`,
+    /// e.g. `Database("app.data", extra_extensions=[".data"])`.
+    ///
+    /// SQLite already waits up to 5 seconds for a locked database before failing with
+    /// "database is locked". With `max_retries`, execute_raw_query(), insert() and the fetch
+    /// methods then run the query again up to `max_retries` times, waiting
+    /// `retry_backoff_ms`, then twice as long before each new attempt (50, 100, 200 ms...).
+    ///
+    /// `cache_size` sets the page cache of the connection (`PRAGMA cache_size`), which
+    /// speeds up the queries reading the same pages again. Its unit depends on its sign:
+    /// * negative - a size in KiB, e.g. `-65536` for 64 MB
+    /// * positive - a number of pages, e.g. `2000` pages of 4 KiB (the default page size)
+    ///
+    /// SQLite's default is `-2000`, about 2 MB.
+    ///
+    /// `param_style` enforces one kind of parameters for the queries, so a list given
+    /// instead of a dict (or the reverse) fails with a clear error:
+    /// * "qmark" - a list, a tuple or a single value, for `?` placeholders
+    /// * "named" - a dict, for `:name` placeholders
+    ///
+    /// By default both are accepted. It can be changed later with `db.param_style`.
+    ///
+    /// `key` opens a database encrypted with SQLCipher, or encrypts a new one. It needs
+    /// the crate to be built with the `sqlcipher` feature, otherwise it raises an error.
+    ///
+    /// With `create_dirs=True`, the missing parent directories of `db_path` are created
+    /// before opening it, e.g. `data/` for `Database("data/app.sqlite", create_dirs=True)`.
+    /// It has no effect with `read_only=True` or `uri=True`.
+    #[new]
+    #[allow(clippy::too_many_arguments)] // Each one is a keyword argument in Python
+    #[pyo3(signature = (db_path = None, read_only = false, connect_retries = 0, connect_retry_delay_ms = 100, allow_extensions = false, extra_extensions = Vec::new(), non_finite_floats = "error", uri = false, max_retries = 0, retry_backoff_ms = 50, cache_size = None, param_style = None, large_ints = "error", key = None, reals = "float", surrogates = "error", create_dirs = false))] // Using signature here because we use the Option<> type
+    fn new(
+        py: Python<'_>,
+        db_path: Option<&str>,
+        read_only: bool,
+        connect_retries: u32,
+        connect_retry_delay_ms: u64,
+        allow_extensions: bool,
+        extra_extensions: Vec<String>,
+        non_finite_floats: &str,
+        uri: bool,
+        max_retries: u32,
+        retry_backoff_ms: u64,
+        cache_size: Option<i64>,
+        param_style: Option<&str>,
+        large_ints: &str,
+        key: Option<&str>,
+        reals: &str,
+        surrogates: &str,
+        create_dirs: bool,
+    ) -> PyResult<Self> {
+        let db_path = db_path.unwrap_or(DEFAULT_DB_PATH);
+        let non_finite_floats = NonFiniteFloats::parse(non_finite_floats)?;
+        let large_ints = LargeInts::parse(large_ints)?;
+        let reals = Reals::parse(reals)?;
+        let surrogates = Surrogates::parse(surrogates)?;
+        let param_style = param_style.map(ParamStyle::parse).transpose()?;
+
+        let allowed_extensions: Vec<String> = ALLOWED_EXTENSIONS
+            .iter()
+            .map(|ext| ext.to_string())
+            .chain(extra_extensions.iter().map(|ext| ext.to_lowercase()))
+            .collect();
+
+        // A URI ends with its parameters and can name an in-memory database,
+        // so only its "file:" scheme is checked. Both ways of opening below already
+        // pass SQLITE_OPEN_URI (it is part of rusqlite's default flags).
+        if uri && !db_path.starts_with("file:") {
+            return Err(PyRuntimeError::new_err(format!(
+                "With \"uri=True\", \"db_path\" must be a URI starting with \"file:\".\n\"{}\" is not correct.",
+                db_path
+            )));
+        }
+
+        // If db_path does not end by one of the allowed extensions
+        if !uri
+            && !allowed_extensions
+                .iter()
+                .any(|ext| db_path.to_lowercase().ends_with(ext.as_str()))
+        {
+            return Err(PyRuntimeError::new_err(format!(
+                "\"db_path\" must end with one of the following extensions: {:?}.\n\"{}\" is not correct.",
+                allowed_extensions.join(", "),
+                db_path
+            )));
+        }
+
+        // A read-only database must already exist, so its directory too
+        if create_dirs && !read_only && !uri {
+            if let Some(parent) = Path::new(db_path)
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(parent).map_err(|e| {
+                    PyRuntimeError::new_err(format!(
+                        "Failed to create the directory \"{}\": {}",
+                        parent.display(),
+                        e
+                    ))
+                })?;
+            }
+        }
+
+        let open = || {
+            if read_only {
+                Connection::open_with_flags(
+                    db_path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | OpenFlags::SQLITE_OPEN_URI
+                        | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )
+            } else {
+                Connection::open(db_path)
+            }
+        };
+
+        // If for some reason we cannot open database, we retry as many times as asked,
+        // then I map the SQLite error into a Python exception
+        let mut attempt = 0;
+        let connection = loop {
+            match open() {
+                Ok(connection) => break connection,
+                Err(_) if attempt < connect_retries => {
+                    attempt += 1;
+                    // We release the GIL while waiting so other Python threads can run
+                    py.allow_threads(|| {
+                        std::thread::sleep(std::time::Duration::from_millis(connect_retry_delay_ms))
+                    });
+                }
+                Err(e) => return Err(sqlite_error("Failed to open DB", e)),
+            }
+        };
+
+        // The key must be given before anything else reads the database
+        if let Some(key) = key {
+            check_encryption_support()?;
+            connection
+                .pragma_update(None, "key", key)
+                .map_err(|e| sqlite_error("Failed to set the encryption key", e))?;
+
+            // A wrong key is only detected when the database is first read
+            connection
+                .query_row("SELECT COUNT(*) FROM sqlite_schema", [], |_| Ok(()))
+                .map_err(|e| {
+                    sqlite_error("Failed to open the encrypted DB, is the key correct?", e)
+                })?;
+        }
+
+        if let Some(cache_size) = cache_size {
+            connection
+                .pragma_update(None, "cache_size", cache_size)
+                .map_err(|e| sqlite_error("Failed to set cache_size", e))?;
+        }
+
+        let mut db = Database::with_connection(connection, db_path);
+        db.allow_extensions = allow_extensions;
+        db.param_options = ParamOptions {
+            non_finite_floats,
+            large_ints,
+            surrogates,
+        };
+        db.reals = reals;
+        db.param_style = param_style;
+        db.max_retries = max_retries;
+        db.retry_backoff_ms = retry_backoff_ms;
+        Ok(db)
+    }
+
+    /// Opens an in-memory database holding a copy of `data`, the content of an SQLite file
+    /// (e.g. read from an asset or returned by serialize()). Nothing is written to disk,
+    /// the changes are lost when the database is closed.
+    ///
+    /// With `read_only=True`, any write attempt fails.
+    ///
+    /// # Examples
+    /// ```python
+    /// db = Database.from_bytes(Path("assets/cities.sqlite").read_bytes(), read_only=True)
+    /// copy = Database.from_bytes(db.serialize())
+    /// ```
+    #[staticmethod]
+    #[pyo3(signature = (data, read_only = false))]
+    fn from_bytes(data: &Bound<'_, PyAny>, read_only: bool) -> PyResult<Self> {
+        let data = blob_bytes(data)?.ok_or_else(|| {
+            PyRuntimeError::new_err("The data must be bytes, bytearray or memoryview.")
+        })?;
+
+        let mut connection =
+            Connection::open_in_memory().map_err(|e| sqlite_error("Failed to open DB", e))?;
+
+        // An empty file is an empty database, there is nothing to load
+        if !data.is_empty() {
+            // SQLite frees the buffer when the connection is closed, so it has to be
+            // allocated by SQLite itself
+            let buffer = unsafe { ffi::sqlite3_malloc64(data.len() as u64) } as *mut u8;
+            let buffer = NonNull::new(buffer)
+                .ok_or_else(|| PyRuntimeError::new_err("Not enough memory to load the data."))?;
+            // Safety: the buffer was just allocated with the size of the data
+            let owned = unsafe {
+                std::ptr::copy_nonoverlapping(data.as_ptr(), buffer.as_ptr(), data.len());
+                OwnedData::from_raw_nonnull(buffer, data.len())
+            };
+            connection
+                .deserialize(DatabaseName::Main, owned, read_only)
+                .map_err(|e| sqlite_error("Failed to load DB", e))?;
+
+            // The data is only read when it is first queried, so invalid data
+            // is detected here rather than by the first query of the caller
+            connection
+                .query_row("SELECT COUNT(*) FROM sqlite_schema", [], |_| Ok(()))
+                .map_err(|e| sqlite_error("Failed to load DB", e))?;
+        }
+
+        Ok(Database::with_connection(connection, ":memory:"))
+    }
+
+    /// The path the database was opened with, readable from Python as `db.path`
+    #[getter]
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The kind of parameters the queries accept: "qmark", "named", or None for both.
+    /// Readable and writable from Python as `db.param_style`.
+    #[getter]
+    fn param_style(&self) -> Option<&'static str> {
+        self.param_style.map(ParamStyle::name)
+    }
+
+    #[setter]
+    fn set_param_style(&mut self, param_style: Option<&str>) -> PyResult<()> {
+        self.param_style = param_style.map(ParamStyle::parse).transpose()?;
+        Ok(())
+    }
+
+    /// Shows the database path and state when printing the object,
+    /// e.g. `Database(path='users.sqlite', open=True)`
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let open = lock_mutex(&self.connection).is_some();
+
+        // We use the Python repr of the path so the quoting matches Python's
+        Ok(format!(
+            "Database(path={}, open={})",
+            PyString::new(py, &self.path).repr()?,
+            if open { "True" } else { "False" }
+        ))
+    }
+
+    /// Closes the connection. Any later operation raises an error.
+    /// Closing an already closed database does nothing.
+    fn close(&self) -> PyResult<()> {
+        let mut guard = lock_mutex(&self.connection);
+
+        if let Some(connection) = guard.take() {
+            // If SQLite refuses to close (e.g. unfinished statements), we keep the connection
+            if let Err((connection, e)) = connection.close() {
+                *guard = Some(connection);
+                return Err(sqlite_error("Failed to close DB", e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Allows using the database in a with-block: `with Database("x.db") as db:`
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Closes the database at the end of the with-block.
+    /// Exceptions raised inside the block are not suppressed.
+    #[pyo3(signature = (_exc_type = None, _exc_value = None, _traceback = None))]
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.close()?;
+        Ok(false)
+    }
+
+    /// Creates a new table in the SQLite database by mapping some Python builtin types
+    /// to SQLite types.
+    ///
+    /// A column type can also be a string, used verbatim as the SQL type,
+    /// e.g. `{"id": "INTEGER PRIMARY KEY", "data": "BLOB"}`.
+    ///
+    /// With `strict=True`, the table is a STRICT table: SQLite rejects the values that
+    /// do not match the type of their column instead of storing them as they are.
+    /// Only the types INT, INTEGER, REAL, TEXT, BLOB and ANY are allowed, so `bool`
+    /// cannot be used. With `without_rowid=True`, the rows are stored by their PRIMARY KEY,
+    /// which one of the columns must then declare.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.create_table("users", {"id": "INTEGER PRIMARY KEY", "name": str}, strict=True)
+    /// db.create_table("settings", {"key": "TEXT PRIMARY KEY", "value": str}, without_rowid=True)
+    /// ```
+    #[pyo3(signature = (table_name, dict_columns, without_rowid = false, strict = false))]
+    fn create_table<'py>(
+        &self,
+        table_name: String,
+        dict_columns: &Bound<'py, PyDict>,
+        without_rowid: bool,
+        strict: bool,
+    ) -> PyResult<usize> {
+        // We create the column definition that will be executed by the database engine.
+        // We iter() through the PyDict sent by Python and map each column type to its SQL type.
+
+        let table_name_lowercase = table_name.to_lowercase();
+        let column_definitions: Vec<String> = dict_columns
+            .iter()
+            .map(|(column_name, column_type)| {
+                let sql_type = sql_column_type(&column_type, &table_name)?;
+
+                // The type is the first word, the rest are constraints (e.g. PRIMARY KEY)
+                let type_name = sql_type.split_whitespace().next().unwrap_or("");
+                if strict && !STRICT_TYPES.contains(&type_name.to_uppercase().as_str()) {
+                    return Err(PyRuntimeError::new_err(format!(
+                        "The column \"{}\" of the STRICT table \"{}\" has the type \"{}\", allowed types are: {}.",
+                        column_name,
+                        table_name,
+                        type_name,
+                        STRICT_TYPES.join(", ")
+                    )));
+                }
+
+                // Return the formatted column definition
+                Ok(format!(
+                    "{} {}",
+                    quote_identifier(&column_name.extract::<String>()?),
+                    sql_type
+                ))
+            })
+            // After generating the string we collect it in the vector
+            .collect::<PyResult<Vec<String>>>()?;
+
+        let columns = column_definitions.join(", ");
+        let mut sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quote_identifier(&table_name_lowercase),
+            columns
+        );
+        let options: Vec<&str> = [(strict, "STRICT"), (without_rowid, "WITHOUT ROWID")]
+            .into_iter()
+            .filter_map(|(enabled, option)| enabled.then_some(option))
+            .collect();
+        if !options.is_empty() {
+            sql.push(' ');
+            sql.push_str(&options.join(", "));
+        }
+
+        // Finally we execute the query to create the table if it doesn't exist.
+        self.__execute(sql, None)
+    }
+
+    /// Creates a table from a Python class with type annotations (str, int, float, bool),
+    /// like a dataclass. The table name defaults to the class name.
+    ///
+    /// # Examples
+    /// ```python
+    /// class User:
+    ///     name: str
+    ///     age: int
+    ///
+    /// db.create_table_from_class(User)  # creates the table "user"
+    /// ```
+    #[pyo3(signature = (cls, table_name = None))]
+    fn create_table_from_class<'py>(
+        &self,
+        cls: &Bound<'py, PyAny>,
+        table_name: Option<String>,
+    ) -> PyResult<usize> {
+        let table_name = match table_name {
+            Some(name) => name,
+            None => cls.getattr("__name__")?.extract()?,
+        };
+
+        // typing.get_type_hints() also resolves the annotations written as strings
+        // (e.g. with `from __future__ import annotations`)
+        let annotations = cls
+            .py()
+            .import("typing")?
+            .call_method1("get_type_hints", (cls,))?
+            .downcast_into::<PyDict>()?;
+
+        if annotations.is_empty() {
+            return Err(PyRuntimeError::new_err(format!(
+                "The class used to create the table \"{}\" has no annotated attributes.",
+                table_name
+            )));
+        }
+
+        self.create_table(table_name, &annotations, false, false)
+    }
+
+    /// Renames a table with `ALTER TABLE ... RENAME TO`. The indexes, triggers and views
+    /// using the table are updated by SQLite.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.rename_table("users", "customers")
+    /// ```
+    fn rename_table(&self, old_name: &str, new_name: &str) -> PyResult<()> {
+        validate_identifier(old_name, "table")?;
+        validate_identifier(new_name, "table")?;
+
+        let sql = format!(
+            "ALTER TABLE {} RENAME TO {}",
+            quote_identifier(old_name),
+            quote_identifier(new_name)
+        );
+        self.lock_connection()?
+            .execute_batch(&sql)
+            .map_err(|e| sqlite_error(&format!("Failed to rename the table \"{}\"", old_name), e))
+    }
+
+    /// Adds a column to a table with `ALTER TABLE ... ADD COLUMN`. The type is given
+    /// like in create_table(): a Python builtin type or a SQL type as a string.
+    /// `constraints` is SQL appended after the type, e.g. `"NOT NULL DEFAULT 0"`.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.add_column("users", "email", str)
+    /// db.add_column("users", "score", int, constraints="NOT NULL DEFAULT 0")
+    /// ```
+    #[pyo3(signature = (table, column_name, column_type, constraints = None))]
+    fn add_column<'py>(
+        &self,
+        table: &str,
+        column_name: &str,
+        column_type: &Bound<'py, PyAny>,
+        constraints: Option<&str>,
+    ) -> PyResult<()> {
+        validate_identifier(table, "table")?;
+        validate_identifier(column_name, "column")?;
+
+        let mut sql = format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            quote_identifier(table),
+            quote_identifier(column_name),
+            sql_column_type(column_type, table)?
+        );
+        if let Some(constraints) = constraints.filter(|c| !c.trim().is_empty()) {
+            sql.push(' ');
+            sql.push_str(constraints);
+        }
+        // The constraints cannot add another statement
+        execute_single(&*self.lock_connection()?, &sql).map_err(|e| {
+            sqlite_error(
+                &format!(
+                    "Failed to add the column \"{}\" to \"{}\"",
+                    column_name, table
+                ),
+                e,
+            )
+        })
+    }
+
+    /// Removes a column from a table with `ALTER TABLE ... DROP COLUMN`, which needs
+    /// SQLite 3.35 or later. SQLite refuses to drop a column that is part of the
+    /// PRIMARY KEY, a UNIQUE constraint or an index.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.drop_column("users", "email")
+    /// ```
+    fn drop_column(&self, table: &str, column_name: &str) -> PyResult<()> {
+        validate_identifier(table, "table")?;
+        validate_identifier(column_name, "column")?;
+
+        if rusqlite::version_number() < 3_035_000 {
+            return Err(PyRuntimeError::new_err(format!(
+                "drop_column() needs SQLite 3.35 or later, this is SQLite {}. Rebuild the table instead: create a new table without the column, copy the rows with INSERT INTO ... SELECT, drop the old table and rename the new one.",
+                rusqlite::version()
+            )));
+        }
+
+        let sql = format!(
+            "ALTER TABLE {} DROP COLUMN {}",
+            quote_identifier(table),
+            quote_identifier(column_name)
+        );
+        self.lock_connection()?.execute_batch(&sql).map_err(|e| {
+            sqlite_error(
+                &format!(
+                    "Failed to drop the column \"{}\" of \"{}\"",
+                    column_name, table
+                ),
+                e,
+            )
+        })
+    }
+
+    /// Creates an index on `columns` of a table, to speed up the queries filtering or
+    /// sorting on them. The name defaults to `idx_<table>_<columns>`.
+    ///
+    /// A column can also be an expression, e.g. `"lower(email)"`, used by the queries
+    /// filtering on the same expression. An index with an expression needs a `name`.
+    ///
+    /// With `where`, the index is partial: only the rows matching the condition are
+    /// indexed, which keeps it small when the queries only look at a subset of the table,
+    /// e.g. `where="deleted_at IS NULL"`. Such queries must repeat the condition.
+    ///
+    /// The expressions and the condition are SQL, passed as they are.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.create_index("users", ["name", "age"])
+    /// db.create_index("users", ["email"], unique=True, where="deleted_at IS NULL")
+    /// db.create_index("users", ["lower(email)"], name="idx_users_email_nocase")
+    /// ```
+    #[pyo3(signature = (table, columns, name = None, unique = false, r#where = None, if_not_exists = true))]
+    fn create_index(
+        &self,
+        table: &str,
+        columns: Vec<String>,
+        name: Option<String>,
+        unique: bool,
+        r#where: Option<&str>,
+        if_not_exists: bool,
+    ) -> PyResult<()> {
+        validate_identifier(table, "table")?;
+        if columns.is_empty() {
+            return Err(PyRuntimeError::new_err(
+                "\"columns\" must contain at least one column or expression.",
+            ));
+        }
+
+        // Plain column names are quoted, anything else is an expression
+        let is_column = |column: &str| validate_identifier(column, "column").is_ok();
+        let name = match name {
+            Some(name) => name,
+            None if columns.iter().all(|c| is_column(c)) => {
+                format!("idx_{}_{}", table, columns.join("_"))
+            }
+            None => {
+                return Err(PyRuntimeError::new_err(
+                    "An index on an expression needs a \"name\".",
+                ))
+            }
+        };
+        validate_identifier(&name, "index")?;
+
+        let indexed: Vec<String> = columns
+            .iter()
+            .map(|c| {
+                if is_column(c) {
+                    quote_identifier(c)
+                } else {
+                    c.clone()
+                }
+            })
+            .collect();
+        let mut sql = format!(
+            "CREATE {}INDEX {}{} ON {} ({})",
+            if unique { "UNIQUE " } else { "" },
+            if if_not_exists { "IF NOT EXISTS " } else { "" },
+            quote_identifier(&name),
+            quote_identifier(table),
+            indexed.join(", ")
+        );
+        if let Some(condition) = r#where {
+            sql.push_str(" WHERE ");
+            sql.push_str(condition);
+        }
+
+        // An expression or the condition cannot add another statement
+        execute_single(&*self.lock_connection()?, &sql)
+            .map_err(|e| sqlite_error(&format!("Failed to create the index \"{}\"", name), e))
+    }
+
+    /// Inserts a row given as a dict of column values and returns the number of inserted rows.
+    ///
+    /// With `dry_run=True`, nothing is executed: the INSERT statement and its parameters
+    /// are returned as a tuple `(sql, params)`, to check what would run. update(), delete()
+    /// and select() have the same option.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.insert("users", {"name": "Ray", "age": 27})  # 1
+    /// db.insert("users", {"name": "Ray"}, dry_run=True)  # ('INSERT INTO "users" ("name") VALUES (?)', ['Ray'])
+    /// ```
+    #[pyo3(signature = (table, values, dry_run = false))]
+    fn insert<'py>(
+        &self,
+        py: Python<'py>,
+        table: String,
+        values: &Bound<'py, PyDict>,
+        dry_run: bool,
+    ) -> PyResult<PyObject> {
+        // Extract column names and values from the dictionary
+        let columns: Vec<String> = values
+            .keys()
+            .iter()
+            .map(|k| k.extract::<String>())
+            .collect::<PyResult<_>>()?;
+
+        let values_vec: Vec<Value> = values
+            .values()
+            .iter()
+            .map(|v| self.insert_value(&v))
+            .collect::<PyResult<Vec<Value>>>()?;
+
+        let sql = insert_sql(&table, &columns);
+
+        if dry_run {
+            return dry_run_result(py, &sql, &values_vec);
+        }
+        Ok(self
+            .execute_values(&sql, values_vec)?
+            .into_pyobject(py)?
+            .into_any()
+            .unbind())
+    }
+
+    /// Same as insert(), but the dict and list values are serialized to JSON strings
+    /// with `json.dumps`, to be queried with SQLite's JSON functions.
+    /// Other values are bound as they are, like in execute_raw_query().
+    ///
+    /// # Examples
+    /// ```python
+    /// db.insert_json("users", {"name": "Ray", "settings": {"theme": "dark"}})
+    /// db.fetch_all("SELECT name FROM users WHERE " + Database.json_extract("settings", "theme") + " = ?", ["dark"])
+    /// ```
+    fn insert_json<'py>(
+        &self,
+        py: Python<'py>,
+        table: String,
+        values: &Bound<'py, PyDict>,
+    ) -> PyResult<usize> {
+        let json_dumps = py.import("json")?.getattr("dumps")?;
+
+        let mut columns = Vec::with_capacity(values.len());
+        let mut sql_values = Vec::with_capacity(values.len());
+        for (column, value) in values.iter() {
+            columns.push(column.extract::<String>()?);
+            sql_values.push(
+                if value.is_instance_of::<PyDict>() || value.is_instance_of::<PyList>() {
+                    Value::Text(json_dumps.call1((value,))?.extract()?)
+                } else {
+                    py_to_value(&value, self.param_options)?
+                },
+            );
+        }
+
+        let sql = insert_sql(&table, &columns);
+
+        self.lock_connection()?
+            .prepare_cached(&sql)
+            .map_err(|e| sqlite_error("Failed to prepare query", e))?
+            .execute(params_from_iter(sql_values))
+            .map_err(|e| sqlite_error("Failed to execute query", e))
+    }
+
+    /// Builds a `json_extract(column, '$.path')` expression to use in a query.
+    /// The path can be given with or without the leading `$.`.
+    ///
+    /// # Examples
+    /// ```python
+    /// Database.json_extract("settings", "theme")       # json_extract(settings, '$.theme')
+    /// Database.json_extract("settings", "$.tags[0]")   # json_extract(settings, '$.tags[0]')
+    /// ```
+    #[staticmethod]
+    fn json_extract(column: &str, path: &str) -> String {
+        let path = if path.starts_with('$') {
+            path.to_string()
+        } else {
+            format!("$.{}", path)
+        };
+        // The path is a string literal, so its quotes are doubled
+        format!("json_extract({}, '{}')", column, path.replace('\'', "''"))
+    }
+
+    /// Quotes a table or column name to put it in an SQL query: it is wrapped in double
+    /// quotes and its double quotes are doubled, so it cannot end the identifier.
+    /// The insert and create_table() methods quote their names the same way.
+    ///
+    /// # Examples
+    /// ```python
+    /// Database.quote_identifier("order")      # "order"
+    /// Database.quote_identifier('a"b')    # "a""b"
+    /// ```
+    #[staticmethod]
+    #[pyo3(name = "quote_identifier")]
+    fn py_quote_identifier(name: &str) -> String {
+        quote_identifier(name)
+    }
+
+    /// Writes a Python value as an SQL literal: None, int, float, str, bool or bytes.
+    /// Strings are wrapped in single quotes and their single quotes doubled, bytes become
+    /// a blob literal like `X'00ff'`. Prefer parameters (`?`) whenever the query allows it.
+    ///
+    /// # Examples
+    /// ```python
+    /// Database.quote_literal("it's")  # 'it''s'
+    /// Database.quote_literal(None)    # NULL
+    /// ```
+    #[staticmethod]
+    fn quote_literal(value: &Bound<'_, PyAny>) -> PyResult<String> {
+        let value = py_to_value(value, ParamOptions::default())?;
+        Ok(sql_literal(ValueRef::from(&value)))
+    }
+
+    /// Executes a SQL query with the given parameters.
+    /// Accepts Python arguments
+    ///
+    /// # Arguments
+    /// * `query` - The SQL query string to execute
+    /// * `params` - A Python list or tuple containing query parameters, or a single value.
+    ///   A list, tuple or set inside it is expanded for IN clauses: `WHERE id IN (?)` with `[[1, 2]]`
+    ///
+    /// # Returns
+    /// * `PyResult<()>` - Ok(()) on successful execution, or Err with a PyRuntimeError
+    ///
+    /// # Supported Parameter Types
+    /// * Integer (i64)
+    /// * Float (f64)
+    /// * String
+    /// * Boolean
+    /// * Bytes (BLOB)
+    /// * None (NULL)
+    ///
+    /// # Examples
+    /// ```python
+    /// db.execute("INSERT INTO users (name, age) VALUES (?, ?)", ["John", 30])
+    /// db.execute("UPDATE users SET active = ? WHERE id = ?", (True, 1))
+    /// db.execute("DELETE FROM users WHERE id IN (?)", [[1, 2, 3]])
+    /// db.execute("DELETE FROM users WHERE id = ?", 4)
+    /// ```
+    ///
+    /// With `timeout_ms`, the query is cancelled if it runs longer and raises QueryTimeout.
+    #[pyo3(signature = (query, params, timeout_ms = None))]
+    fn execute_raw_query<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        timeout_ms: Option<u64>,
+    ) -> PyResult<usize> {
+        self.check_param_style(params)?;
+        // Convert the parameters to SQL-compatible types, see query_params()
+        let (query, sql_params) = query_params(query, params, self.param_options)?;
+
+        let sql_params = WithoutGil(sql_params);
+
+        // Execute the query with thread-safe connection handling
+        // and return the result.
+        // The prepared statement is cached and the GIL released, see fetch_values()
+        py.allow_threads(|| {
+            let sql_params = sql_params.into_inner();
+            let conn = self.lock_connection()?;
+            self.with_timeout(timeout_ms, || {
+                let mut stmt = self
+                    .retry_busy(|| conn.prepare_cached(&query))
+                    .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+                check_param_count(stmt.parameter_count(), sql_params.len())?;
+
+                self.retry_busy(|| stmt.execute(params_from_iter(sql_params.iter())))
+                    .map_err(|e| sqlite_error("Failed to execute query", e))
+            })
+        })
+    }
+
+    /// Runs several statements, each given as a tuple `(query, params)` with its own
+    /// parameters, in a single transaction: either all of them are applied or none.
+    /// Covers the writes that neither execute_raw_query() (one statement) nor
+    /// execute_script() (no parameters) can do atomically.
+    /// Returns the number of rows each statement inserted, updated or deleted.
+    ///
+    /// Like insert_many(), it runs in a savepoint, so inside begin()/commit() a failure
+    /// only undoes the statements of this call.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.execute_many_statements([
+    ///     ("UPDATE accounts SET balance = balance - ? WHERE id = ?", [amount, source]),
+    ///     ("UPDATE accounts SET balance = balance + ? WHERE id = ?", [amount, target]),
+    ///     ("INSERT INTO transfers (source, target, amount) VALUES (?, ?, ?)", [source, target, amount]),
+    /// ])  # [1, 1, 1]
+    /// ```
+    fn execute_many_statements<'py>(
+        &self,
+        py: Python<'py>,
+        statements: Vec<(String, Bound<'py, PyAny>)>,
+    ) -> PyResult<Vec<usize>> {
+        let statements = statements
+            .iter()
+            .map(|(query, params)| {
+                self.check_param_style(params)?;
+                query_params(query, params, self.param_options)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let statements = WithoutGil(statements);
+
+        // The GIL is released while the statements run, like in execute_raw_query()
+        py.allow_threads(|| {
+            let statements = statements.into_inner();
+            let conn = self.lock_connection()?;
+            in_savepoint(&conn, "execute_many_statements", || {
+                let mut changes = Vec::with_capacity(statements.len());
+                for (index, (query, sql_params)) in statements.iter().enumerate() {
+                    let mut stmt = conn.prepare_cached(query).map_err(|e| {
+                        sqlite_error(&format!("Failed to prepare statement {}", index), e)
+                    })?;
+                    check_param_count(stmt.parameter_count(), sql_params.len())?;
+                    changes.push(
+                        stmt.execute(params_from_iter(sql_params.iter()))
+                            .map_err(|e| sqlite_error(&format!("Statement {} failed", index), e))?,
+                    );
+                }
+                Ok(changes)
+            })
+        })
+    }
+
+    /// Runs a statement that both changes rows and returns data, like an
+    /// `UPDATE ... RETURNING`, and returns a tuple `(rows, changes)`: the rows like in
+    /// fetch_all() and the number of rows inserted, updated or deleted, like
+    /// execute_raw_query() returns. The statement runs only once.
+    ///
+    /// # Examples
+    /// ```python
+    /// rows, changes = db.execute_returning(
+    ///     "UPDATE users SET age = age + 1 WHERE age < ? RETURNING name, age", [18]
+    /// )  # ([["Tom", "13"]], 1)
+    /// ```
+    #[pyo3(signature = (query, params, typed = false))]
+    fn execute_returning<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        typed: bool,
+    ) -> PyResult<(Vec<PyObject>, u64)> {
+        self.check_param_style(params)?;
+        let options = FetchOptions {
+            typed,
+            ..Default::default()
+        };
+        let (column_names, mut batches, changes) =
+            self.fetch_value_batches(py, query, std::slice::from_ref(params), options)?;
+
+        let rows = batches.pop().unwrap_or_default();
+        Ok((self.apply_row_factory(py, &column_names, rows)?, changes))
+    }
+
+    /// Runs a script of several SQL statements separated by semicolons, without parameters,
+    /// and returns the number of rows they inserted, updated or deleted in total,
+    /// the rows changed by triggers included.
+    ///
+    /// The statements run in order and are not wrapped in a transaction: if one fails, the
+    /// previous ones stay applied. Put the script between BEGIN and COMMIT to make it atomic.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.execute_script("""
+    ///     UPDATE users SET age = age + 1;
+    ///     DELETE FROM users WHERE age > 120;
+    /// """)  # 42
+    /// ```
+    fn execute_script(&self, py: Python<'_>, sql: &str) -> PyResult<u64> {
+        py.allow_threads(|| {
+            let conn = self.lock_connection()?;
+
+            // execute_batch() does not report the changes, so they are counted
+            // with the connection's total before and after the script
+            let before = conn.total_changes();
+            conn.execute_batch(sql)
+                .map_err(|e| sqlite_error("Failed to execute script", e))?;
+            Ok(conn.total_changes() - before)
+        })
+    }
+
+    /// Runs a SELECT query and returns all the rows.
+    ///
+    /// Values are returned as strings (NULL as "NULL"), except the columns declared
+    /// as BOOLEAN (like the ones created from `bool` in create_table()) which are
+    /// returned as Python booleans, and BLOBs which are returned as `bytes`.
+    ///
+    /// The columns named in `json_columns` are parsed back into Python objects with
+    /// `json.loads`, and their NULLs are returned as None.
+    ///
+    /// If `row_factory` is set, each row is the result of `row_factory(column_names, values)`:
+    /// ```python
+    /// db.row_factory = lambda columns, values: dict(zip(columns, values))
+    /// db.fetch_all("SELECT name FROM users", [])  # [{"name": "Ray"}]
+    /// ```
+    ///
+    /// With `timeout_ms`, the query is cancelled if it runs longer and raises QueryTimeout.
+    ///
+    /// With `typed=True`, values are converted from the declared type of their column
+    /// (the one `PRAGMA table_info` shows): INTEGER columns give ints, REAL columns floats,
+    /// TEXT columns strings, and NULL gives None. Other columns, like expressions, keep the
+    /// type SQLite stored: int, float, str or bytes.
+    ///
+    /// `converters` maps column names to callables, called with the value SQLite stored
+    /// (int, float, str or bytes) and returning the value to put in the row instead.
+    /// NULL values are returned as None without calling the converter.
+    ///
+    /// `max_rows` guards against fetching a huge table by mistake: the rows are read until
+    /// one more than `max_rows` is found, then RowLimitExceeded is raised. With
+    /// `truncate=True`, the first `max_rows` rows are returned instead and
+    /// `db.last_fetch_truncated` tells if rows were left out.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.fetch_all("SELECT name, settings FROM users", [], json_columns=["settings"])
+    /// db.fetch_all("SELECT * FROM big_table", [], timeout_ms=5000)
+    /// db.fetch_all("SELECT name, age, is_underage FROM users", [], typed=True)  # [["Ray", 27, False]]
+    /// db.fetch_all("SELECT created_at FROM users", [], converters={"created_at": datetime.fromisoformat})
+    /// db.fetch_all("SELECT * FROM logs", [], max_rows=1000, truncate=True)
+    /// ```
+    #[pyo3(signature = (query, params, json_columns = Vec::new(), timeout_ms = None, typed = false, converters = HashMap::new(), max_rows = None, truncate = false))]
+    #[allow(clippy::too_many_arguments)] // Each option is a keyword argument in Python
+    fn fetch_all<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        json_columns: Vec<String>,
+        timeout_ms: Option<u64>,
+        typed: bool,
+        converters: HashMap<String, PyObject>,
+        max_rows: Option<usize>,
+        truncate: bool,
+    ) -> PyResult<Vec<PyObject>> {
+        self.check_param_style(params)?;
+        let converters: Vec<(String, PyObject)> = converters.into_iter().collect();
+        let options = FetchOptions {
+            json_columns: &json_columns,
+            converters: &converters,
+            timeout_ms,
+            typed,
+            max_rows,
+            truncate,
+        };
+        self.last_fetch_truncated.store(false, Ordering::SeqCst);
+        Ok(self.fetch_rows(py, query, params, options)?.1)
+    }
+
+    /// Whether the last fetch_all() called with `max_rows` and `truncate=True` returned
+    /// only part of the rows, readable from Python as `db.last_fetch_truncated`
+    #[getter]
+    fn last_fetch_truncated(&self) -> bool {
+        self.last_fetch_truncated.load(Ordering::SeqCst)
+    }
+
+    /// Returns a cursor over the rows of a SELECT query, read in batches with its
+    /// fetchmany(), like DB-API's. Each cursor has its own position, so several cursors
+    /// can be read at the same time, e.g. in nested loops.
+    ///
+    /// Note: a statement borrows the connection, so it cannot be kept open between calls
+    /// without locking the database for everyone else. Each fetchmany() runs the query
+    /// again with `LIMIT`/`OFFSET` for its batch instead, so only one batch is in memory.
+    /// Like any offset paging, rows inserted or deleted between two calls shift the
+    /// position, and the query needs an `ORDER BY` for the order to be stable.
+    ///
+    /// # Examples
+    /// ```python
+    /// cursor = db.cursor("SELECT * FROM events", [])
+    /// while rows := cursor.fetchmany(500):
+    ///     process(rows)
+    /// ```
+    fn cursor(slf: &Bound<'_, Self>, query: String, params: &Bound<'_, PyAny>) -> PyResult<Cursor> {
+        slf.borrow().check_param_style(params)?;
+        Ok(Cursor {
+            db: slf.clone().unbind(),
+            // The query becomes a subquery, which cannot end with a semicolon
+            query: query.trim_end().trim_end_matches(';').to_string(),
+            params: params.clone().unbind(),
+            offset: 0,
+            exhausted: false,
+        })
+    }
+
+    /// Runs the same SELECT query for each parameter set and returns one list of rows
+    /// per set, in the same order. The statement is prepared once and the connection
+    /// locked once, which is faster than calling fetch_all() in a loop for many lookups.
+    ///
+    /// The rows are converted like in fetch_all(), which takes the same options.
+    /// `timeout_ms` applies to the whole batch.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.fetch_all_batched("SELECT age FROM users WHERE name = ?", [["Ray"], ["Tom"]])
+    /// # [[["27"]], [["12"]]]
+    /// ```
+    #[pyo3(signature = (query, param_sets, json_columns = Vec::new(), timeout_ms = None, typed = false, converters = HashMap::new()))]
+    #[allow(clippy::too_many_arguments)] // Same keyword arguments as fetch_all()
+    fn fetch_all_batched<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        param_sets: Vec<Bound<'py, PyAny>>,
+        json_columns: Vec<String>,
+        timeout_ms: Option<u64>,
+        typed: bool,
+        converters: HashMap<String, PyObject>,
+    ) -> PyResult<Vec<Vec<PyObject>>> {
+        for params in &param_sets {
+            self.check_param_style(params)?;
+        }
+        let converters: Vec<(String, PyObject)> = converters.into_iter().collect();
+        let options = FetchOptions {
+            json_columns: &json_columns,
+            converters: &converters,
+            timeout_ms,
+            typed,
+            ..Default::default()
+        };
+        let (column_names, batches, _) =
+            self.fetch_value_batches(py, query, &param_sets, options)?;
+
+        batches
+            .into_iter()
+            .map(|rows| self.apply_row_factory(py, &column_names, rows))
+            .collect()
+    }
+
+    /// Same as fetch_all(), but returns a tuple `(column_names, rows)` so the
+    /// column order is known, e.g. to build a DataFrame or a CSV header.
+    #[pyo3(signature = (query, params, json_columns = Vec::new()))]
+    fn fetch_all_with_columns<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        json_columns: Vec<String>,
+    ) -> PyResult<(Vec<String>, Vec<PyObject>)> {
+        self.check_param_style(params)?;
+        let options = FetchOptions {
+            json_columns: &json_columns,
+            ..Default::default()
+        };
+        self.fetch_rows(py, query, params, options)
+    }
+
+    /// Runs a SELECT query and returns a single cell, at `row` and `column` (0-based),
+    /// as a native Python value: int, float, str, bytes or None. Returns None if the query
+    /// has fewer rows, and raises IndexError if it has fewer columns.
+    ///
+    /// Only the rows up to `row` are read, the rest of the results is never built.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.fetch_value("SELECT COUNT(*) FROM users", [])  # 2
+    /// db.fetch_value("SELECT name, age FROM users ORDER BY age", [], row=1, column=1)  # 27
+    /// ```
+    #[pyo3(signature = (query, params, row = 0, column = 0))]
+    fn fetch_value<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        row: usize,
+        column: usize,
+    ) -> PyResult<PyObject> {
+        self.check_param_style(params)?;
+        let (query, sql_params) = query_params(query, params, self.param_options)?;
+
+        let conn = self.lock_connection()?;
+        let mut stmt = conn
+            .prepare_cached(&query)
+            .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+        check_param_count(stmt.parameter_count(), sql_params.len())?;
+
+        if column >= stmt.column_count() {
+            return Err(PyIndexError::new_err(format!(
+                "Column index {} is out of range, the query returns {} column(s).",
+                column,
+                stmt.column_count()
+            )));
+        }
+
+        let mut rows = stmt
+            .query(params_from_iter(sql_params.iter()))
+            .map_err(|e| sqlite_error("Query execution error", e))?;
+
+        for _ in 0..row {
+            if rows
+                .next()
+                .map_err(|e| sqlite_error("Query execution error", e))?
+                .is_none()
+            {
+                return Ok(py.None());
+            }
+        }
+
+        match rows
+            .next()
+            .map_err(|e| sqlite_error("Query execution error", e))?
+        {
+            Some(found) => match found
+                .get_ref(column)
+                .map_err(|e| sqlite_error("Query execution error", e))?
+            {
+                ValueRef::Real(f) => self.reals.convert(py, f),
+                value => value_ref_to_py(py, value),
+            },
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Runs a SELECT query and returns the results by column, as a dict of
+    /// `column_name -> list of values`, e.g. to build a pandas or polars DataFrame
+    /// without transposing the rows in Python. Values are converted like in fetch_all(),
+    /// the row factory is not used.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.fetch_columns("SELECT name, age FROM users", [])  # {"name": ["Ray", "Tom"], "age": ["27", "12"]}
+    /// pandas.DataFrame(db.fetch_columns("SELECT * FROM users", [], typed=True))
+    /// ```
+    #[pyo3(signature = (query, params, json_columns = Vec::new(), typed = false))]
+    fn fetch_columns<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        json_columns: Vec<String>,
+        typed: bool,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        self.check_param_style(params)?;
+        let options = FetchOptions {
+            json_columns: &json_columns,
+            typed,
+            ..Default::default()
+        };
+        let (column_names, rows) = self.fetch_values(py, query, params, options)?;
+
+        let mut columns: Vec<Vec<PyObject>> = column_names
+            .iter()
+            .map(|_| Vec::with_capacity(rows.len()))
+            .collect();
+        for row in rows {
+            for (column, value) in columns.iter_mut().zip(row) {
+                column.push(value);
+            }
+        }
+
+        // With duplicate column names (e.g. a join), the last column wins like in a dict
+        let result = PyDict::new(py);
+        for (name, values) in column_names.iter().zip(columns) {
+            result.set_item(name, PyList::new(py, values)?)?;
+        }
+        Ok(result)
+    }
+
+    /// Returns the content of the database as `bytes`, the same bytes as its file
+    /// would have. Database.from_bytes() opens them again as an in-memory database.
+    ///
+    /// # Examples
+    /// ```python
+    /// data = db.serialize()
+    /// snapshot = Database.from_bytes(data)
+    /// ```
+    fn serialize<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let conn = self.lock_connection()?;
+        let data = conn
+            .serialize(DatabaseName::Main)
+            .map_err(|e| sqlite_error("Failed to serialize DB", e))?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Copies the live database into the file at `dest_path` using SQLite's online backup API.
+    /// Unlike copying the file on disk, this is safe while the database is in use.
+    ///
+    /// If a `progress` callable is given, it is called after each step with
+    /// `(remaining_pages, total_pages)`.
+    ///
+    /// The backup keeps the database locked from start to end, waits included: the other
+    /// threads using it wait for the backup to finish, and `progress` cannot use it (it
+    /// raises a DatabaseError if it tries).
+    ///
+    /// A step that finds the source or the destination busy or locked by another connection
+    /// is retried, with the GIL released while waiting. After `timeout_ms` without any
+    /// step succeeding, the backup fails with an OperationalError.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.backup("snapshot.sqlite")
+    /// db.backup("snapshot.sqlite", lambda remaining, total: print(f"{total - remaining}/{total}"))
+    /// ```
+    #[pyo3(signature = (dest_path, progress = None, pages_per_step = 100, timeout_ms = 5000))]
+    fn backup<'py>(
+        &self,
+        py: Python<'py>,
+        dest_path: &str,
+        progress: Option<&Bound<'py, PyAny>>,
+        pages_per_step: i32,
+        timeout_ms: u64,
+    ) -> PyResult<()> {
+        if pages_per_step <= 0 {
+            return Err(PyRuntimeError::new_err(
+                "\"pages_per_step\" must be a positive number.",
+            ));
+        }
+
+        let conn = self.lock_connection()?;
+
+        let mut dest = Connection::open(dest_path)
+            .map_err(|e| sqlite_error("Failed to open backup destination", e))?;
+        // The waits for a locked destination are done by the loop below, without the GIL
+        dest.busy_timeout(Duration::ZERO)
+            .map_err(|e| sqlite_error("Failed to open backup destination", e))?;
+
+        let backup =
+            Backup::new(&conn, &mut dest).map_err(|e| sqlite_error("Failed to start backup", e))?;
+
+        // We copy the pages step by step, so other connections can still use the source
+        // database in between. Busy or locked steps are retried after a short pause.
+        let timeout = Duration::from_millis(timeout_ms);
+        let mut busy_since: Option<Instant> = None;
+        loop {
+            let step = backup
+                .step(pages_per_step)
+                .map_err(|e| sqlite_error("Backup failed", e))?;
+
+            if let Some(callback) = progress {
+                let p = backup.progress();
+                callback.call1((p.remaining, p.pagecount))?;
+            }
+
+            match step {
+                StepResult::Done => return Ok(()),
+                StepResult::More => busy_since = None,
+                // Busy, Locked, or any state added by a future rusqlite version
+                _ => {
+                    if busy_since.get_or_insert_with(Instant::now).elapsed() >= timeout {
+                        return Err(OperationalError::new_err(format!(
+                            "Backup failed: the database stayed busy or locked for more than {} ms.",
+                            timeout_ms
+                        )));
+                    }
+                    py.allow_threads(|| std::thread::sleep(Duration::from_millis(10)));
+                }
+            }
+        }
+    }
+
+    /// Rebuilds the database file to reclaim the free pages left by deleted data.
+    ///
+    /// Note: VACUUM cannot run inside a transaction, so we check it beforehand
+    /// and return a clear error instead of SQLite's generic one.
+    fn vacuum(&self) -> PyResult<()> {
+        let conn = self.lock_connection()?;
+
+        if !conn.is_autocommit() {
+            return Err(PyRuntimeError::new_err(
+                "Cannot VACUUM while a transaction is open. Commit or rollback first.",
+            ));
+        }
+
+        conn.execute_batch("VACUUM")
+            .map_err(|e| sqlite_error("Failed to vacuum database", e))
+    }
+
+    /// Writes a compacted copy of the database to `dest_path` with `VACUUM INTO`,
+    /// leaving the live database untouched. Cheaper than backup() for a clean snapshot.
+    ///
+    /// Note: SQLite refuses to overwrite a file, so we check that `dest_path` does not
+    /// exist beforehand and return a clear error instead of SQLite's generic one.
+    fn vacuum_into(&self, dest_path: &str) -> PyResult<()> {
+        if Path::new(dest_path).exists() {
+            return Err(PyRuntimeError::new_err(format!(
+                "Cannot VACUUM INTO \"{}\": the file already exists.",
+                dest_path
+            )));
+        }
+
+        let conn = self.lock_connection()?;
+
+        if !conn.is_autocommit() {
+            return Err(PyRuntimeError::new_err(
+                "Cannot VACUUM while a transaction is open. Commit or rollback first.",
+            ));
+        }
+
+        conn.execute("VACUUM INTO ?1", [dest_path])
+            .map(|_| ())
+            .map_err(|e| sqlite_error("Failed to vacuum into the destination", e))
+    }
+
+    /// Refreshes the statistics used by the query planner to choose indexes.
+    fn analyze(&self) -> PyResult<()> {
+        self.lock_connection()?
+            .execute_batch("ANALYZE")
+            .map_err(|e| sqlite_error("Failed to analyze database", e))
+    }
+
+    /// Runs `PRAGMA integrity_check`.
+    /// Returns True if the database is healthy, otherwise the list of problems reported by SQLite.
+    fn integrity_check(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let conn = self.lock_connection()?;
+
+        let mut stmt = conn
+            .prepare("PRAGMA integrity_check")
+            .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+
+        let results: Vec<String> = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| sqlite_error("Query execution error", e))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| sqlite_error("Query execution error", e))?;
+
+        // A healthy database reports a single "ok" row
+        if results.len() == 1 && results[0] == "ok" {
+            Ok(PyBool::new(py, true).to_owned().into_any().unbind())
+        } else {
+            Ok(PyList::new(py, results)?.into_any().unbind())
+        }
+    }
+
+    /// Registers a Python callable as a scalar SQL function, usable as `name(...)` in queries.
+    /// `num_args` is the number of arguments the function takes, or -1 for any number.
+    ///
+    /// The callable runs in the middle of the query, which keeps the database locked, so it
+    /// must not use the database: that raises a DatabaseError, which fails the query.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.create_function("reverse", 1, lambda s: s[::-1])
+    /// db.fetch_all("SELECT reverse(name) FROM users", [])
+    /// ```
+    fn create_function<'py>(
+        &self,
+        name: &str,
+        num_args: i32,
+        callable: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        if !callable.is_callable() {
+            return Err(PyRuntimeError::new_err(format!(
+                "The function \"{}\" must be a Python callable.",
+                name
+            )));
+        }
+
+        // The callable is stored as a Py<PyAny> because SQLite keeps the function
+        // for the whole lifetime of the connection.
+        let callable: Py<PyAny> = callable.clone().unbind();
+        let param_options = self.param_options;
+
+        self.lock_connection()?
+            .create_scalar_function(name, num_args, FunctionFlags::SQLITE_UTF8, move |ctx| {
+                // SQLite calls the function in the middle of a statement, we re-acquire the GIL.
+                // The query usually runs with the GIL released, so this can wait for another
+                // thread, which never holds the GIL while waiting for the connection
+                // (see lock_mutex()), so it cannot deadlock with this one
+                Python::with_gil(|py| {
+                    let args = (0..ctx.len())
+                        .map(|i| value_ref_to_py(py, ctx.get_raw(i)))
+                        .collect::<PyResult<Vec<PyObject>>>()
+                        .and_then(|args| PyTuple::new(py, args))
+                        .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))?;
+
+                    callable
+                        .bind(py)
+                        .call1(args)
+                        .and_then(|result| py_to_value(&result, param_options))
+                        .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+                })
+            })
+            .map_err(|e| sqlite_error(&format!("Failed to create function \"{}\"", name), e))
+    }
+
+    /// Registers a Python class as an aggregate SQL function, usable as `name(...)` in queries
+    /// like `SUM()` or `GROUP_CONCAT()`. `num_args` is the number of arguments the function
+    /// takes, or -1 for any number.
+    ///
+    /// A new instance of `agg_class` is created for each group of rows: its `step(*args)`
+    /// method is called with the arguments of each row, then `finalize()` returns the result
+    /// (None, str, int, float, bool or bytes). This is the same interface as Python's
+    /// `sqlite3.Connection.create_aggregate()`.
+    ///
+    /// These methods are called by the query while it keeps the database locked, so they
+    /// must not use the database: it raises a DatabaseError, which fails the query.
+    ///
+    /// # Examples
+    /// ```python
+    /// class WeightedAverage:
+    ///     def __init__(self):
+    ///         self.total, self.weights = 0, 0
+    ///     def step(self, value, weight):
+    ///         self.total += value * weight
+    ///         self.weights += weight
+    ///     def finalize(self):
+    ///         return self.total / self.weights if self.weights else None
+    ///
+    /// db.create_aggregate("weighted_avg", 2, WeightedAverage)
+    /// db.fetch_all("SELECT weighted_avg(price, quantity) FROM orders", [])
+    /// ```
+    fn create_aggregate<'py>(
+        &self,
+        name: &str,
+        num_args: i32,
+        agg_class: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        if !agg_class.is_callable() {
+            return Err(PyRuntimeError::new_err(format!(
+                "The aggregate \"{}\" must be a Python class.",
+                name
+            )));
+        }
+
+        let aggregate = PyAggregate {
+            class: AssertUnwindSafe(agg_class.clone().unbind()),
+            param_options: self.param_options,
+        };
+        self.lock_connection()?
+            .create_aggregate_function(name, num_args, FunctionFlags::SQLITE_UTF8, aggregate)
+            .map_err(|e| sqlite_error(&format!("Failed to create aggregate \"{}\"", name), e))
+    }
+
+    /// Registers a Python callable as a collation, usable as `COLLATE name` in queries
+    /// and column definitions. `callable(a, b)` compares two strings and returns a
+    /// negative number if `a` sorts first, 0 if they are equal, a positive number otherwise.
+    /// Registering the same name again replaces the collation.
+    ///
+    /// SQLite cannot report an error from a collation, so an exception in the callable
+    /// is reported with `sys.unraisablehook` and the strings are considered equal.
+    /// The comparisons run while the query keeps the database locked, so the callable must
+    /// not use the database: it would get a DatabaseError, reported the same way.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.create_collation("nocase_accents", lambda a, b: locale.strcoll(a.casefold(), b.casefold()))
+    /// db.fetch_all("SELECT name FROM users ORDER BY name COLLATE nocase_accents", [])
+    /// ```
+    fn create_collation<'py>(&self, name: &str, callable: &Bound<'py, PyAny>) -> PyResult<()> {
+        if !callable.is_callable() {
+            return Err(PyRuntimeError::new_err(format!(
+                "The collation \"{}\" must be a Python callable.",
+                name
+            )));
+        }
+
+        let callable: Py<PyAny> = callable.clone().unbind();
+
+        self.lock_connection()?
+            .create_collation(name, move |a, b| {
+                Python::with_gil(|py| {
+                    callable
+                        .call1(py, (a, b))
+                        .and_then(|result| result.extract::<i64>(py))
+                        .map(|result| result.cmp(&0))
+                        .unwrap_or_else(|e| {
+                            e.write_unraisable(py, Some(callable.bind(py)));
+                            std::cmp::Ordering::Equal
+                        })
+                })
+            })
+            .map_err(|e| sqlite_error(&format!("Failed to create collation \"{}\"", name), e))
+    }
+
+    /// Reads `PRAGMA user_version`, an integer stored in the database header.
+    /// Migration tools use it to track the number of the last applied migration.
+    fn get_user_version(&self) -> PyResult<i64> {
+        self.lock_connection()?
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| sqlite_error("Failed to read user_version", e))
+    }
+
+    /// Sets `PRAGMA user_version`, see `get_user_version()`
+    fn set_user_version(&self, version: i64) -> PyResult<()> {
+        self.lock_connection()?
+            .pragma_update(None, "user_version", version)
+            .map_err(|e| sqlite_error("Failed to set user_version", e))
+    }
+
+    /// Runs `PRAGMA wal_checkpoint(MODE)` to copy the WAL file content back into the database.
+    /// `mode` is one of PASSIVE, FULL, RESTART or TRUNCATE.
+    ///
+    /// Returns the `(busy, log, checkpointed)` values reported by SQLite: busy is 1 if the
+    /// checkpoint could not complete, then the number of pages in the WAL and the number
+    /// of pages written back. Outside of WAL mode the counts are -1.
+    #[pyo3(signature = (mode = "PASSIVE"))]
+    fn wal_checkpoint(&self, mode: &str) -> PyResult<(i64, i64, i64)> {
+        const CHECKPOINT_MODES: [&str; 4] = ["PASSIVE", "FULL", "RESTART", "TRUNCATE"];
+
+        let mode = mode.to_uppercase();
+        if !CHECKPOINT_MODES.contains(&mode.as_str()) {
+            return Err(PyRuntimeError::new_err(format!(
+                "\"mode\" must be one of the following: {}.\n\"{}\" is not correct.",
+                CHECKPOINT_MODES.join(", "),
+                mode
+            )));
+        }
+
+        self.lock_connection()?
+            .query_row(&format!("PRAGMA wal_checkpoint({})", mode), [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(|e| sqlite_error("Failed to checkpoint the WAL", e))
+    }
+
+    /// Sets `PRAGMA wal_autocheckpoint`: in WAL mode, a checkpoint runs automatically
+    /// after a commit once the WAL holds at least `n_pages` pages (1000 by default).
+    /// A lower value keeps the WAL file small, a higher one makes the writes faster under
+    /// sustained load. `0` disables the automatic checkpoints, wal_checkpoint() must then
+    /// be called instead. Returns the new value.
+    fn wal_autocheckpoint(&self, n_pages: i64) -> PyResult<i64> {
+        if n_pages < 0 {
+            return Err(PyRuntimeError::new_err(format!(
+                "\"n_pages\" must be 0 or more.\n\"{}\" is not correct.",
+                n_pages
+            )));
+        }
+        self.lock_connection()?
+            .pragma_update_and_check(None, "wal_autocheckpoint", n_pages, |row| row.get(0))
+            .map_err(|e| sqlite_error("Failed to set wal_autocheckpoint", e))
+    }
+
+    /// Reads `PRAGMA page_count`, the number of pages of the database
+    fn page_count(&self) -> PyResult<i64> {
+        self.lock_connection()?
+            .pragma_query_value(None, "page_count", |row| row.get(0))
+            .map_err(|e| sqlite_error("Failed to read page_count", e))
+    }
+
+    /// Reads `PRAGMA page_size`, the size of a page in bytes (4096 by default)
+    fn page_size(&self) -> PyResult<i64> {
+        self.lock_connection()?
+            .pragma_query_value(None, "page_size", |row| row.get(0))
+            .map_err(|e| sqlite_error("Failed to read page_size", e))
+    }
+
+    /// Returns the size of the database in bytes, `page_count` × `page_size`, to monitor
+    /// its growth. Unlike the size of the file, it includes the pages committed to the WAL
+    /// but not yet checkpointed, and it works for in-memory databases too.
+    /// Free pages are counted, vacuum() removes them.
+    fn database_size(&self) -> PyResult<i64> {
+        self.lock_connection()?
+            .query_row(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| sqlite_error("Failed to read the database size", e))
+    }
+
+    /// Loads a SQLite extension from a shared library (e.g. spatialite).
+    /// `entry_point` is the name of the init function, SQLite guesses it when None.
+    ///
+    /// Extensions run native code in the process, so this is only allowed when the
+    /// database was created with `allow_extensions=True`. Loading is enabled for
+    /// the duration of this call only, SQL cannot load extensions by itself.
+    #[pyo3(signature = (path, entry_point = None))]
+    fn load_extension(&self, path: &str, entry_point: Option<&str>) -> PyResult<()> {
+        if !self.allow_extensions {
+            return Err(PyRuntimeError::new_err(
+                "Extension loading is disabled. Create the database with \"allow_extensions=True\" to use it.",
+            ));
+        }
+
+        let conn = self.lock_connection()?;
+
+        // Safety: no SQL statement runs while loading is enabled, the guard disables
+        // it again when dropped. Trusting the library itself is up to the caller,
+        // who explicitly opted in with allow_extensions.
+        unsafe {
+            let _guard = LoadExtensionGuard::new(&conn)
+                .map_err(|e| sqlite_error("Failed to enable extension loading", e))?;
+            conn.load_extension(path, entry_point)
+        }
+        .map_err(|e| sqlite_error(&format!("Failed to load extension \"{}\"", path), e))
+    }
+
+    /// Changes the encryption key of a database opened with `key`, re-encrypting all
+    /// its pages. Needs the crate to be built with the `sqlcipher` feature.
+    ///
+    /// # Examples
+    /// ```python
+    /// db = Database("secrets.db", key="old secret")
+    /// db.rekey("new secret")
+    /// ```
+    fn rekey(&self, new_key: &str) -> PyResult<()> {
+        check_encryption_support()?;
+        self.lock_connection()?
+            .pragma_update(None, "rekey", new_key)
+            .map_err(|e| sqlite_error("Failed to change the encryption key", e))
+    }
+
+    /// Starts a transaction. `mode` picks when the locks are taken:
+    /// * DEFERRED (default) - on the first read or write
+    /// * IMMEDIATE - the write lock right away, avoids "database is locked" errors
+    ///   later in write-heavy concurrent workloads
+    /// * EXCLUSIVE - like IMMEDIATE, and also prevents other connections from reading
+    #[pyo3(signature = (mode = "DEFERRED"))]
+    fn begin(&self, mode: &str) -> PyResult<()> {
+        let mode = transaction_mode(mode)?;
+
+        self.lock_connection()?
+            .execute_batch(&format!("BEGIN {}", mode))
+            .map_err(|e| sqlite_error("Failed to begin transaction", e))
+    }
+
+    /// Commits the current transaction.
+    fn commit(&self) -> PyResult<()> {
+        self.lock_connection()?
+            .execute_batch("COMMIT")
+            .map_err(|e| sqlite_error("Failed to commit transaction", e))
+    }
+
+    /// Rolls back the current transaction.
+    fn rollback(&self) -> PyResult<()> {
+        self.lock_connection()?
+            .execute_batch("ROLLBACK")
+            .map_err(|e| sqlite_error("Failed to rollback transaction", e))
+    }
+
+    /// Returns whether a transaction is open, started by begin(), a transactional
+    /// function or a raw `BEGIN`, so a helper can avoid starting a nested one.
+    fn in_transaction(&self) -> PyResult<bool> {
+        Ok(!self.lock_connection()?.is_autocommit())
+    }
+
+    /// Returns a context manager running its block in a transaction, committed when the
+    /// block ends and rolled back when it raises (the exception is then re-raised).
+    /// `mode` is the one of begin().
+    ///
+    /// When it is entered inside a transaction, e.g. in a function called by another
+    /// `with db.transaction():` block, the block runs in a savepoint with a unique name
+    /// instead, so only its own changes are rolled back and callers do not have to
+    /// know whether a transaction is already open.
+    ///
+    /// # Examples
+    /// ```python
+    /// with db.transaction(mode="IMMEDIATE"):
+    ///     db.execute_raw_query("UPDATE accounts SET balance = balance - ? WHERE id = ?", [amount, source])
+    ///     db.execute_raw_query("UPDATE accounts SET balance = balance + ? WHERE id = ?", [amount, target])
+    /// ```
+    #[pyo3(signature = (mode = "DEFERRED"))]
+    fn transaction(slf: &Bound<'_, Self>, mode: &str) -> PyResult<Transaction> {
+        Ok(Transaction {
+            db: slf.clone().unbind(),
+            mode: transaction_mode(mode)?,
+            read_only: false,
+            started: None,
+            restore_query_only: false,
+        })
+    }
+
+    /// Returns a context manager running its block in a read-only transaction, for the
+    /// SELECTs of a report that must all see the same snapshot of the database, even when
+    /// other connections write meanwhile (in WAL mode, they are not blocked).
+    ///
+    /// The transaction is DEFERRED and its snapshot is taken when the block starts.
+    /// Writes inside the block fail with `PRAGMA query_only`, which is restored afterwards.
+    /// Nested in another transaction, it uses a savepoint like transaction().
+    ///
+    /// # Examples
+    /// ```python
+    /// with db.read_transaction():
+    ///     total = db.fetch_value("SELECT SUM(amount) FROM orders", [])
+    ///     rows = db.fetch_all("SELECT * FROM orders", [])  # Consistent with the total
+    /// ```
+    fn read_transaction(slf: &Bound<'_, Self>) -> Transaction {
+        Transaction {
+            db: slf.clone().unbind(),
+            mode: "DEFERRED".to_string(),
+            read_only: true,
+            started: None,
+            restore_query_only: false,
+        }
+    }
+
+    /// Returns a context manager for large imports, which runs its block in one
+    /// transaction with the pragmas that make writes fastest:
+    /// * `synchronous = OFF` - SQLite does not wait for the disk to confirm the writes
+    /// * `journal_mode = MEMORY` - the rollback journal is kept in memory, not in a file
+    /// * `defer_foreign_keys = ON` - foreign keys are checked at the commit, so the rows
+    ///   can be loaded in any order
+    ///
+    /// The block is committed when it ends and rolled back when it raises, then the
+    /// previous `synchronous` and `journal_mode` are restored in both cases.
+    ///
+    /// A crash of the OS or a power loss during the load can corrupt the database, so it is
+    /// meant for loads that can be started over, e.g. into a new file. It cannot run inside
+    /// a transaction, where the journal mode cannot be changed, and switching a WAL database
+    /// to another journal mode fails while other connections use it.
+    ///
+    /// # Examples
+    /// ```python
+    /// with db.bulk_load():
+    ///     for chunk in chunks:
+    ///         db.insert_many("measures", chunk)
+    /// ```
+    fn bulk_load(slf: &Bound<'_, Self>) -> BulkLoad {
+        BulkLoad {
+            db: slf.clone().unbind(),
+            previous: None,
+        }
+    }
+
+    /// Wraps a function so that each call runs in a transaction, committed when the
+    /// function returns and rolled back when it raises (the exception is then re-raised).
+    /// `mode` is the one of begin().
+    ///
+    /// When it is called inside a transaction, e.g. by another transactional function,
+    /// the call runs in a savepoint instead, so only its own changes are rolled back.
+    ///
+    /// # Examples
+    /// ```python
+    /// @db.transactional
+    /// def transfer(source, target, amount):
+    ///     db.execute_raw_query("UPDATE accounts SET balance = balance - ? WHERE id = ?", [amount, source])
+    ///     db.execute_raw_query("UPDATE accounts SET balance = balance + ? WHERE id = ?", [amount, target])
+    ///
+    /// @db.transactional(mode="IMMEDIATE")
+    /// def increment():
+    ///     ...
+    /// ```
+    #[pyo3(signature = (func = None, *, mode = "DEFERRED"))]
+    fn transactional<'py>(
+        slf: &Bound<'py, Self>,
+        func: Option<PyObject>,
+        mode: &str,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let mode = transaction_mode(mode)?;
+
+        match func {
+            Some(func) => Ok(Bound::new(
+                py,
+                Transactional {
+                    db: slf.clone().unbind(),
+                    func,
+                    mode,
+                },
+            )?
+            .into_any()),
+            // Used as @db.transactional(mode=...), we return the decorator
+            None => {
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("mode", mode)?;
+                py.import("functools")?
+                    .getattr("partial")?
+                    .call((slf.getattr("transactional")?,), Some(&kwargs))
+            }
+        }
+    }
+
+    /// Runs a query and writes the results to a CSV file at `path`, with the column names
+    /// as header. Rows are written one by one as they are read, without going through Python.
+    ///
+    /// NULL values are written as empty fields and BLOBs are base64 encoded.
+    /// Returns the number of rows written.
+    fn export_csv<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+        path: &str,
+    ) -> PyResult<usize> {
+        self.check_param_style(params)?;
+        let (query, sql_params) = query_params(query, params, self.param_options)?;
+
+        let conn = self.lock_connection()?;
+
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+        check_param_count(stmt.parameter_count(), sql_params.len())?;
+
+        let io_error = |e: std::io::Error| {
+            PyRuntimeError::new_err(format!("Failed to write CSV file \"{}\": {}", path, e))
+        };
+
+        let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+
+        let header: Vec<String> = stmt.column_names().iter().map(|n| csv_field(n)).collect();
+        writeln!(writer, "{}", header.join(",")).map_err(io_error)?;
+
+        let column_count = stmt.column_count();
+        let mut rows = stmt
+            .query(params_from_iter(sql_params.iter().map(|p| p.as_ref())))
+            .map_err(|e| sqlite_error("Query execution error", e))?;
+
+        let mut written = 0;
+        while let Some(row) = rows
+            .next()
+            .map_err(|e| sqlite_error("Query execution error", e))?
+        {
+            let mut fields = Vec::with_capacity(column_count);
+            for i in 0..column_count {
+                let value = row
+                    .get_ref(i)
+                    .map_err(|e| sqlite_error("Query execution error", e))?;
+                fields.push(match value {
+                    ValueRef::Null => String::new(),
+                    ValueRef::Integer(i) => i.to_string(),
+                    ValueRef::Real(f) => f.to_string(),
+                    ValueRef::Text(t) => csv_field(&String::from_utf8_lossy(t)),
+                    ValueRef::Blob(b) => BASE64_STANDARD.encode(b),
+                });
+            }
+            writeln!(writer, "{}", fields.join(",")).map_err(io_error)?;
+            written += 1;
+
+            // Let Python handle Ctrl+C on very large exports
+            if written % 10_000 == 0 {
+                py.check_signals()?;
+            }
+        }
+
+        writer.flush().map_err(io_error)?;
+        Ok(written)
+    }
+
+    /// Runs a query and returns its rows as a JSON string, an array with one object per row
+    /// mapping the column names to the values. The JSON is built in Rust with serde_json
+    /// straight from the SQLite values, without building the Python objects, so it is
+    /// ready to be sent in an HTTP response.
+    ///
+    /// INTEGER and REAL values become numbers (NaN and infinities become null), TEXT
+    /// strings, NULL null and BLOBs base64 strings.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.fetch_json("SELECT name, age FROM users", [])  # '[{"name":"Ray","age":27}]'
+    /// ```
+    fn fetch_json<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+    ) -> PyResult<String> {
+        self.check_param_style(params)?;
+        let (query, sql_params) = query_params(query, params, self.param_options)?;
+        let sql_params = WithoutGil(sql_params);
+
+        py.allow_threads(|| {
+            let sql_params = sql_params.into_inner();
+            let conn = self.lock_connection()?;
+            let mut stmt = self
+                .retry_busy(|| conn.prepare_cached(&query))
+                .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+            check_param_count(stmt.parameter_count(), sql_params.len())?;
+
+            let keys: Vec<String> = stmt
+                .column_names()
+                .iter()
+                .map(|name| name.to_string())
+                .collect();
+
+            let mut rows = stmt
+                .query(params_from_iter(sql_params.iter().map(|p| p.as_ref())))
+                .map_err(|e| sqlite_error("Query execution error", e))?;
+
+            let mut objects = Vec::new();
+            while let Some(row) = rows
+                .next()
+                .map_err(|e| sqlite_error("Query execution error", e))?
+            {
+                let mut object = serde_json::Map::with_capacity(keys.len());
+                for (i, key) in keys.iter().enumerate() {
+                    let value = row
+                        .get_ref(i)
+                        .map_err(|e| sqlite_error("Query execution error", e))?;
+                    let value = match value {
+                        ValueRef::Null => serde_json::Value::Null,
+                        ValueRef::Integer(i) => i.into(),
+                        // JSON has no NaN or infinities, serde_json makes them null
+                        ValueRef::Real(f) => f.into(),
+                        ValueRef::Text(t) => String::from_utf8_lossy(t).into(),
+                        ValueRef::Blob(b) => BASE64_STANDARD.encode(b).into(),
+                    };
+                    object.insert(key.clone(), value);
+                }
+                objects.push(serde_json::Value::Object(object));
+            }
+            Ok(serde_json::Value::Array(objects).to_string())
+        })
+    }
+
+    /// Returns an SQL script recreating the schema and the data of the database, like the
+    /// `.dump` command of the sqlite3 shell. It is a text backup that any SQLite version
+    /// can read, and it can be applied to another database with load_dump().
+    ///
+    /// The tables and their rows come first, then the indexes, triggers and views.
+    /// The rows of the virtual tables are dumped as well, while their internal tables
+    /// (e.g. the `_data` table of FTS5) are left to the virtual table to recreate.
+    ///
+    /// # Examples
+    /// ```python
+    /// with open("backup.sql", "w") as f:
+    ///     f.write(db.dump())
+    /// ```
+    fn dump(&self, py: Python<'_>) -> PyResult<String> {
+        let conn = self.lock_connection()?;
+
+        // Reading everything in one transaction gives a consistent snapshot
+        // even if another connection writes in the meantime
+        conn.execute_batch("SAVEPOINT dump")
+            .map_err(|e| sqlite_error("Failed to start transaction", e))?;
+        let script = dump_database(py, &conn);
+        // Nothing was written, so releasing the savepoint cannot fail in a way that matters
+        let _ = conn.execute_batch("RELEASE dump");
+        script
+    }
+
+    /// Runs an SQL script made by dump(), e.g. to restore a backup into an empty database.
+    /// The script runs in a transaction: if a statement fails, nothing is applied and
+    /// the error is raised.
+    ///
+    /// The script disables foreign keys while it runs, as the rows are not inserted in
+    /// the order of their references. The previous setting is restored afterwards.
+    ///
+    /// # Examples
+    /// ```python
+    /// restored = Database("restored.sqlite")
+    /// restored.load_dump(db.dump())
+    /// ```
+    fn load_dump(&self, py: Python<'_>, sql: &str) -> PyResult<()> {
+        py.allow_threads(|| {
+            let conn = self.lock_connection()?;
+            let foreign_keys: bool = conn
+                .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+                .map_err(|e| sqlite_error("Failed to read foreign_keys", e))?;
+            let was_autocommit = conn.is_autocommit();
+
+            let result = conn.execute_batch(sql);
+            // A failing statement leaves the transaction of the script open
+            if result.is_err() && was_autocommit && !conn.is_autocommit() {
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+            let restored = conn.pragma_update(None, "foreign_keys", foreign_keys);
+
+            result.map_err(|e| sqlite_error("Failed to load dump", e))?;
+            restored.map_err(|e| sqlite_error("Failed to restore foreign_keys", e))
+        })
+    }
+
+    /// Applies the migrations whose version is greater than the current `user_version`.
+    /// `migrations` is a list of `(version, sql)` tuples, `sql` can contain several statements.
+    ///
+    /// Each migration runs in its own transaction and bumps `user_version` to its version
+    /// when it succeeds. If one fails, it is rolled back, the following ones are not
+    /// applied and the error is raised.
+    ///
+    /// Returns the versions that were applied.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.migrate([
+    ///     (1, "CREATE TABLE users (name TEXT)"),
+    ///     (2, "ALTER TABLE users ADD COLUMN age INTEGER"),
+    /// ])
+    /// ```
+    fn migrate(&self, mut migrations: Vec<(i64, String)>) -> PyResult<Vec<i64>> {
+        migrations.sort_by_key(|(version, _)| *version);
+        if let Some(pair) = migrations.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(PyRuntimeError::new_err(format!(
+                "The migration version {} is used more than once.",
+                pair[0].0
+            )));
+        }
+
+        let conn = self.lock_connection()?;
+
+        let current_version: i64 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| sqlite_error("Failed to read user_version", e))?;
+
+        let mut applied = Vec::new();
+        for (version, sql) in migrations {
+            if version <= current_version {
+                continue;
+            }
+
+            // The transaction is rolled back when dropped without commit, i.e. on any error
+            let tx = conn
+                .unchecked_transaction()
+                .map_err(|e| sqlite_error("Failed to begin transaction", e))?;
+            tx.execute_batch(&sql)
+                .map_err(|e| sqlite_error(&format!("Migration {} failed", version), e))?;
+            tx.pragma_update(None, "user_version", version)
+                .map_err(|e| sqlite_error("Failed to set user_version", e))?;
+            tx.commit()
+                .map_err(|e| sqlite_error("Failed to commit transaction", e))?;
+
+            applied.push(version);
+        }
+
+        Ok(applied)
+    }
+
+    /// Returns the rowid of the most recent row (the one with the highest rowid) whose
+    /// columns have the values of `where`, or None if no row matches. A None value
+    /// matches NULL. The rowid can then be given to open_blob() or used in an UPDATE,
+    /// which finds the row faster than by other columns.
+    ///
+    /// # Examples
+    /// ```python
+    /// rowid = db.get_rowid("files", {"name": "report.pdf"})
+    /// with db.open_blob("files", "data", rowid) as blob:
+    ///     header = blob.read(4)
+    /// ```
+    fn get_rowid(&self, table: &str, r#where: &Bound<'_, PyDict>) -> PyResult<Option<i64>> {
+        validate_identifier(table, "table")?;
+
+        let (conditions, values) = where_clause(table, r#where)?;
+        let values = values
+            .iter()
+            .map(|value| py_to_value(value, self.param_options))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let sql = format!(
+            "SELECT rowid FROM {}{} ORDER BY rowid DESC LIMIT 1",
+            quote_identifier(table),
+            conditions
+        );
+
+        self.lock_connection()?
+            .query_row(&sql, params_from_iter(values), |row| row.get(0))
+            .optional()
+            .map_err(|e| sqlite_error("Query execution error", e))
+    }
+
+    /// Opens a BLOB for incremental I/O, to stream large values without loading
+    /// them entirely in memory. The BLOB is identified by its table, column and rowid.
+    ///
+    /// A BLOB cannot be resized through this handle, writes must fit in its current size.
+    /// Use `zeroblob(n)` in an INSERT or UPDATE to reserve the space first.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.execute_raw_query("INSERT INTO files (data) VALUES (zeroblob(?))", [size])
+    /// with db.open_blob("files", "data", rowid, read_only=False) as blob:
+    ///     blob.write(chunk)
+    /// ```
+    #[pyo3(signature = (table, column, rowid, read_only = true))]
+    fn open_blob(
+        &self,
+        table: String,
+        column: String,
+        rowid: i64,
+        read_only: bool,
+    ) -> PyResult<Blob> {
+        let blob = Blob {
+            connection: Arc::clone(&self.connection),
+            table,
+            column,
+            rowid,
+            read_only,
+            position: 0,
+            closed: false,
+        };
+
+        // We open it once right away, so a missing row or column fails here
+        blob.with_blob(|_| Ok(()))?;
+        Ok(blob)
+    }
+
+    /// Creates a view from a SELECT query, to reuse it like a table in other queries.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.create_view("adults", "SELECT name, age FROM users WHERE age >= 18")
+    /// db.fetch_all("SELECT name FROM adults", [])
+    /// ```
+    #[pyo3(signature = (name, query, if_not_exists = true))]
+    fn create_view(&self, name: &str, query: &str, if_not_exists: bool) -> PyResult<()> {
+        validate_identifier(name, "view")?;
+
+        let sql = format!(
+            "CREATE VIEW {}{} AS {}",
+            if if_not_exists { "IF NOT EXISTS " } else { "" },
+            quote_identifier(name),
+            query
+        );
+        execute_single(&*self.lock_connection()?, &sql)
+            .map_err(|e| sqlite_error(&format!("Failed to create the view \"{}\"", name), e))
+    }
+
+    /// Drops a view created with create_view()
+    #[pyo3(signature = (name, if_exists = true))]
+    fn drop_view(&self, name: &str, if_exists: bool) -> PyResult<()> {
+        validate_identifier(name, "view")?;
+
+        let sql = format!(
+            "DROP VIEW {}{}",
+            if if_exists { "IF EXISTS " } else { "" },
+            quote_identifier(name)
+        );
+        self.lock_connection()?
+            .execute(&sql, [])
+            .map(|_| ())
+            .map_err(|e| sqlite_error(&format!("Failed to drop the view \"{}\"", name), e))
+    }
+
+    /// Runs a SELECT query preceded by common table expressions: each `name -> query`
+    /// of `ctes` becomes `name AS (query)` of a `WITH` clause, in the order of the dict,
+    /// so a CTE can use the ones before it. A CTE can also refer to itself to be recursive.
+    ///
+    /// The parameters are bound in the order their placeholders appear in the full
+    /// query, the CTEs first. The rows are returned like in fetch_all().
+    ///
+    /// # Examples
+    /// ```python
+    /// db.with_cte(
+    ///     {"adults": "SELECT name, age FROM users WHERE age >= ?"},
+    ///     "SELECT name FROM adults ORDER BY age",
+    ///     [18],
+    /// )
+    /// ```
+    #[pyo3(signature = (ctes, main_query, params, typed = false))]
+    fn with_cte<'py>(
+        &self,
+        py: Python<'py>,
+        ctes: &Bound<'py, PyDict>,
+        main_query: &str,
+        params: &Bound<'py, PyAny>,
+        typed: bool,
+    ) -> PyResult<Vec<PyObject>> {
+        self.check_param_style(params)?;
+        let ctes = ctes
+            .iter()
+            .map(|(name, query)| {
+                let name: String = name.extract()?;
+                validate_identifier(&name, "CTE")?;
+                Ok(format!(
+                    "{} AS ({})",
+                    quote_identifier(&name),
+                    query.extract::<String>()?
+                ))
+            })
+            .collect::<PyResult<Vec<String>>>()?;
+
+        let query = if ctes.is_empty() {
+            main_query.to_string()
+        } else {
+            format!("WITH {} {}", ctes.join(", "), main_query)
+        };
+
+        let options = FetchOptions {
+            typed,
+            ..Default::default()
+        };
+        Ok(self.fetch_rows(py, &query, params, options)?.1)
+    }
+
+    /// Health check: returns True if the database is open and answers `SELECT 1`.
+    /// Never raises, a closed or broken connection simply returns False.
+    fn ping(&self) -> bool {
+        self.lock_connection().is_ok_and(|conn| {
+            conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+                .is_ok_and(|one| one == 1)
+        })
+    }
+
+    /// The number of rows inserted, updated or deleted since the database was opened,
+    /// the rows changed by triggers included. Sampling it over time gives the write
+    /// throughput of the connection.
+    fn total_changes(&self) -> PyResult<u64> {
+        Ok(self.lock_connection()?.total_changes())
+    }
+
+    /// Inserts a row and returns the `returning` columns of the inserted row(s), using
+    /// SQLite's `RETURNING` clause. Useful to get generated ids or default values back
+    /// without a second query. Values are bound like in insert().
+    ///
+    /// # Examples
+    /// ```python
+    /// db.insert_returning("users", {"name": "Ray"}, ["id", "created_at"])  # [["1", "2025-01-01 10:00:00"]]
+    /// db.insert_returning("users", {"name": "Ray"}, ["*"])
+    /// ```
+    fn insert_returning<'py>(
+        &self,
+        py: Python<'py>,
+        table: String,
+        values: &Bound<'py, PyDict>,
+        returning: Vec<String>,
+    ) -> PyResult<Vec<PyObject>> {
+        let returning = returning_clause(&returning)?;
+
+        let columns: Vec<String> = values
+            .keys()
+            .iter()
+            .map(|k| k.extract::<String>())
+            .collect::<PyResult<_>>()?;
+
+        let sql = format!("{}{}", insert_sql(&table, &columns), returning);
+        let values = values
+            .values()
+            .iter()
+            .map(|v| self.insert_value(&v))
+            .collect::<PyResult<Vec<Value>>>()?;
+
+        // The returned rows are converted like the ones of fetch_all(), row factory included
+        let (column_names, mut batches, _) =
+            self.fetch_bound_batches(py, vec![(sql, values)], FetchOptions::default())?;
+        self.apply_row_factory(py, &column_names, batches.pop().unwrap_or_default())
+    }
+
+    /// Inserts a list of rows given as dicts, in a single transaction: either all
+    /// the rows are inserted or none of them. All the dicts must have the same keys.
+    /// Values are bound like in execute_raw_query(). Returns the number of inserted rows.
+    ///
+    /// It can also run inside begin()/commit(): a failure then only removes the rows of
+    /// this call and leaves the transaction open.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.insert_many("users", [{"name": "Ray", "age": 27}, {"name": "Tom", "age": 12}])
+    /// ```
+    fn insert_many<'py>(&self, table: String, rows: Vec<Bound<'py, PyDict>>) -> PyResult<usize> {
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let (columns, values) = self.rows_values(&rows)?;
+
+        let sql = insert_sql(&table, &columns);
+
+        let conn = self.lock_connection()?;
+        in_savepoint(&conn, "insert_many", || {
+            let mut stmt = conn
+                .prepare_cached(&sql)
+                .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+            let mut inserted = 0;
+            for row_values in values {
+                inserted += stmt
+                    .execute(params_from_iter(row_values))
+                    .map_err(|e| sqlite_error("Failed to execute query", e))?;
+            }
+            Ok(inserted)
+        })
+    }
+
+    /// Inserts a list of rows given as dicts, or updates the existing row when one
+    /// conflicts with it on `conflict_columns` (which need a UNIQUE constraint or index),
+    /// with SQLite's `INSERT ... ON CONFLICT DO UPDATE`. The other columns of the dicts
+    /// are the ones updated. Like insert_many(), the rows are written in a single
+    /// transaction with one prepared statement: either all of them are written or none.
+    ///
+    /// Returns a tuple `(inserted, updated)` with the number of rows of each kind.
+    /// A row is counted as updated when a row with the same `conflict_columns` values
+    /// existed before it, which is looked up in the same transaction.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.upsert_many("users", [{"email": "ray@example.com", "name": "Ray"}], ["email"])  # (0, 1)
+    /// ```
+    fn upsert_many<'py>(
+        &self,
+        table: &str,
+        rows: Vec<Bound<'py, PyDict>>,
+        conflict_columns: Vec<String>,
+    ) -> PyResult<(usize, usize)> {
+        if conflict_columns.is_empty() {
+            return Err(PyRuntimeError::new_err(
+                "\"conflict_columns\" must contain at least one column.",
+            ));
+        }
+        validate_identifier(table, "table")?;
+        if rows.is_empty() {
+            return Ok((0, 0));
+        }
+        let (columns, values) = self.rows_values(&rows)?;
+
+        let conflict_indexes = conflict_columns
+            .iter()
+            .map(|column| {
+                columns.iter().position(|c| c == column).ok_or_else(|| {
+                    PyRuntimeError::new_err(format!(
+                        "The conflict column \"{}\" is not in the rows.",
+                        column
+                    ))
+                })
+            })
+            .collect::<PyResult<Vec<usize>>>()?;
+
+        let quoted = |names: &[String]| -> Vec<String> {
+            names.iter().map(|name| quote_identifier(name)).collect()
+        };
+        let updated_columns: Vec<String> = columns
+            .iter()
+            .filter(|column| !conflict_columns.contains(column))
+            .map(|column| format!("{0} = excluded.{0}", quote_identifier(column)))
+            .collect();
+        // When the rows only have the conflict columns, there is nothing to update
+        let action = if updated_columns.is_empty() {
+            "NOTHING".to_string()
+        } else {
+            format!("UPDATE SET {}", updated_columns.join(", "))
+        };
+        let sql = format!(
+            "{} ON CONFLICT ({}) DO {}",
+            insert_sql(table, &columns),
+            quoted(&conflict_columns).join(", "),
+            action
+        );
+        // NULLs never conflict in a UNIQUE column, and never match with "="
+        let exists_sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE {})",
+            quote_identifier(table),
+            quoted(&conflict_columns)
+                .iter()
+                .map(|column| format!("{} = ?", column))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        );
+
+        let conn = self.lock_connection()?;
+        in_savepoint(&conn, "upsert_many", || {
+            let mut stmt = conn
+                .prepare_cached(&sql)
+                .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+            let mut exists_stmt = conn
+                .prepare_cached(&exists_sql)
+                .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+
+            let (mut inserted, mut updated) = (0, 0);
+            for row_values in values {
+                let existed: bool = exists_stmt
+                    .query_row(
+                        params_from_iter(conflict_indexes.iter().map(|&i| &row_values[i])),
+                        |row| row.get(0),
+                    )
+                    .map_err(|e| sqlite_error("Query execution error", e))?;
+                let changed = stmt
+                    .execute(params_from_iter(&row_values))
+                    .map_err(|e| sqlite_error("Failed to execute query", e))?;
+                if existed {
+                    updated += changed;
+                } else {
+                    inserted += changed;
+                }
+            }
+            Ok((inserted, updated))
+        })
+    }
+
+    /// Selects the rows of a table matching `where`, a dict of column values combined with
+    /// AND (None matches NULL), and returns them like fetch_all().
+    ///
+    /// `order_by` is a list of `(column, "ASC" | "DESC")` tuples. The columns are
+    /// validated and `limit` and `offset` are bound as parameters, so none of them can
+    /// inject SQL, even when they come from user input (sorting, pagination).
+    /// `dry_run=True` returns `(sql, params)` without running the query, like in insert().
+    ///
+    /// # Examples
+    /// ```python
+    /// db.select("users", ["name", "age"], {"city": "Paris"}, order_by=[("age", "DESC")], limit=10, offset=20)
+    /// ```
+    #[pyo3(signature = (table, columns = None, r#where = None, order_by = None, limit = None, offset = None, dry_run = false))]
+    #[allow(clippy::too_many_arguments)] // Each clause is a keyword argument in Python
+    fn select<'py>(
+        &self,
+        py: Python<'py>,
+        table: &str,
+        columns: Option<Vec<String>>,
+        r#where: Option<&Bound<'py, PyDict>>,
+        order_by: Option<Vec<(String, String)>>,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        dry_run: bool,
+    ) -> PyResult<PyObject> {
+        validate_identifier(table, "table")?;
+
+        let columns = match columns {
+            Some(columns) if columns.is_empty() => {
+                return Err(PyRuntimeError::new_err(
+                    "\"columns\" must contain at least one column.",
+                ))
+            }
+            Some(columns) => columns
+                .iter()
+                .map(|column| {
+                    validate_identifier(column, "column")?;
+                    Ok(format!(
+                        "{}.{}",
+                        quote_identifier(table),
+                        quote_identifier(column)
+                    ))
+                })
+                .collect::<PyResult<Vec<_>>>()?
+                .join(", "),
+            None => "*".to_string(),
+        };
+        let (conditions, where_params) = match r#where {
+            Some(r#where) => where_clause(table, r#where)?,
+            None => (String::new(), Vec::new()),
+        };
+        let mut sql = format!(
+            "SELECT {} FROM {}{}",
+            columns,
+            quote_identifier(table),
+            conditions
+        );
+
+        if let Some(order_by) = order_by.filter(|order_by| !order_by.is_empty()) {
+            let terms = order_by
+                .iter()
+                .map(|(column, direction)| {
+                    validate_identifier(column, "column")?;
+                    let direction = direction.to_uppercase();
+                    if direction != "ASC" && direction != "DESC" {
+                        return Err(PyRuntimeError::new_err(format!(
+                            "Invalid sort direction \"{}\" for the column \"{}\", expected \"ASC\" or \"DESC\".",
+                            direction, column
+                        )));
+                    }
+                    Ok(format!(
+                        "{}.{} {}",
+                        quote_identifier(table),
+                        quote_identifier(column),
+                        direction
+                    ))
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            sql.push_str(" ORDER BY ");
+            sql.push_str(&terms.join(", "));
+        }
+
+        let params = PyList::new(py, where_params)?;
+        // OFFSET needs a LIMIT, and a negative one means no limit
+        if limit.is_some() || offset.is_some() {
+            sql.push_str(" LIMIT ?");
+            match limit {
+                Some(limit) => params.append(limit)?,
+                None => params.append(-1)?,
+            }
+        }
+        if let Some(offset) = offset {
+            sql.push_str(" OFFSET ?");
+            params.append(offset)?;
+        }
+        let values = self.helper_params(&params)?;
+        if dry_run {
+            return dry_run_result(py, &sql, &values);
+        }
+
+        let (column_names, mut batches, _) =
+            self.fetch_bound_batches(py, vec![(sql, values)], FetchOptions::default())?;
+        let rows = batches.pop().unwrap_or_default();
+        Ok(
+            PyList::new(py, self.apply_row_factory(py, &column_names, rows)?)?
+                .into_any()
+                .unbind(),
+        )
+    }
+
+    /// Sets the columns of `values` on the rows matching `where`, a dict of column values
+    /// combined with AND (None matches NULL, an empty dict matches every row). Values are
+    /// bound like in execute_raw_query(), except that a list is refused, not expanded.
+    ///
+    /// Returns the number of updated rows, or with `returning`, a list of columns or
+    /// `["*"]`, the `returning` columns of the updated rows like in fetch_all().
+    /// `dry_run=True` returns `(sql, params)` without running the update, like in insert().
+    ///
+    /// # Examples
+    /// ```python
+    /// db.update("users", {"age": 28}, {"name": "Ray"})  # 1
+    /// db.update("users", {"age": 28}, {"name": "Ray"}, returning=["id"])  # [["1"]]
+    /// ```
+    #[pyo3(signature = (table, values, r#where, returning = None, dry_run = false))]
+    fn update<'py>(
+        &self,
+        py: Python<'py>,
+        table: &str,
+        values: &Bound<'py, PyDict>,
+        r#where: &Bound<'py, PyDict>,
+        returning: Option<Vec<String>>,
+        dry_run: bool,
+    ) -> PyResult<PyObject> {
+        validate_identifier(table, "table")?;
+        if values.is_empty() {
+            return Err(PyRuntimeError::new_err(
+                "\"values\" must contain at least one column.",
+            ));
+        }
+
+        let mut assignments = Vec::with_capacity(values.len());
+        for column in values.keys() {
+            let column = column.extract::<String>()?;
+            validate_identifier(&column, "column")?;
+            assignments.push(format!("{} = ?", quote_identifier(&column)));
+        }
+        let (conditions, where_params) = where_clause(table, r#where)?;
+
+        let sql = format!(
+            "UPDATE {} SET {}{}",
+            quote_identifier(table),
+            assignments.join(", "),
+            conditions
+        );
+        let params = values.values();
+        for value in where_params {
+            params.append(value)?;
+        }
+        self.change_rows(py, sql, &params, returning.as_deref(), dry_run)
+    }
+
+    /// Deletes the rows matching `where`, a dict of column values combined with AND like
+    /// in update(). Use truncate() to delete all the rows of a table.
+    ///
+    /// Returns the number of deleted rows, or with `returning`, the `returning` columns
+    /// of the deleted rows like in fetch_all().
+    /// `dry_run=True` returns `(sql, params)` without running the deletion, like in insert().
+    ///
+    /// # Examples
+    /// ```python
+    /// db.delete("users", {"name": "Ray"})  # 1
+    /// db.delete("users", {"name": "Ray"}, returning=["*"])  # [["1", "Ray", "27"]]
+    /// ```
+    #[pyo3(signature = (table, r#where, returning = None, dry_run = false))]
+    fn delete<'py>(
+        &self,
+        py: Python<'py>,
+        table: &str,
+        r#where: &Bound<'py, PyDict>,
+        returning: Option<Vec<String>>,
+        dry_run: bool,
+    ) -> PyResult<PyObject> {
+        validate_identifier(table, "table")?;
+
+        let (conditions, where_params) = where_clause(table, r#where)?;
+        let sql = format!("DELETE FROM {}{}", quote_identifier(table), conditions);
+        let params = PyList::new(py, where_params)?;
+        self.change_rows(py, sql, &params, returning.as_deref(), dry_run)
+    }
+
+    /// Copies the rows of `source_table` matching `where` (all of them by default) into
+    /// `dest_table` with `INSERT INTO ... SELECT *`, and returns the number of copied rows.
+    /// `where` is a dict of column values combined with AND like in update().
+    ///
+    /// The columns are copied by position, so both tables should have the same columns in
+    /// the same order. A different number of columns is reported by SQLite as an error.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.copy_rows("orders", "orders_archive", {"status": "done"})  # 120
+    /// ```
+    #[pyo3(signature = (source_table, dest_table, r#where = None))]
+    fn copy_rows(
+        &self,
+        source_table: &str,
+        dest_table: &str,
+        r#where: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<usize> {
+        validate_identifier(source_table, "table")?;
+        validate_identifier(dest_table, "table")?;
+
+        let (conditions, values) = match r#where {
+            Some(r#where) => where_clause(source_table, r#where)?,
+            None => (String::new(), Vec::new()),
+        };
+        let values = values
+            .iter()
+            .map(|value| py_to_value(value, self.param_options))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let sql = format!(
+            "INSERT INTO {} SELECT * FROM {}{}",
+            quote_identifier(dest_table),
+            quote_identifier(source_table),
+            conditions
+        );
+        self.execute_values(&sql, values)
+    }
+
+    /// Deletes all the rows of a table and returns how many were removed. SQLite has no
+    /// TRUNCATE, but a DELETE without WHERE drops the table's pages at once instead of
+    /// deleting the rows one by one (unless the table has triggers).
+    ///
+    /// With `reset_sequence=True`, the AUTOINCREMENT counter of the table is also reset
+    /// in `sqlite_sequence`, so the next id starts at 1 again. Both are done in a savepoint,
+    /// so they can run inside begin()/commit() like insert_many().
+    ///
+    /// # Examples
+    /// ```python
+    /// db.truncate("logs")  # 1250
+    /// db.truncate("users", reset_sequence=True)
+    /// ```
+    #[pyo3(signature = (table, reset_sequence = false))]
+    fn truncate(&self, table: &str, reset_sequence: bool) -> PyResult<usize> {
+        validate_identifier(table, "table")?;
+
+        let conn = self.lock_connection()?;
+        in_savepoint(&conn, "truncate", || {
+            let deleted = conn
+                .execute(&format!("DELETE FROM {}", quote_identifier(table)), [])
+                .map_err(|e| sqlite_error("Failed to execute query", e))?;
+
+            if reset_sequence {
+                // sqlite_sequence is only created along with the first AUTOINCREMENT table
+                let has_sequence: bool = conn
+                    .query_row(
+                        "SELECT EXISTS (SELECT 1 FROM sqlite_schema WHERE name = 'sqlite_sequence')",
+                        [],
+                        |row| row.get(0),
+                    )
+                    .map_err(|e| sqlite_error("Query execution error", e))?;
+                if has_sequence {
+                    conn.execute("DELETE FROM sqlite_sequence WHERE name = ?1", [table])
+                        .map_err(|e| sqlite_error("Failed to reset the sequence", e))?;
+                }
+            }
+            Ok(deleted)
+        })
+    }
+
+    /// Returns the query plan of `query` with `EXPLAIN QUERY PLAN`, as a list of dicts
+    /// with the keys "id", "parent", "notused" and "detail". The query is not run.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.explain("SELECT * FROM users WHERE name = ?", ["Ray"])
+    /// # [{"id": 2, "parent": 0, "notused": 0, "detail": "SEARCH users USING INDEX idx_name (name=?)"}]
+    /// ```
+    fn explain<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        params: &Bound<'py, PyAny>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.check_param_style(params)?;
+        let (query, sql_params) = query_params(query, params, self.param_options)?;
+
+        let conn = self.lock_connection()?;
+        let mut stmt = conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", query))
+            .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+        check_param_count(stmt.parameter_count(), sql_params.len())?;
+
+        let plan = stmt
+            .query_map(params_from_iter(sql_params.iter()), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .map_err(|e| sqlite_error("Query execution error", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| sqlite_error("Query execution error", e))?;
+
+        plan.into_iter()
+            .map(|(id, parent, notused, detail)| {
+                let step = PyDict::new(py);
+                step.set_item("id", id)?;
+                step.set_item("parent", parent)?;
+                step.set_item("notused", notused)?;
+                step.set_item("detail", detail)?;
+                Ok(step)
+            })
+            .collect()
+    }
+
+    /// Returns the foreign keys of a table with `PRAGMA foreign_key_list`, as a list of
+    /// dicts with the keys:
+    /// * "referenced_table" - the table the key points to
+    /// * "columns" - the columns of `table` making the key
+    /// * "referenced_columns" - the matching columns of the referenced table, None for
+    ///   each one when the key points to its PRIMARY KEY without naming the columns
+    /// * "on_update", "on_delete" - the actions, e.g. "CASCADE" or "NO ACTION"
+    /// * "match" - the MATCH clause, "NONE" when there is none
+    ///
+    /// A key on several columns is a single dict, its columns in the key's order.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.list_foreign_keys("orders")
+    /// # [{"referenced_table": "users", "columns": ["user_id"], "referenced_columns": ["id"],
+    /// #   "on_update": "NO ACTION", "on_delete": "CASCADE", "match": "NONE"}]
+    /// ```
+    fn list_foreign_keys<'py>(
+        &self,
+        py: Python<'py>,
+        table: &str,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        validate_identifier(table, "table")?;
+        let query_error = |e| sqlite_error("Query execution error", e);
+
+        let conn = self.lock_connection()?;
+        // The pragma returns no rows for a missing table, which would look like a table
+        // without foreign keys
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM pragma_table_list WHERE name = ?1)",
+                [table],
+                |row| row.get(0),
+            )
+            .map_err(query_error)?;
+        if !exists {
+            return Err(PyRuntimeError::new_err(format!(
+                "The table \"{}\" does not exist.",
+                table
+            )));
+        }
+
+        // One row per column of each key, numbered by "id" and ordered by "seq"
+        type KeyColumn = (i64, String, String, Option<String>, String, String, String);
+        let rows: Vec<KeyColumn> = conn
+            .prepare(
+                "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete, \"match\" \
+                 FROM pragma_foreign_key_list(?1) ORDER BY id, seq",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([table], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                    ))
+                })?
+                .collect()
+            })
+            .map_err(query_error)?;
+
+        // The rows of a key on several columns are grouped, they follow each other
+        let mut keys: Vec<(KeyColumn, Vec<String>, Vec<Option<String>>)> = Vec::new();
+        for row in rows {
+            match keys.last_mut() {
+                Some((first, columns, referenced_columns)) if first.0 == row.0 => {
+                    columns.push(row.2);
+                    referenced_columns.push(row.3);
+                }
+                _ => keys.push((row.clone(), vec![row.2], vec![row.3])),
+            }
+        }
+
+        keys.into_iter()
+            .map(|(first, columns, referenced_columns)| {
+                let (_, referenced_table, _, _, on_update, on_delete, r#match) = first;
+                let key = PyDict::new(py);
+                key.set_item("referenced_table", referenced_table)?;
+                key.set_item("columns", columns)?;
+                key.set_item("referenced_columns", referenced_columns)?;
+                key.set_item("on_update", on_update)?;
+                key.set_item("on_delete", on_delete)?;
+                key.set_item("match", r#match)?;
+                Ok(key)
+            })
+            .collect()
+    }
+
+    /// Returns the rows violating a foreign key constraint with `PRAGMA foreign_key_check`,
+    /// in `table` or in all the tables, e.g. to validate the data after a bulk load done
+    /// with the foreign keys disabled. Each violation is a dict with the keys:
+    /// * "table" - the table of the row
+    /// * "rowid" - the rowid of the row, None for a WITHOUT ROWID table
+    /// * "referenced_table" - the table the row's key points to
+    /// * "fk_index" - which foreign key of the table fails, its position in
+    ///   list_foreign_keys(table)
+    ///
+    /// # Examples
+    /// ```python
+    /// db.foreign_key_check()
+    /// # [{"table": "orders", "rowid": 3, "referenced_table": "users", "fk_index": 0}]
+    /// ```
+    #[pyo3(signature = (table = None))]
+    fn foreign_key_check<'py>(
+        &self,
+        py: Python<'py>,
+        table: Option<&str>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let conn = self.lock_connection()?;
+        let violations = match table {
+            Some(table) => {
+                validate_identifier(table, "table")?;
+                conn.prepare(
+                    "SELECT \"table\", rowid, parent, fkid FROM pragma_foreign_key_check(?1)",
+                )
+                .and_then(|mut stmt| {
+                    stmt.query_map([table], foreign_key_violation)?
+                        .collect::<Result<Vec<_>, _>>()
+                })
+            }
+            None => conn
+                .prepare("SELECT \"table\", rowid, parent, fkid FROM pragma_foreign_key_check")
+                .and_then(|mut stmt| {
+                    stmt.query_map([], foreign_key_violation)?
+                        .collect::<Result<Vec<_>, _>>()
+                }),
+        }
+        .map_err(|e| sqlite_error("Failed to check the foreign keys", e))?;
+
+        violations
+            .into_iter()
+            .map(|(table, rowid, referenced_table, fk_index)| {
+                let violation = PyDict::new(py);
+                violation.set_item("table", table)?;
+                violation.set_item("rowid", rowid)?;
+                violation.set_item("referenced_table", referenced_table)?;
+                violation.set_item("fk_index", fk_index)?;
+                Ok(violation)
+            })
+            .collect()
+    }
+
+    /// Calls `callback(sql)` with the SQL text of each statement before it runs,
+    /// the parameters being replaced by their values. `None` disables tracing.
+    ///
+    /// The callback runs while the database is in use, so it must not call the
+    /// database itself. Its exceptions are reported with `sys.unraisablehook`
+    /// instead of interrupting the query.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.set_trace(print)
+    /// db.fetch_all("SELECT * FROM users WHERE id = ?", [1])  # prints SELECT * FROM users WHERE id = 1
+    /// db.set_trace(None)
+    /// ```
+    #[pyo3(signature = (callback))]
+    fn set_trace(&mut self, callback: Option<PyObject>) -> PyResult<()> {
+        let conn = lock_open_connection(&self.connection)?;
+
+        let tracer = callback.map(Box::new);
+        // Safety: the callback pointer stays valid while it is registered, the box is
+        // only dropped after it has been replaced or cleared, see also Drop for Database
+        unsafe {
+            match &tracer {
+                Some(callback) => ffi::sqlite3_trace_v2(
+                    conn.handle(),
+                    ffi::SQLITE_TRACE_STMT as c_uint,
+                    Some(trace_callback),
+                    &**callback as *const PyObject as *mut c_void,
+                ),
+                None => ffi::sqlite3_trace_v2(conn.handle(), 0, None, std::ptr::null_mut()),
+            };
+        }
+
+        drop(conn);
+        self.tracer = tracer;
+        Ok(())
+    }
+
+    /// Calls `callback(action, arg1, arg2, db_name, trigger_or_view)` each time SQLite
+    /// compiles a statement, once per action it performs (reading a column, inserting into
+    /// a table, attaching a database...). It returns SQLITE_OK to allow the action,
+    /// SQLITE_DENY to fail the whole statement with a DatabaseError, or SQLITE_IGNORE to
+    /// go on without it (a denied column read returns NULL). `None` removes the authorizer.
+    ///
+    /// The arguments follow Python's `sqlite3.Connection.set_authorizer()`: `action` is one
+    /// of the SQLITE_* action codes exported by this module, the other arguments are
+    /// strings or None depending on the action. An exception in the callback, or a return
+    /// value that is not one of the three codes, denies the action and is reported with
+    /// `sys.unraisablehook`. The callback must not use the database.
+    ///
+    /// # Examples
+    /// ```python
+    /// def read_only(action, arg1, arg2, db_name, trigger_or_view):
+    ///     if action in (SQLITE_SELECT, SQLITE_READ):
+    ///         return SQLITE_OK
+    ///     return SQLITE_DENY
+    ///
+    /// db.set_authorizer(read_only)
+    /// db.execute_raw_query("DELETE FROM users")  # raises DatabaseError: not authorized
+    /// db.set_authorizer(None)
+    /// ```
+    #[pyo3(signature = (callback))]
+    fn set_authorizer(&mut self, callback: Option<PyObject>) -> PyResult<()> {
+        let conn = lock_open_connection(&self.connection)?;
+
+        // SQLite checks a statement when it is prepared, so the cached statements
+        // have to be prepared again to go through the new authorizer
+        conn.flush_prepared_statement_cache();
+
+        let authorizer = callback.map(Box::new);
+        // Safety: same as set_trace(), the box outlives its registration
+        unsafe {
+            match &authorizer {
+                Some(callback) => ffi::sqlite3_set_authorizer(
+                    conn.handle(),
+                    Some(authorizer_callback),
+                    &**callback as *const PyObject as *mut c_void,
+                ),
+                None => ffi::sqlite3_set_authorizer(conn.handle(), None, std::ptr::null_mut()),
+            };
+        }
+
+        drop(conn);
+        self.authorizer = authorizer;
+        Ok(())
+    }
+
+    /// Calls `callback()` each time a transaction is about to commit, including the
+    /// implicit transaction of a statement run outside begin()/commit(). If it returns
+    /// a true value, the commit is turned into a rollback and the statement raises an
+    /// IntegrityError. `None` removes the hook.
+    ///
+    /// An exception in the callback also rolls the transaction back, and is reported
+    /// with `sys.unraisablehook`. The callback must not use the database.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.set_commit_hook(lambda: cache.clear())  # clear() returns None, the commit goes on
+    /// db.set_commit_hook(None)
+    /// ```
+    #[pyo3(signature = (callback))]
+    fn set_commit_hook(&self, callback: Option<PyObject>) -> PyResult<()> {
+        let conn = lock_open_connection(&self.connection)?;
+        conn.commit_hook(callback.map(|callback| {
+            move || {
+                Python::with_gil(|py| {
+                    callback
+                        .call0(py)
+                        .and_then(|result| result.is_truthy(py))
+                        .unwrap_or_else(|e| {
+                            e.write_unraisable(py, Some(callback.bind(py)));
+                            true
+                        })
+                })
+            }
+        }));
+        Ok(())
+    }
+
+    /// Calls `callback()` each time a transaction is rolled back, by rollback() or
+    /// because a commit hook refused the commit. `None` removes the hook.
+    /// The exceptions of the callback are reported with `sys.unraisablehook`.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.set_rollback_hook(lambda: print("rolled back"))
+    /// ```
+    #[pyo3(signature = (callback))]
+    fn set_rollback_hook(&self, callback: Option<PyObject>) -> PyResult<()> {
+        let conn = lock_open_connection(&self.connection)?;
+        conn.rollback_hook(callback.map(|callback| {
+            move || {
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call0(py) {
+                        e.write_unraisable(py, Some(callback.bind(py)));
+                    }
+                })
+            }
+        }));
+        Ok(())
+    }
+
+    /// Calls `callback(operation, table, rowid)` for each row changed on this connection,
+    /// `operation` being "INSERT", "UPDATE" or "DELETE". `None` removes the hook.
+    ///
+    /// The hook runs in the middle of the statement, before it is committed: it can
+    /// record the change (e.g. to refresh a cache after commit) but must not use the
+    /// database. Rows changed by a DROP TABLE, a truncating DELETE without WHERE or
+    /// a WITHOUT ROWID table are not reported, as documented for sqlite3_update_hook().
+    /// The exceptions of the callback are reported with `sys.unraisablehook`.
+    ///
+    /// # Examples
+    /// ```python
+    /// db.set_update_hook(lambda operation, table, rowid: print(operation, table, rowid))
+    /// db.insert("users", {"name": "Ray"})  # prints INSERT users 1
+    /// ```
+    #[pyo3(signature = (callback))]
+    fn set_update_hook(&self, callback: Option<PyObject>) -> PyResult<()> {
+        let conn = lock_open_connection(&self.connection)?;
+        conn.update_hook(callback.map(|callback| {
+            move |action: Action, _db_name: &str, table: &str, rowid: i64| {
+                let operation = match action {
+                    Action::SQLITE_INSERT => "INSERT",
+                    Action::SQLITE_UPDATE => "UPDATE",
+                    Action::SQLITE_DELETE => "DELETE",
+                    _ => "UNKNOWN",
+                };
+                // The statement runs with the GIL released, so it is taken back here
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call1(py, (operation, table, rowid)) {
+                        e.write_unraisable(py, Some(callback.bind(py)));
+                    }
+                })
+            }
+        }));
+        Ok(())
+    }
+
+    /// Calls `callback()` about every `n_ops` virtual machine instructions while a query
+    /// runs, e.g. to report progress or to cancel it without a watchdog thread. If it
+    /// returns a true value, the query stops and raises an OperationalError
+    /// ("interrupted"), like after interrupt(). `None` removes the handler.
+    ///
+    /// An exception in the callback also stops the query, and is reported with
+    /// `sys.unraisablehook`. The callback must not use the database.
+    ///
+    /// # Examples
+    /// ```python
+    /// deadline = time.monotonic() + 5
+    /// db.set_progress_handler(10_000, lambda: time.monotonic() > deadline)
+    /// ```
+    #[pyo3(signature = (n_ops, callback))]
+    fn set_progress_handler(&self, n_ops: i32, callback: Option<PyObject>) -> PyResult<()> {
+        // SQLite disables the handler for a count below 1, which would hide a mistake
+        if callback.is_some() && n_ops < 1 {
+            return Err(PyRuntimeError::new_err(
+                "\"n_ops\" must be a positive number.",
+            ));
+        }
+
+        let conn = lock_open_connection(&self.connection)?;
+        conn.progress_handler(
+            n_ops,
+            callback.map(|callback| {
+                move || {
+                    // The query runs with the GIL released, so it is taken back here
+                    Python::with_gil(|py| {
+                        callback
+                            .call0(py)
+                            .and_then(|result| result.is_truthy(py))
+                            .unwrap_or_else(|e| {
+                                e.write_unraisable(py, Some(callback.bind(py)));
+                                true
+                            })
+                    })
+                }
+            }),
+        );
+        Ok(())
+    }
+
+    /// Cancels the query running on this database, from another thread (e.g. a watchdog).
+    /// The query then raises an OperationalError ("interrupted"). Does nothing if no query
+    /// is running or if the database is closed.
+    ///
+    /// The GIL is released while fetch_all() and execute_raw_query() run,
+    /// so Python threads can call interrupt() during these queries.
+    ///
+    /// # Examples
+    /// ```python
+    /// threading.Timer(5.0, db.interrupt).start()
+    /// db.fetch_all("SELECT ... a very slow query ...", [])
+    /// ```
+    fn interrupt(&self) {
+        self.interrupt_handle.interrupt();
+    }
+
+    /// Applies a coherent set of pragmas trading durability for speed:
+    ///
+    /// * "safe" - `journal_mode=DELETE`, `synchronous=FULL`, 2 MB cache (SQLite's defaults).
+    ///   A committed transaction survives a crash or a power loss.
+    /// * "fast" - `journal_mode=WAL`, `synchronous=NORMAL`, 64 MB cache, temporary tables in memory.
+    ///   The database cannot be corrupted, but the last transactions can be lost on a power
+    ///   loss (not on an application crash). Good default for most applications.
+    /// * "bulk" - `journal_mode=WAL`, `synchronous=OFF`, 256 MB cache, temporary tables in memory.
+    ///   Data is handed to the OS without waiting for the disk, so a power loss or OS crash
+    ///   can corrupt the database. Only for initial loads that can be redone from scratch.
+    ///
+    /// The pragmas apply to this connection, except `journal_mode=WAL` which is stored in
+    /// the database file. Returns the journal mode actually used (an in-memory database
+    /// always uses "memory").
+    fn set_performance_profile(&self, profile: &str) -> PyResult<String> {
+        // (profile, journal_mode, synchronous, cache_size in KiB, temp_store)
+        const PROFILES: [(&str, &str, &str, i64, &str); 3] = [
+            ("safe", "DELETE", "FULL", 2_000, "DEFAULT"),
+            ("fast", "WAL", "NORMAL", 64 * 1024, "MEMORY"),
+            ("bulk", "WAL", "OFF", 256 * 1024, "MEMORY"),
+        ];
+
+        let Some(&(_, journal_mode, synchronous, cache_size, temp_store)) =
+            PROFILES.iter().find(|(name, ..)| *name == profile)
+        else {
+            return Err(PyRuntimeError::new_err(format!(
+                "\"profile\" must be one of the following: safe, fast, bulk.\n\"{}\" is not correct.",
+                profile
+            )));
+        };
+
+        let conn = self.lock_connection()?;
+        let journal_mode = conn
+            .pragma_update_and_check(None, "journal_mode", journal_mode, |row| {
+                row.get::<_, String>(0)
+            })
+            .map_err(|e| sqlite_error("Failed to set journal_mode", e))?;
+        conn.pragma_update(None, "synchronous", synchronous)
+            .map_err(|e| sqlite_error("Failed to set synchronous", e))?;
+        // A negative cache_size is a size in KiB instead of a number of pages
+        conn.pragma_update(None, "cache_size", -cache_size)
+            .map_err(|e| sqlite_error("Failed to set cache_size", e))?;
+        conn.pragma_update(None, "temp_store", temp_store)
+            .map_err(|e| sqlite_error("Failed to set temp_store", e))?;
+
+        Ok(journal_mode)
+    }
+
+    /// The version of the SQLite library in use, e.g. "3.47.0".
+    /// It is bundled with the module, so it does not depend on the system's SQLite.
+    ///
+    /// # Examples
+    /// ```python
+    /// Database.sqlite_version()  # "3.47.0"
+    /// ```
+    #[staticmethod]
+    fn sqlite_version() -> &'static str {
+        rusqlite::version()
+    }
+
+    /// The same version as a number that can be compared, e.g. 3047000 for "3.47.0".
+    ///
+    /// # Examples
+    /// ```python
+    /// if Database.sqlite_version_number() >= 3035000:  # RETURNING needs SQLite 3.35
+    ///     db.insert_returning("users", {"name": "Ray"}, ["id"])
+    /// ```
+    #[staticmethod]
+    fn sqlite_version_number() -> i32 {
+        rusqlite::version_number()
+    }
+
+    //// INTERNALS ////
+
+    /// Method to execute queries. Used inside the create_table() and insert() methods
+    #[pyo3(signature = (query, values=None))]
+    fn __execute(&self, query: String, values: Option<Vec<Option<String>>>) -> PyResult<usize> {
+        let values = values
+            .unwrap_or_default()
+            .into_iter()
+            .map(|value| value.map_or(Value::Null, Value::Text))
+            .collect();
+        self.execute_values(&query, values)
+    }
+}
+
+impl Drop for Database {
+    /// A Blob can keep the connection alive after the Database is dropped,
+    /// so the trace and authorizer callbacks are unregistered before they are freed
+    fn drop(&mut self) {
+        if self.tracer.is_some() || self.authorizer.is_some() {
+            let guard = lock_mutex(&self.connection);
+            if let Some(conn) = guard.as_ref() {
+                unsafe {
+                    ffi::sqlite3_trace_v2(conn.handle(), 0, None, std::ptr::null_mut());
+                    ffi::sqlite3_set_authorizer(conn.handle(), None, std::ptr::null_mut());
+                }
+            }
+        }
+    }
+}
+
+/// Called by SQLite for each statement traced by Database.set_trace()
+unsafe extern "C" fn trace_callback(
+    _event: c_uint,
+    callback: *mut c_void,
+    statement: *mut c_void,
+    _sql: *mut c_void,
+) -> c_int {
+    // The expanded SQL contains the values of the parameters, it has to be freed by SQLite
+    let expanded = ffi::sqlite3_expanded_sql(statement as *mut ffi::sqlite3_stmt);
+    if expanded.is_null() {
+        return 0;
+    }
+    let sql = CStr::from_ptr(expanded).to_string_lossy().into_owned();
+    ffi::sqlite3_free(expanded as *mut c_void);
+
+    let callback = &*(callback as *const PyObject);
+    Python::with_gil(|py| {
+        if let Err(e) = callback.call1(py, (sql,)) {
+            e.write_unraisable(py, Some(callback.bind(py)));
+        }
+    });
+    0
+}
+
+/// Called by SQLite for each action checked by Database.set_authorizer()
+unsafe extern "C" fn authorizer_callback(
+    callback: *mut c_void,
+    action: c_int,
+    arg1: *const c_char,
+    arg2: *const c_char,
+    db_name: *const c_char,
+    trigger_or_view: *const c_char,
+) -> c_int {
+    let text = |ptr: *const c_char| {
+        (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    };
+    let args = (
+        action,
+        text(arg1),
+        text(arg2),
+        text(db_name),
+        text(trigger_or_view),
+    );
+
+    let callback = &*(callback as *const PyObject);
+    Python::with_gil(|py| {
+        let result = callback
+            .call1(py, args)
+            .and_then(|code| match code.extract::<c_int>(py) {
+                Ok(code @ (ffi::SQLITE_OK | ffi::SQLITE_DENY | ffi::SQLITE_IGNORE)) => Ok(code),
+                _ => Err(PyRuntimeError::new_err(format!(
+                    "The authorizer must return SQLITE_OK, SQLITE_DENY or SQLITE_IGNORE, not {}.",
+                    code.bind(py).repr()?
+                ))),
+            });
+        result.unwrap_or_else(|e| {
+            e.write_unraisable(py, Some(callback.bind(py)));
+            ffi::SQLITE_DENY
+        })
+    })
+}
+
+/// Result and action codes of the authorizer, exported as module constants
+/// with the names Python's sqlite3 module uses
+const AUTHORIZER_CODES: [(&str, c_int); 37] = [
+    ("SQLITE_OK", ffi::SQLITE_OK),
+    ("SQLITE_DENY", ffi::SQLITE_DENY),
+    ("SQLITE_IGNORE", ffi::SQLITE_IGNORE),
+    ("SQLITE_CREATE_INDEX", ffi::SQLITE_CREATE_INDEX),
+    ("SQLITE_CREATE_TABLE", ffi::SQLITE_CREATE_TABLE),
+    ("SQLITE_CREATE_TEMP_INDEX", ffi::SQLITE_CREATE_TEMP_INDEX),
+    ("SQLITE_CREATE_TEMP_TABLE", ffi::SQLITE_CREATE_TEMP_TABLE),
+    (
+        "SQLITE_CREATE_TEMP_TRIGGER",
+        ffi::SQLITE_CREATE_TEMP_TRIGGER,
+    ),
+    ("SQLITE_CREATE_TEMP_VIEW", ffi::SQLITE_CREATE_TEMP_VIEW),
+    ("SQLITE_CREATE_TRIGGER", ffi::SQLITE_CREATE_TRIGGER),
+    ("SQLITE_CREATE_VIEW", ffi::SQLITE_CREATE_VIEW),
+    ("SQLITE_DELETE", ffi::SQLITE_DELETE),
+    ("SQLITE_DROP_INDEX", ffi::SQLITE_DROP_INDEX),
+    ("SQLITE_DROP_TABLE", ffi::SQLITE_DROP_TABLE),
+    ("SQLITE_DROP_TEMP_INDEX", ffi::SQLITE_DROP_TEMP_INDEX),
+    ("SQLITE_DROP_TEMP_TABLE", ffi::SQLITE_DROP_TEMP_TABLE),
+    ("SQLITE_DROP_TEMP_TRIGGER", ffi::SQLITE_DROP_TEMP_TRIGGER),
+    ("SQLITE_DROP_TEMP_VIEW", ffi::SQLITE_DROP_TEMP_VIEW),
+    ("SQLITE_DROP_TRIGGER", ffi::SQLITE_DROP_TRIGGER),
+    ("SQLITE_DROP_VIEW", ffi::SQLITE_DROP_VIEW),
+    ("SQLITE_INSERT", ffi::SQLITE_INSERT),
+    ("SQLITE_PRAGMA", ffi::SQLITE_PRAGMA),
+    ("SQLITE_READ", ffi::SQLITE_READ),
+    ("SQLITE_SELECT", ffi::SQLITE_SELECT),
+    ("SQLITE_TRANSACTION", ffi::SQLITE_TRANSACTION),
+    ("SQLITE_UPDATE", ffi::SQLITE_UPDATE),
+    ("SQLITE_ATTACH", ffi::SQLITE_ATTACH),
+    ("SQLITE_DETACH", ffi::SQLITE_DETACH),
+    ("SQLITE_ALTER_TABLE", ffi::SQLITE_ALTER_TABLE),
+    ("SQLITE_REINDEX", ffi::SQLITE_REINDEX),
+    ("SQLITE_ANALYZE", ffi::SQLITE_ANALYZE),
+    ("SQLITE_CREATE_VTABLE", ffi::SQLITE_CREATE_VTABLE),
+    ("SQLITE_DROP_VTABLE", ffi::SQLITE_DROP_VTABLE),
+    ("SQLITE_FUNCTION", ffi::SQLITE_FUNCTION),
+    ("SQLITE_SAVEPOINT", ffi::SQLITE_SAVEPOINT),
+    ("SQLITE_RECURSIVE", ffi::SQLITE_RECURSIVE),
+    ("SQLITE_COPY", ffi::SQLITE_COPY),
+];
+
+/// An aggregate registered with Database.create_aggregate(), which creates an instance
+/// of the Python class for each group of rows
+struct PyAggregate {
+    class: AssertUnwindSafe<PyObject>,
+    param_options: ParamOptions,
+}
+
+impl PyAggregate {
+    fn new_instance(&self, py: Python<'_>) -> rusqlite::Result<AssertUnwindSafe<PyObject>> {
+        self.class
+            .call0(py)
+            .map(AssertUnwindSafe)
+            .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+    }
+}
+
+// SQLite calls the aggregate in the middle of a statement, so the GIL is acquired again
+// in each method. The Python errors become the error of the query. Panics can't cross
+// Python calls, hence the AssertUnwindSafe that rusqlite requires.
+impl Aggregate<AssertUnwindSafe<PyObject>, Value> for PyAggregate {
+    fn init(&self, _ctx: &mut Context<'_>) -> rusqlite::Result<AssertUnwindSafe<PyObject>> {
+        Python::with_gil(|py| self.new_instance(py))
+    }
+
+    fn step(
+        &self,
+        ctx: &mut Context<'_>,
+        instance: &mut AssertUnwindSafe<PyObject>,
+    ) -> rusqlite::Result<()> {
+        Python::with_gil(|py| {
+            (0..ctx.len())
+                .map(|i| value_ref_to_py(py, ctx.get_raw(i)))
+                .collect::<PyResult<Vec<PyObject>>>()
+                .and_then(|args| PyTuple::new(py, args))
+                .and_then(|args| instance.call_method1(py, "step", args))
+                .map(|_| ())
+                .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+        })
+    }
+
+    fn finalize(
+        &self,
+        _ctx: &mut Context<'_>,
+        instance: Option<AssertUnwindSafe<PyObject>>,
+    ) -> rusqlite::Result<Value> {
+        Python::with_gil(|py| {
+            // Without rows, step() was never called and there is no instance yet,
+            // but finalize() still gives the result, e.g. 0 for a count
+            let instance = match instance {
+                Some(instance) => instance,
+                None => self.new_instance(py)?,
+            };
+            instance
+                .call_method0(py, "finalize")
+                .and_then(|result| py_to_value(result.bind(py), self.param_options))
+                .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+        })
+    }
+}
+
+/// A function wrapped by Database.transactional()
+#[pyclass]
+struct Transactional {
+    db: Py<Database>,
+    func: PyObject,
+    mode: String,
+}
+
+#[pymethods]
+impl Transactional {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python<'_>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        let savepoint = start_transaction(&*self.db.borrow(py).lock_connection()?, &self.mode)?;
+
+        // The connection is not locked while the function runs, so it can use the database
+        let result = self.func.call(py, args, kwargs);
+
+        let db = self.db.borrow(py);
+        let conn = db.lock_connection()?;
+        // The error of the function is raised, not the one of the rollback
+        finish_transaction(&conn, savepoint.as_deref(), result.is_ok())?;
+        result
+    }
+
+    /// Binds the function like a method when it is defined in a class
+    fn __get__<'py>(
+        slf: Bound<'py, Self>,
+        obj: Option<&Bound<'py, PyAny>>,
+        _owner: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        match obj {
+            Some(obj) => slf
+                .py()
+                .import("types")?
+                .getattr("MethodType")?
+                .call1((slf, obj)),
+            None => Ok(slf.into_any()),
+        }
+    }
+
+    /// The wrapped function, like the functions decorated with functools.wraps()
+    #[getter(__wrapped__)]
+    fn wrapped(&self, py: Python<'_>) -> PyObject {
+        self.func.clone_ref(py)
+    }
+}
+
+/// The context manager returned by Database.transaction() and Database.read_transaction()
+#[pyclass]
+struct Transaction {
+    db: Py<Database>,
+    mode: String,
+    read_only: bool,
+    /// Set once entered, to the savepoint used when it is nested in another transaction
+    started: Option<Option<String>>,
+    /// Whether `query_only` was turned on by a read-only transaction, to turn it off on exit
+    restore_query_only: bool,
+}
+
+#[pymethods]
+impl Transaction {
+    fn __enter__(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.started.is_some() {
+            return Err(PyRuntimeError::new_err(
+                "The transaction has already been started.",
+            ));
+        }
+        let db = self.db.borrow(py);
+        let conn = db.lock_connection()?;
+        let savepoint = start_transaction(&conn, &self.mode)?;
+
+        if self.read_only {
+            let enter = || -> rusqlite::Result<bool> {
+                // A deferred transaction takes its snapshot at its first read
+                conn.query_row("SELECT COUNT(*) FROM sqlite_schema", [], |_| Ok(()))?;
+                let query_only: bool =
+                    conn.pragma_query_value(None, "query_only", |row| row.get(0))?;
+                if !query_only {
+                    conn.pragma_update(None, "query_only", true)?;
+                }
+                Ok(!query_only)
+            };
+            match enter() {
+                Ok(restore) => self.restore_query_only = restore,
+                Err(e) => {
+                    finish_transaction(&conn, savepoint.as_deref(), false)?;
+                    return Err(sqlite_error("Failed to begin transaction", e));
+                }
+            }
+        }
+        self.started = Some(savepoint);
+        Ok(())
+    }
+
+    /// Commits when the block ends normally, rolls back when it raises.
+    /// Returns false so the exception of the block is not swallowed.
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> PyResult<bool> {
+        let Some(savepoint) = self.started.take() else {
+            return Err(PyRuntimeError::new_err(
+                "The transaction has not been started.",
+            ));
+        };
+        let db = self.db.borrow(py);
+        let conn = db.lock_connection()?;
+        if std::mem::take(&mut self.restore_query_only) {
+            if let Err(e) = conn.pragma_update(None, "query_only", false) {
+                finish_transaction(&conn, savepoint.as_deref(), false)?;
+                return Err(sqlite_error("Failed to restore query_only", e));
+            }
+        }
+        finish_transaction(&conn, savepoint.as_deref(), exc_type.is_none())?;
+        Ok(false)
+    }
+}
+
+/// The context manager returned by Database.bulk_load()
+#[pyclass]
+struct BulkLoad {
+    db: Py<Database>,
+    /// Set once entered, to the journal_mode and synchronous to restore on exit
+    previous: Option<(String, i64)>,
+}
+
+#[pymethods]
+impl BulkLoad {
+    fn __enter__(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.previous.is_some() {
+            return Err(PyRuntimeError::new_err(
+                "The bulk load has already been started.",
+            ));
+        }
+        let db = self.db.borrow(py);
+        let conn = db.lock_connection()?;
+        if !conn.is_autocommit() {
+            return Err(PyRuntimeError::new_err(
+                "bulk_load() cannot be used inside a transaction, the journal mode cannot be changed in one.",
+            ));
+        }
+
+        let journal_mode: String = conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .map_err(|e| sqlite_error("Failed to read journal_mode", e))?;
+        let synchronous: i64 = conn
+            .pragma_query_value(None, "synchronous", |row| row.get(0))
+            .map_err(|e| sqlite_error("Failed to read synchronous", e))?;
+
+        let enter = || -> rusqlite::Result<()> {
+            conn.pragma_update(None, "synchronous", "OFF")?;
+            // journal_mode returns the new mode, which pragma_update() does not expect
+            conn.pragma_update_and_check(None, "journal_mode", "MEMORY", |_| Ok(()))?;
+            conn.execute_batch("BEGIN")?;
+            // Only lasts until the end of the transaction
+            conn.pragma_update(None, "defer_foreign_keys", true)
+        };
+        if let Err(e) = enter() {
+            if !conn.is_autocommit() {
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+            // The error of the setup is the one worth raising
+            let _ = restore_write_pragmas(&conn, &journal_mode, synchronous);
+            return Err(sqlite_error("Failed to start the bulk load", e));
+        }
+        self.previous = Some((journal_mode, synchronous));
+        Ok(())
+    }
+
+    /// Commits when the block ends normally, rolls back when it raises, then restores
+    /// the pragmas. Returns false so the exception of the block is not swallowed.
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> PyResult<bool> {
+        let Some((journal_mode, synchronous)) = self.previous.take() else {
+            return Err(PyRuntimeError::new_err(
+                "The bulk load has not been started.",
+            ));
+        };
+        let db = self.db.borrow(py);
+        let conn = db.lock_connection()?;
+        // The pragmas are restored even when the commit fails, but the commit error
+        // comes first since it is the one that tells the rows were not saved
+        let finished = finish_transaction(&conn, None, exc_type.is_none());
+        let restored = restore_write_pragmas(&conn, &journal_mode, synchronous);
+        finished?;
+        restored.map_err(|e| sqlite_error("Failed to restore the pragmas of the bulk load", e))?;
+        Ok(false)
+    }
+}
+
+/// The cursor returned by Database.cursor()
+#[pyclass]
+struct Cursor {
+    db: Py<Database>,
+    query: String,
+    params: PyObject,
+    /// The number of rows already returned
+    offset: usize,
+    /// Set once a batch comes back short, so the query is not run again
+    exhausted: bool,
+}
+
+#[pymethods]
+impl Cursor {
+    /// Returns up to `size` of the next rows, or an empty list once they have all
+    /// been returned. The rows are converted like in fetch_all().
+    fn fetchmany(&mut self, py: Python<'_>, size: usize) -> PyResult<Vec<PyObject>> {
+        if size == 0 {
+            return Err(PyRuntimeError::new_err(
+                "\"size\" must be a positive number.",
+            ));
+        }
+
+        if self.exhausted {
+            return Ok(Vec::new());
+        }
+
+        // The numbers are written in the SQL, a placeholder would not mix with named
+        // parameters. The newline ends a `--` comment at the end of the query.
+        let query = format!(
+            "SELECT * FROM ({}\n) LIMIT {} OFFSET {}",
+            self.query, size, self.offset
+        );
+        let db = self.db.borrow(py);
+        let (_, rows) = db.fetch_rows(py, &query, self.params.bind(py), FetchOptions::default())?;
+
+        self.offset += rows.len();
+        self.exhausted = rows.len() < size;
+        Ok(rows)
+    }
+}
+
+/// A handle on a BLOB returned by Database.open_blob(), used like a binary file
+/// with read(), write(), seek() and tell().
+///
+/// rusqlite's Blob borrows the connection, so it cannot be kept in a Python object.
+/// Instead we keep a reference to the shared connection and open the BLOB on each call,
+/// which also lets other queries run between two reads.
+#[pyclass]
+struct Blob {
+    connection: Arc<Mutex<Option<Connection>>>,
+    table: String,
+    column: String,
+    rowid: i64,
+    read_only: bool,
+    position: usize,
+    closed: bool,
+}
+
+impl Blob {
+    /// Opens the BLOB on the locked connection and runs `f` on it
+    fn with_blob<T>(
+        &self,
+        f: impl FnOnce(&mut rusqlite::blob::Blob<'_>) -> rusqlite::Result<T>,
+    ) -> PyResult<T> {
+        if self.closed {
+            return Err(PyRuntimeError::new_err("Cannot operate on a closed BLOB."));
+        }
+
+        let conn = lock_open_connection(&self.connection)?;
+        conn.blob_open(
+            DatabaseName::Main,
+            &self.table,
+            &self.column,
+            self.rowid,
+            self.read_only,
+        )
+        .and_then(|mut blob| f(&mut blob))
+        .map_err(|e| {
+            sqlite_error(
+                &format!(
+                    "BLOB error on {}.{} (rowid {})",
+                    self.table, self.column, self.rowid
+                ),
+                e,
+            )
+        })
+    }
+}
+
+#[pymethods]
+impl Blob {
+    /// Reads up to `size` bytes from the current position, or until the end if `size` is negative
+    #[pyo3(signature = (size = -1))]
+    fn read<'py>(&mut self, py: Python<'py>, size: i64) -> PyResult<Bound<'py, PyBytes>> {
+        let position = self.position;
+        let data = self.with_blob(|blob| {
+            let remaining = blob.len().saturating_sub(position);
+            let size = if size < 0 {
+                remaining
+            } else {
+                (size as usize).min(remaining)
+            };
+
+            let mut buffer = vec![0; size];
+            let read = blob.read_at(&mut buffer, position)?;
+            buffer.truncate(read);
+            Ok(buffer)
+        })?;
+
+        self.position += data.len();
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Writes `data` at the current position and returns the number of bytes written.
+    /// The BLOB cannot grow, writing past its end raises an error.
+    /// `data` can be bytes, a bytearray or a memoryview.
+    fn write(&mut self, data: &Bound<'_, PyAny>) -> PyResult<usize> {
+        if self.read_only {
+            return Err(PyRuntimeError::new_err(
+                "The BLOB was opened read-only, use \"read_only=False\" to write.",
+            ));
+        }
+
+        let data = blob_bytes(data)?.ok_or_else(|| {
+            PyRuntimeError::new_err("Only bytes, bytearray or memoryview can be written to a BLOB.")
+        })?;
+
+        let position = self.position;
+        self.with_blob(|blob| blob.write_at(&data, position))?;
+
+        self.position += data.len();
+        Ok(data.len())
+    }
+
+    /// Moves the position like a file: `whence` is 0 (from the start),
+    /// 1 (from the current position) or 2 (from the end). Returns the new position.
+    #[pyo3(signature = (offset, whence = 0))]
+    fn seek(&mut self, offset: i64, whence: i32) -> PyResult<usize> {
+        let base = match whence {
+            0 => 0,
+            1 => self.position as i64,
+            2 => self.with_blob(|blob| Ok(blob.len()))? as i64,
+            _ => {
+                return Err(PyRuntimeError::new_err(
+                    "\"whence\" must be 0 (start), 1 (current position) or 2 (end).",
+                ))
+            }
+        };
+
+        let position = base + offset;
+        if position < 0 {
+            return Err(PyRuntimeError::new_err(
+                "Cannot seek before the start of the BLOB.",
+            ));
+        }
+
+        self.position = position as usize;
+        Ok(self.position)
+    }
+
+    /// Returns the current position
+    fn tell(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the size of the BLOB in bytes
+    fn __len__(&self) -> PyResult<usize> {
+        self.with_blob(|blob| Ok(blob.len()))
+    }
+
+    /// Closes the handle, any later operation raises an error
+    fn close(&mut self) {
+        self.closed = true;
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (_exc_type = None, _exc_value = None, _traceback = None))]
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.close();
+        false
+    }
+}
+
+/// Fails unless SQLite is SQLCipher, with which `PRAGMA key` encrypts the database.
+/// Plain SQLite silently ignores these pragmas, which would leave the data unencrypted.
+fn check_encryption_support() -> PyResult<()> {
+    if cfg!(feature = "sqlcipher") {
+        Ok(())
+    } else {
+        Err(PyRuntimeError::new_err(
+            "This build has no encryption support, rebuild it with the \"sqlcipher\" feature to use an encryption key.",
+        ))
+    }
+}
+
+/// Runs `run` in a savepoint, which starts a transaction when none is active and nests in
+/// the current one otherwise. The changes of `run` are thus all-or-nothing, even inside
+/// begin()/commit(), without committing or rolling back what came before them.
+/// When `run` fails, the error of the rollback is ignored and the one of `run` is returned.
+fn in_savepoint<T>(
+    conn: &Connection,
+    name: &str,
+    run: impl FnOnce() -> PyResult<T>,
+) -> PyResult<T> {
+    conn.execute_batch(&format!("SAVEPOINT {}", name))
+        .map_err(|e| sqlite_error("Failed to start transaction", e))?;
+
+    let result = run().and_then(|value| {
+        conn.execute_batch(&format!("RELEASE {}", name))
+            .map_err(|e| sqlite_error("Failed to commit transaction", e))?;
+        Ok(value)
+    });
+    result.inspect_err(|_| {
+        let _ = conn.execute_batch(&format!("ROLLBACK TO {0}; RELEASE {0}", name));
+    })
+}
+
+/// Begins a transaction, or a savepoint with a unique name when one is already open.
+/// Returns the name of the savepoint, to give to finish_transaction().
+fn start_transaction(conn: &Connection, mode: &str) -> PyResult<Option<String>> {
+    if conn.is_autocommit() {
+        conn.execute_batch(&format!("BEGIN {}", mode))
+            .map_err(|e| sqlite_error("Failed to begin transaction", e))?;
+        return Ok(None);
+    }
+
+    let savepoint = format!(
+        "transaction_{}",
+        NEXT_SAVEPOINT.fetch_add(1, Ordering::Relaxed)
+    );
+    conn.execute_batch(&format!("SAVEPOINT {}", savepoint))
+        .map_err(|e| sqlite_error("Failed to begin transaction", e))?;
+    Ok(Some(savepoint))
+}
+
+/// Commits or rolls back what start_transaction() began. When the commit fails,
+/// everything is rolled back and the error of the commit is returned; the error of a
+/// rollback is ignored since the caller already has one to raise.
+fn finish_transaction(conn: &Connection, savepoint: Option<&str>, commit: bool) -> PyResult<()> {
+    let (commit_sql, rollback_sql) = match savepoint {
+        Some(name) => (
+            format!("RELEASE {}", name),
+            format!("ROLLBACK TO {0}; RELEASE {0}", name),
+        ),
+        None => ("COMMIT".to_string(), "ROLLBACK".to_string()),
+    };
+
+    if !commit {
+        let _ = conn.execute_batch(&rollback_sql);
+        return Ok(());
+    }
+    conn.execute_batch(&commit_sql).map_err(|e| {
+        let _ = conn.execute_batch(&rollback_sql);
+        sqlite_error("Failed to commit transaction", e)
+    })
+}
+
+/// Restores the pragmas changed by Database.bulk_load()
+fn restore_write_pragmas(
+    conn: &Connection,
+    journal_mode: &str,
+    synchronous: i64,
+) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "synchronous", synchronous)?;
+    conn.pragma_update_and_check(None, "journal_mode", journal_mode, |_| Ok(()))
+}
+
+/// Checks a transaction mode of begin() and returns it in uppercase
+fn transaction_mode(mode: &str) -> PyResult<String> {
+    const TRANSACTION_MODES: [&str; 3] = ["DEFERRED", "IMMEDIATE", "EXCLUSIVE"];
+
+    let mode = mode.to_uppercase();
+    if !TRANSACTION_MODES.contains(&mode.as_str()) {
+        return Err(PyRuntimeError::new_err(format!(
+            "\"mode\" must be one of the following: {}.\n\"{}\" is not correct.",
+            TRANSACTION_MODES.join(", "),
+            mode
+        )));
+    }
+    Ok(mode)
+}
+
+/// Checks that a name used in generated SQL is a plain identifier
+/// (letters, digits and underscores, not starting with a digit), so it cannot inject SQL
+fn validate_identifier(name: &str, kind: &str) -> PyResult<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !valid {
+        return Err(PyRuntimeError::new_err(format!(
+            "Invalid {} name \"{}\": only letters, digits and underscores are allowed, and it cannot start with a digit.",
+            kind, name
+        )));
+    }
+    Ok(())
+}
+
+/// Maps the type of a column given from Python to its SQL type.
+/// A Python builtin type is mapped to the matching SQLite type, while a string is
+/// used verbatim so any SQL type (and constraints) can be expressed.
+fn sql_column_type(column_type: &Bound<'_, PyAny>, table_name: &str) -> PyResult<String> {
+    if column_type.is_instance_of::<PyString>() {
+        let sql_type: String = column_type.extract()?;
+        if sql_type.trim().is_empty() {
+            return Err(PyRuntimeError::new_err(format!(
+                "Empty SQL type for the creation of the table \"{}\".",
+                table_name
+            )));
+        }
+        return Ok(sql_type);
+    }
+
+    let wrong_type = || {
+        PyRuntimeError::new_err(format!(
+            "Wrong type for the creation of the table \"{}\". Allowed types are valid Python builtin types: str, int, float, and bool, or a SQL type as a string.",
+            table_name
+        ))
+    };
+
+    // A type returns class "type" so we use its attribute "__name__"
+    let column_type_name: String = column_type
+        .getattr("__name__")
+        .map_err(|_| wrong_type())?
+        .extract()?;
+
+    let sql_type_mapping = match column_type_name.as_str() {
+        "str" => "TEXT",
+        "int" => "INTEGER",
+        "float" => "REAL",
+        "bool" => "BOOLEAN",
+        _ => return Err(wrong_type()),
+    };
+
+    Ok(sql_type_mapping.to_string())
+}
+
+/// Converts a value read from SQLite into the equivalent Python object.
+fn value_ref_to_py(py: Python<'_>, value: ValueRef<'_>) -> PyResult<PyObject> {
+    Ok(match value {
+        ValueRef::Null => py.None(),
+        ValueRef::Integer(i) => i.into_pyobject(py)?.into_any().unbind(),
+        ValueRef::Real(f) => f.into_pyobject(py)?.into_any().unbind(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t)
+            .into_pyobject(py)?
+            .into_any()
+            .unbind(),
+        ValueRef::Blob(b) => PyBytes::new(py, b).into_any().unbind(),
+    })
+}
+
+/// Converts a Python object into a value SQLite can store.
+fn py_to_value(item: &Bound<'_, PyAny>, options: ParamOptions) -> PyResult<Value> {
+    if item.is_none() {
+        Ok(Value::Null)
+    } else if item.is_instance_of::<PyBool>() {
+        Ok(Value::Integer(item.extract::<bool>()? as i64))
+    } else if item.is_instance_of::<PyInt>() {
+        options.large_ints.convert(item)
+    } else if item.is_instance_of::<PyFloat>() {
+        options.non_finite_floats.convert(item.extract::<f64>()?)
+    } else if let Ok(text) = item.downcast::<PyString>() {
+        options.surrogates.convert(text)
+    } else if let Some(data) = blob_bytes(item)? {
+        Ok(Value::Blob(data))
+    } else if let Some(text) = datetime_text(item)? {
+        Ok(Value::Text(text))
+    } else if let Some(value) = enum_value(item)? {
+        py_to_value(&value, options)
+    } else if let Some(number) = number_value(item)? {
+        py_to_value(&number, options)
+    } else {
+        Err(PyRuntimeError::new_err(format!(
+            "Unsupported type for \"{}\". Supported types are: None, str, int, bool, float, bytes, bytearray, memoryview, datetime, date, time, enum.Enum.",
+            item
+        )))
+    }
+}
+
+/// Checks the number of parameters given for a prepared query. SQLite counts the
+/// placeholders itself, ignoring the ones in string literals and comments.
+/// rusqlite's own error does not say how many parameters were given when there are too many.
+fn check_param_count(expected: usize, given: usize) -> PyResult<()> {
+    if expected == given {
+        return Ok(());
+    }
+
+    Err(PyRuntimeError::new_err(format!(
+        "The query expects {} parameter{} but {} {} given.",
+        expected,
+        if expected == 1 { "" } else { "s" },
+        given,
+        if given == 1 { "was" } else { "were" }
+    )))
+}
+
+/// Returns the content of the Python binary types stored as BLOBs:
+/// bytes, bytearray and memoryview (e.g. from numpy or struct). None for other types.
+fn blob_bytes(item: &Bound<'_, PyAny>) -> PyResult<Option<Vec<u8>>> {
+    if let Ok(bytes) = item.downcast::<PyBytes>() {
+        Ok(Some(bytes.as_bytes().to_vec()))
+    } else if let Ok(bytearray) = item.downcast::<PyByteArray>() {
+        Ok(Some(bytearray.to_vec()))
+    } else if item.is_instance_of::<PyMemoryView>() {
+        // tobytes() also handles non-contiguous views and item formats other than bytes
+        Ok(Some(item.call_method0("tobytes")?.extract::<Vec<u8>>()?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns the text stored for the Python datetime types, in the formats of SQLite's date
+/// and time functions: `YYYY-MM-DD HH:MM:SS[.ffffff]` for a datetime (the separator is a
+/// space, like in Python's sqlite3 module), `YYYY-MM-DD` for a date and `HH:MM:SS[.ffffff]`
+/// for a time. Aware values end with their UTC offset, e.g. `+02:00`. None for other types.
+fn datetime_text(item: &Bound<'_, PyAny>) -> PyResult<Option<String>> {
+    // A datetime is also a date, so it is checked first
+    if item.is_instance_of::<PyDateTime>() {
+        Ok(Some(item.call_method1("isoformat", (" ",))?.extract()?))
+    } else if item.is_instance_of::<PyDate>() || item.is_instance_of::<PyTime>() {
+        Ok(Some(item.call_method0("isoformat")?.extract()?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns the int, float or bool stored instead of a number of another type, e.g.
+/// `numpy.int64`, `numpy.float32` or `numpy.bool_`, or None for other objects.
+/// Only the types registered as `numbers.Integral` or `numbers.Real` are converted, like
+/// NumPy's integer and floating types, so a Decimal is not silently rounded to a float.
+fn number_value<'py>(item: &Bound<'py, PyAny>) -> PyResult<Option<Bound<'py, PyAny>>> {
+    // The classes are imported once, this runs for every parameter that is not a builtin
+    static INTEGRAL: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+    static REAL: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+    // bool is an int
+    if item.is_instance_of::<PyInt>() || item.is_instance_of::<PyFloat>() {
+        return Ok(None);
+    }
+    let py = item.py();
+    if item.is_instance(INTEGRAL.import(py, "numbers", "Integral")?)? {
+        // __index__ gives an exact int, even beyond 64 bits
+        return Ok(Some(py.import("operator")?.call_method1("index", (item,))?));
+    }
+    if item.is_instance(REAL.import(py, "numbers", "Real")?)? {
+        return Ok(Some(PyFloat::new(py, item.extract::<f64>()?).into_any()));
+    }
+
+    // numpy.bool_ is not a number for Python, it is named "bool" since NumPy 2
+    let item_type = item.get_type();
+    let name = item_type.name()?;
+    if item_type.module()?.to_cow()? == "numpy" && matches!(&*name.to_cow()?, "bool" | "bool_") {
+        return Ok(Some(item.call_method0("item")?));
+    }
+    Ok(None)
+}
+
+/// Returns the value of an enum member, stored instead of the member, or None if `item`
+/// is not an enum.Enum. IntEnum and StrEnum members are already ints and strs, so only
+/// the plain enums get here. Only int and str values are accepted, so a member is never
+/// stored as something it cannot be looked up by.
+fn enum_value<'py>(item: &Bound<'py, PyAny>) -> PyResult<Option<Bound<'py, PyAny>>> {
+    static ENUM: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+    if !item.is_instance(ENUM.import(item.py(), "enum", "Enum")?)? {
+        return Ok(None);
+    }
+
+    let value = item.getattr("value")?;
+    if value.is_instance_of::<PyInt>() || value.is_instance_of::<PyString>() {
+        Ok(Some(value))
+    } else {
+        Err(PyRuntimeError::new_err(format!(
+            "The value of the enum member \"{}\" must be an int or a str, not {}.",
+            item,
+            value.get_type().name()?
+        )))
+    }
+}
+
+/// Converts the Python parameters of a query (list, tuple, dict or single value) to SQL types.
+/// Returns the query, rewritten if a parameter was expanded for an IN clause, with the parameters.
+fn query_params(
+    query: &str,
+    params: &Bound<'_, PyAny>,
+    options: ParamOptions,
+) -> PyResult<(String, Vec<Box<dyn ToSql>>)> {
+    if let Ok(named) = params.downcast::<PyDict>() {
+        return Ok((
+            query.to_string(),
+            named_query_params(query, named, options)?,
+        ));
+    }
+
+    // Convert Python list/tuple to Vec of PyAny
+    // A single value is a one-parameter list, so `fetch_all(query, 1)` works like `fetch_all(query, [1])`
+    // Raise an error if it is none of these
+    let params: Vec<Bound<'_, PyAny>> = match params.get_type().name()?.to_str()? {
+        "list" => params.downcast::<PyList>()?.iter().collect::<Vec<_>>(),
+        "tuple" => params.downcast::<PyTuple>()?.iter().collect::<Vec<_>>(),
+        "int" | "float" | "str" | "bool" | "bytes" | "bytearray" | "memoryview" => {
+            vec![params.clone()]
+        }
+        _ => {
+            return Err(PyRuntimeError::new_err(
+                "Unsupported parameter type. Expected a list, a tuple, a dict or a single value (int, float, str, bool or bytes).",
+            ));
+        }
+    };
+
+    // A list, tuple or set parameter is expanded into several placeholders for IN clauses
+    let (query, params) = expand_sequence_params(query, params)?;
+
+    // Convert all parameters to SQL-compatible types
+    // Box<T> is a smart pointer that puts data on the heap rather than the stack.
+    //We need it here because:
+
+    // - Different parameter types have different sizes (String vs i64)
+    // - We need to store them in a Vec together
+
+    // dyn is used for dynamic dispatch with traits. In our case:
+
+    // ToSql is a trait implemented by various types (String, i64, etc.)
+    // dyn ToSql means "any type that implements ToSql"
+    // We need Box<dyn ToSql> to store different types that implement ToSql in our Vec
+    let sql_params: Vec<Box<dyn ToSql>> = params
+        .iter() // Iterate over Python parameters
+        .map(|item| sql_param(item, options))
+        .collect::<PyResult<Vec<_>>>()?; // Collect into Result<Vec<Box<dyn ToSql>>>
+                                         // Final ? operator unwraps the PyResult
+
+    Ok((query, sql_params))
+}
+
+/// Converts the values of a dict to the parameters of the named placeholders of a query
+/// (`:name`, `@name` or `$name`), in the order SQLite numbers them: the order in which
+/// each name first appears. The keys of the dict are the names without their prefix.
+fn named_query_params(
+    query: &str,
+    params: &Bound<'_, PyDict>,
+    options: ParamOptions,
+) -> PyResult<Vec<Box<dyn ToSql>>> {
+    let mut names: Vec<&str> = Vec::new();
+    for (_, placeholder) in placeholders(query) {
+        if placeholder == "?" {
+            return Err(PyRuntimeError::new_err(
+                "Parameters given as a dict need named placeholders like :name, the query uses \"?\".",
+            ));
+        }
+        if !names.contains(&placeholder) {
+            names.push(placeholder);
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|name| match params.get_item(&name[1..])? {
+            Some(item) => sql_param(&item, options),
+            None => Err(PyRuntimeError::new_err(format!(
+                "No value given for the named parameter \"{}\".",
+                name
+            ))),
+        })
+        .collect()
+}
+
+/// Converts a Python parameter to a SQL type
+fn sql_param(item: &Bound<'_, PyAny>, options: ParamOptions) -> PyResult<Box<dyn ToSql>> {
+    // For each parameter, try to convert it to a SQL type:
+    if item.is_none() {
+        // Python None -> SQL NULL -> Box<dyn ToSql>
+        Ok(Box::new(Null))
+    } else if item.is_instance_of::<PyInt>() {
+        // Python int -> Rust i64 -> Box<dyn ToSql>
+        // Ints that do not fit in 64 bits are handled as configured, see LargeInts
+        Ok(Box::new(options.large_ints.convert(item)?))
+    } else if item.is_instance_of::<PyFloat>() {
+        // Python float -> Rust f64 -> Box<dyn ToSql>
+        // NaN and infinite floats are handled as configured, see NonFiniteFloats
+        Ok(Box::new(
+            options.non_finite_floats.convert(item.extract::<f64>()?)?,
+        ))
+    } else if let Ok(text) = item.downcast::<PyString>() {
+        // Python str -> Rust String (or BLOB with lone surrogates) -> Box<dyn ToSql>
+        // Strings that are not valid UTF-8 are handled as configured, see Surrogates
+        Ok(Box::new(options.surrogates.convert(text)?))
+    } else if item.is_instance_of::<PyBool>() {
+        // Python bool -> Rust bool -> Box<dyn ToSql>
+        Ok(Box::new(item.extract::<bool>()?))
+    } else if let Some(data) = blob_bytes(item)? {
+        // Python bytes/bytearray/memoryview -> Rust Vec<u8> (BLOB) -> Box<dyn ToSql>
+        Ok(Box::new(data))
+    } else if let Some(text) = datetime_text(item)? {
+        // Python datetime/date/time -> Rust String (ISO 8601 TEXT) -> Box<dyn ToSql>
+        Ok(Box::new(text))
+    } else if let Some(value) = enum_value(item)? {
+        // Python enum member -> its int or str value -> Box<dyn ToSql>
+        sql_param(&value, options)
+    } else if let Some(number) = number_value(item)? {
+        // NumPy scalar or other number type -> Python int, float or bool -> Box<dyn ToSql>
+        sql_param(&number, options)
+    } else {
+        // Unsupported type -> PyErr
+        Err(PyRuntimeError::new_err(
+            "Unsupported parameter type in query.",
+        ))
+    }
+}
+
+/// Reads a row of `PRAGMA foreign_key_check`, see Database.foreign_key_check()
+fn foreign_key_violation(
+    row: &rusqlite::Row<'_>,
+) -> rusqlite::Result<(String, Option<i64>, String, i64)> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Builds the script returned by Database.dump()
+fn dump_database(py: Python<'_>, conn: &Connection) -> PyResult<String> {
+    let query_error = |e| sqlite_error("Query execution error", e);
+
+    // The internal tables of the virtual tables, recreated by their CREATE VIRTUAL TABLE
+    let shadow_tables: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_list WHERE type = 'shadow'")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(query_error)?;
+
+    // sqlite_sequence is created along with the first AUTOINCREMENT table,
+    // so only its rows are restored, after all the tables
+    let tables: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT name, sql FROM sqlite_schema WHERE type = 'table' AND sql NOT NULL \
+             ORDER BY name = 'sqlite_sequence', rowid",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(query_error)?;
+
+    let mut script = String::from("PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n");
+    let mut dumped_rows = 0;
+    for (table, sql) in tables {
+        if shadow_tables.contains(&table) {
+            continue;
+        }
+        if table == "sqlite_sequence" {
+            script.push_str("DELETE FROM sqlite_sequence;\n");
+        } else if table.starts_with("sqlite_") {
+            // Other internal tables, like the statistics of ANALYZE
+            continue;
+        } else {
+            script.push_str(&sql);
+            script.push_str(";\n");
+        }
+
+        // The generated columns cannot be inserted, they are computed again
+        let columns: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_xinfo(?) WHERE hidden = 0")
+            .and_then(|mut stmt| stmt.query_map([&table], |row| row.get(0))?.collect())
+            .map_err(query_error)?;
+        let columns: Vec<String> = columns.iter().map(|c| quote_identifier(c)).collect();
+
+        let insert = format!(
+            "INSERT INTO {}({}) VALUES(",
+            quote_identifier(&table),
+            columns.join(",")
+        );
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM {}",
+                columns.join(", "),
+                quote_identifier(&table)
+            ))
+            .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+        let mut rows = stmt.query([]).map_err(query_error)?;
+        while let Some(row) = rows.next().map_err(query_error)? {
+            script.push_str(&insert);
+            for i in 0..columns.len() {
+                if i > 0 {
+                    script.push(',');
+                }
+                script.push_str(&sql_literal(row.get_ref(i).map_err(query_error)?));
+            }
+            script.push_str(");\n");
+
+            // Let Python handle Ctrl+C on very large databases
+            dumped_rows += 1;
+            if dumped_rows % 10_000 == 0 {
+                py.check_signals()?;
+            }
+        }
+    }
+
+    // The indexes, triggers and views come after the data, in the order they were created
+    // since a view can depend on another one
+    let mut stmt = conn
+        .prepare(
+            "SELECT tbl_name, sql FROM sqlite_schema \
+             WHERE type IN ('index', 'trigger', 'view') AND sql NOT NULL ORDER BY rowid",
+        )
+        .map_err(|e| sqlite_error("Failed to prepare query", e))?;
+    let mut rows = stmt.query([]).map_err(query_error)?;
+    while let Some(row) = rows.next().map_err(query_error)? {
+        let table: String = row.get(0).map_err(query_error)?;
+        if !shadow_tables.contains(&table) {
+            script.push_str(row.get_ref(1).map_err(query_error)?.as_str().unwrap_or(""));
+            script.push_str(";\n");
+        }
+    }
+
+    script.push_str("COMMIT;\n");
+    Ok(script)
+}
+
+/// Runs a statement built with SQL fragments given by the caller (constraints, conditions,
+/// a view's query...), and fails without running anything if they add another statement,
+/// e.g. `DEFAULT 0; DROP TABLE users`. rusqlite's execute() only checks it with its
+/// `extra_check` feature.
+fn execute_single(conn: &Connection, sql: &str) -> rusqlite::Result<()> {
+    let mut statements = Batch::new(conn, sql);
+    let Some(mut statement) = statements.next()? else {
+        return Ok(());
+    };
+    if statements.next()?.is_some() {
+        return Err(rusqlite::Error::MultipleStatement);
+    }
+    statement.execute([]).map(|_| ())
+}
+
+/// Quotes a table or column name for SQL, e.g. `my"name` gives `"my""name"`
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Builds the INSERT statement of the insert methods, with a placeholder per column
+fn insert_sql(table: &str, columns: &[String]) -> String {
+    let columns: Vec<String> = columns.iter().map(|c| quote_identifier(c)).collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_identifier(table),
+        columns.join(", "),
+        placeholders
+    )
+}
+
+/// Builds the WHERE clause of a dict of column values combined with AND, e.g.
+/// ` WHERE "t"."a" = ? AND "t"."b" IS NULL`, and returns it with the values to bind.
+/// An empty dict gives an empty clause.
+fn where_clause<'py>(
+    table: &str,
+    conditions: &Bound<'py, PyDict>,
+) -> PyResult<(String, Vec<Bound<'py, PyAny>>)> {
+    if conditions.is_empty() {
+        return Ok((String::new(), Vec::new()));
+    }
+
+    let mut clauses = Vec::with_capacity(conditions.len());
+    let mut values = Vec::with_capacity(conditions.len());
+    for (column, value) in conditions.iter() {
+        let column = column.extract::<String>()?;
+        validate_identifier(&column, "column")?;
+        // Qualified, an unknown column is an error and not read as a string literal
+        let column = format!("{}.{}", quote_identifier(table), quote_identifier(&column));
+        // "= NULL" never matches
+        if value.is_none() {
+            clauses.push(format!("{} IS NULL", column));
+        } else {
+            clauses.push(format!("{} = ?", column));
+            values.push(value);
+        }
+    }
+    Ok((format!(" WHERE {}", clauses.join(" AND ")), values))
+}
+
+/// The result of the helpers called with `dry_run=True`: a tuple of the statement and
+/// of its parameters converted like they would be bound
+fn dry_run_result(py: Python<'_>, sql: &str, values: &[Value]) -> PyResult<PyObject> {
+    let params = values
+        .iter()
+        .map(|value| value_ref_to_py(py, value.into()))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok((sql, PyList::new(py, params)?)
+        .into_pyobject(py)?
+        .into_any()
+        .unbind())
+}
+
+/// Builds the RETURNING clause of the columns to return, or `*`
+fn returning_clause(returning: &[String]) -> PyResult<String> {
+    if returning.is_empty() {
+        return Err(PyRuntimeError::new_err(
+            "\"returning\" must contain at least one column, or \"*\".",
+        ));
+    }
+    for column in returning {
+        if column != "*" {
+            validate_identifier(column, "column")?;
+        }
+    }
+    Ok(format!(" RETURNING {}", returning.join(", ")))
+}
+
+/// Writes a value as an SQL literal that gives back the same value and type
+fn sql_literal(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        // SQLite reads 1e999 as an infinite REAL
+        ValueRef::Real(f) if f.is_infinite() => {
+            if f > 0.0 { "1e999" } else { "-1e999" }.to_string()
+        }
+        // Debug keeps the decimal point of whole numbers (3.0 instead of 3),
+        // otherwise the value would be read back as an INTEGER
+        ValueRef::Real(f) => format!("{:?}", f),
+        ValueRef::Text(t) => format!("'{}'", String::from_utf8_lossy(t).replace('\'', "''")),
+        ValueRef::Blob(b) => {
+            let hex: String = b.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+/// Returns the byte positions of the `?` placeholders in a query,
+/// ignoring the ones inside string literals, quoted identifiers and comments.
+fn placeholder_positions(query: &str) -> Vec<usize> {
+    placeholders(query)
+        .into_iter()
+        .filter(|(_, placeholder)| *placeholder == "?")
+        .map(|(position, _)| position)
+        .collect()
+}
+
+/// Returns the byte position and the text of the placeholders in a query: `?` (without
+/// the number of a `?NNN`) or a name like `:name`, `@name` or `$name`. The ones inside
+/// string literals, quoted identifiers and comments are ignored.
+fn placeholders(query: &str) -> Vec<(usize, &str)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    let mut placeholders = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    let mut after_word = false;
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Skip everything until the closing quote. A doubled quote ('') is an escaped
+            // quote, which simply reads as closing then reopening the literal.
+            '\'' | '"' | '`' => {
+                for (_, next) in chars.by_ref() {
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '[' => {
+                for (_, next) in chars.by_ref() {
+                    if next == ']' {
+                        break;
+                    }
+                }
+            }
+            '-' if matches!(chars.peek(), Some((_, '-'))) => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                chars.next();
+                let mut previous = ' ';
+                for (_, next) in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            '?' => placeholders.push((i, "?")),
+            // `$` can also be part of an identifier, like `price$usd`
+            ':' | '@' | '$' if !after_word && chars.peek().is_some_and(|&(_, n)| is_word(n)) => {
+                let mut end = query.len();
+                while let Some(&(j, next)) = chars.peek() {
+                    if !is_word(next) {
+                        end = j;
+                        break;
+                    }
+                    chars.next();
+                }
+                placeholders.push((i, &query[i..end]));
+            }
+            _ => {}
+        }
+        after_word = is_word(c);
+    }
+
+    placeholders
+}
+
+/// Expands the list, tuple and set parameters so they can be used in IN clauses:
+/// `WHERE id IN (?)` with `[[1, 2, 3]]` becomes `WHERE id IN (?, ?, ?)` with `[1, 2, 3]`.
+/// An empty sequence becomes `NULL`, which matches nothing.
+fn expand_sequence_params<'py>(
+    query: &str,
+    params: Vec<Bound<'py, PyAny>>,
+) -> PyResult<(String, Vec<Bound<'py, PyAny>>)> {
+    let is_sequence = |p: &Bound<'py, PyAny>| {
+        p.is_instance_of::<PyList>()
+            || p.is_instance_of::<PyTuple>()
+            || p.is_instance_of::<PySet>()
+            || p.is_instance_of::<PyFrozenSet>()
+    };
+
+    // Nothing to rewrite, we keep the query as it is
+    if !params.iter().any(is_sequence) {
+        return Ok((query.to_string(), params));
+    }
+
+    let positions = placeholder_positions(query);
+    if positions.len() != params.len() {
+        return Err(PyRuntimeError::new_err(format!(
+            "Cannot expand a sequence parameter: the query has {} \"?\" placeholders but {} parameters were given.",
+            positions.len(),
+            params.len()
+        )));
+    }
+
+    let mut expanded_query = String::with_capacity(query.len());
+    let mut expanded_params = Vec::with_capacity(params.len());
+    let mut last = 0;
+
+    for (position, param) in positions.into_iter().zip(params) {
+        expanded_query.push_str(&query[last..position]);
+        last = position + 1;
+
+        // A numbered placeholder like ?1 cannot be expanded
+        if query[last..].starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(PyRuntimeError::new_err(
+                "Sequence parameters can only be used with \"?\" placeholders, not numbered ones.",
+            ));
+        }
+
+        if is_sequence(&param) {
+            let items = param.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+            if items.is_empty() {
+                expanded_query.push_str("NULL");
+            } else {
+                expanded_query.push_str(&vec!["?"; items.len()].join(", "));
+            }
+            expanded_params.extend(items);
+        } else {
+            expanded_query.push('?');
+            expanded_params.push(param);
+        }
+    }
+    expanded_query.push_str(&query[last..]);
+
+    Ok((expanded_query, expanded_params))
+}
+
+#[pymodule]
+fn rust_sqlite_wrapper(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Database>()?;
+    m.add_class::<Blob>()?;
+    m.add_class::<Transactional>()?;
+    m.add_class::<Transaction>()?;
+    m.add_class::<BulkLoad>()?;
+    m.add_class::<Cursor>()?;
+    m.add("DatabaseError", m.py().get_type::<DatabaseError>())?;
+    m.add("IntegrityError", m.py().get_type::<IntegrityError>())?;
+    m.add("OperationalError", m.py().get_type::<OperationalError>())?;
+    m.add("QueryTimeout", m.py().get_type::<QueryTimeout>())?;
+    m.add("RowLimitExceeded", m.py().get_type::<RowLimitExceeded>())?;
+    for (name, code) in AUTHORIZER_CODES {
+        m.add(name, code)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poisoned_lock_is_recovered() {
+        let db = Database::with_connection(Connection::open_in_memory().unwrap(), ":memory:");
+
+        // A thread panicking while it holds the lock poisons the mutex
+        let connection = Arc::clone(&db.connection);
+        let _ = std::thread::spawn(move || {
+            let _guard = connection.lock().unwrap();
+            panic!("poisoning the lock");
+        })
+        .join();
+        assert!(db.connection.is_poisoned());
+
+        let conn = db.lock_connection().unwrap();
+        conn.execute_batch("CREATE TABLE users (name TEXT)")
+            .unwrap();
+        assert_eq!(
+            conn.execute("INSERT INTO users (name) VALUES (?)", ["rayan"])
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn placeholders_in_literals_and_comments_are_ignored() {
+        let query = "SELECT '?', \"a?\" FROM t -- ?\nWHERE a = ? /* ? */ AND b IN (?)";
+        let positions = placeholder_positions(query);
+
+        assert_eq!(positions.len(), 2);
+        assert!(positions.iter().all(|&p| &query[p..p + 1] == "?"));
+    }
+
+    #[test]
+    fn named_placeholders_are_found() {
+        let query = "SELECT ':skipped', price$usd FROM t WHERE a = :name AND b = @other_1 OR c = ?";
+        let found: Vec<&str> = placeholders(query).into_iter().map(|(_, p)| p).collect();
+
+        assert_eq!(found, [":name", "@other_1", "?"]);
+    }
+
+    #[test]
+    fn sql_literals_keep_their_type() {
+        assert_eq!(sql_literal(ValueRef::Null), "NULL");
+        assert_eq!(sql_literal(ValueRef::Integer(-3)), "-3");
+        assert_eq!(sql_literal(ValueRef::Real(3.0)), "3.0");
+        assert_eq!(sql_literal(ValueRef::Real(f64::NEG_INFINITY)), "-1e999");
+        assert_eq!(sql_literal(ValueRef::Text(b"it's")), "'it''s'");
+        assert_eq!(sql_literal(ValueRef::Blob(&[0, 255])), "X'00ff'");
+    }
+
+    #[test]
+    fn non_finite_floats_follow_the_option() {
+        assert!(NonFiniteFloats::Error.convert(f64::NAN).is_err());
+        assert_eq!(
+            NonFiniteFloats::Error.convert(1.5).unwrap(),
+            Value::Real(1.5)
+        );
+        assert_eq!(
+            NonFiniteFloats::Null.convert(f64::INFINITY).unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            NonFiniteFloats::Text.convert(f64::NAN).unwrap(),
+            Value::Text("nan".to_string())
+        );
+        assert_eq!(
+            NonFiniteFloats::Text.convert(f64::NEG_INFINITY).unwrap(),
+            Value::Text("-inf".to_string())
+        );
+    }
 }
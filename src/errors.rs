@@ -0,0 +1,93 @@
+use pyo3::{create_exception, exceptions::PyRuntimeError, types::PyAnyMethods, PyErr, Python};
+use rusqlite::ErrorCode;
+
+// Python exceptions raised for SQLite errors, following the DB-API names.
+// DatabaseError inherits from RuntimeError, so `except RuntimeError` keeps working.
+create_exception!(
+    rust_sqlite_wrapper,
+    DatabaseError,
+    PyRuntimeError,
+    "Base class of the errors raised by the SQLite engine."
+);
+create_exception!(
+    rust_sqlite_wrapper,
+    IntegrityError,
+    DatabaseError,
+    "A constraint was violated (UNIQUE, NOT NULL, FOREIGN KEY, CHECK...)."
+);
+create_exception!(
+    rust_sqlite_wrapper,
+    OperationalError,
+    DatabaseError,
+    "The database could not perform the operation: locked or busy database, \
+missing table, invalid SQL, read-only database..."
+);
+create_exception!(
+    rust_sqlite_wrapper,
+    QueryTimeout,
+    OperationalError,
+    "The query was cancelled because it ran longer than its timeout_ms."
+);
+create_exception!(
+    rust_sqlite_wrapper,
+    RowLimitExceeded,
+    DatabaseError,
+    "The query returned more rows than its max_rows."
+);
+
+/// Maps a rusqlite error onto the matching Python exception.
+/// `context` is prepended to the message, e.g. "Failed to execute query: UNIQUE constraint failed".
+///
+/// The SQLite result codes are attached to the exception, so callers can branch on them
+/// without parsing the message: `sqlite_errorcode` is the extended code
+/// (e.g. 2067 for SQLITE_CONSTRAINT_UNIQUE) and `sqlite_primary_errorcode` the primary one
+/// (e.g. 19 for SQLITE_CONSTRAINT). Both are None for errors that do not come from SQLite.
+pub(crate) fn sqlite_error(context: &str, error: rusqlite::Error) -> PyErr {
+    let codes = match &error {
+        rusqlite::Error::SqliteFailure(failure, _) => {
+            Some((failure.extended_code, failure.extended_code & 0xff))
+        }
+        _ => None,
+    };
+
+    let err = exception_for(context, error);
+    Python::with_gil(|py| {
+        let value = err.value(py);
+        // Setting an attribute on a fresh exception instance cannot fail
+        let _ = value.setattr("sqlite_errorcode", codes.map(|(extended, _)| extended));
+        let _ = value.setattr(
+            "sqlite_primary_errorcode",
+            codes.map(|(_, primary)| primary),
+        );
+    });
+    err
+}
+
+/// Picks the exception class matching a rusqlite error
+fn exception_for(context: &str, error: rusqlite::Error) -> PyErr {
+    let message = format!("{}: {}", context, error);
+
+    match &error {
+        rusqlite::Error::SqliteFailure(failure, _) => match failure.code {
+            ErrorCode::ConstraintViolation => IntegrityError::new_err(message),
+            ErrorCode::DatabaseBusy
+            | ErrorCode::DatabaseLocked
+            | ErrorCode::ReadOnly
+            | ErrorCode::OperationInterrupted
+            | ErrorCode::CannotOpen
+            | ErrorCode::DiskFull
+            | ErrorCode::SystemIoFailure
+            | ErrorCode::PermissionDenied
+            | ErrorCode::FileLockingProtocolFailed
+            | ErrorCode::SchemaChanged
+            | ErrorCode::TooBig
+            // SQLITE_ERROR is used for missing tables, syntax errors...
+            | ErrorCode::Unknown => OperationalError::new_err(message),
+            _ => DatabaseError::new_err(message),
+        },
+        rusqlite::Error::SqlInputError { .. } | rusqlite::Error::UserFunctionError(_) => {
+            OperationalError::new_err(message)
+        }
+        _ => DatabaseError::new_err(message),
+    }
+}
@@ -1,7 +1,17 @@
+import datetime
+import enum
+import fractions
+import numbers
 import unittest
 import os
+import sys
+import tempfile
+import threading
+import time
+from decimal import Decimal
 
-from rust_sqlite_wrapper import Database
+from rust_sqlite_wrapper import Database, DatabaseError, IntegrityError, OperationalError, QueryTimeout, RowLimitExceeded
+from rust_sqlite_wrapper import SQLITE_ATTACH, SQLITE_DENY, SQLITE_IGNORE, SQLITE_INSERT, SQLITE_OK, SQLITE_READ
 
 class TestRustSQLiteWrapper(unittest.TestCase):
 
@@ -21,6 +31,8 @@ class TestRustSQLiteWrapper(unittest.TestCase):
 
     def setUp(self):
         """Initialize a fresh database instance before each test."""
+        if os.path.exists(TestRustSQLiteWrapper.TEST_DB_NAME):
+            os.remove(TestRustSQLiteWrapper.TEST_DB_NAME)
         self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME)
 
     def tearDown(self):
@@ -43,6 +55,477 @@ class TestRustSQLiteWrapper(unittest.TestCase):
         with self.assertRaises(RuntimeError):
             self.db.execute_raw_query("INSERT INTO users (name, age, is_underage) VALUES (?,?,?)", "wrong type")
 
+    def test_execute_many_statements(self):
+        """Test running several parameterized statements atomically."""
+        self.db.execute_raw_query("CREATE TABLE accounts (id INTEGER PRIMARY KEY, balance INTEGER CHECK (balance >= 0))", [])
+        self.db.execute_raw_query("INSERT INTO accounts (id, balance) VALUES (1, 100), (2, 0)", [])
+        transfer = lambda amount: [
+            ("UPDATE accounts SET balance = balance - ? WHERE id = ?", [amount, 1]),
+            ("UPDATE accounts SET balance = balance + :amount WHERE id = :id", {"amount": amount, "id": 2}),
+            ("DELETE FROM accounts WHERE id IN (?)", [[3, 4]]),
+        ]
+
+        self.assertEqual(self.db.execute_many_statements(transfer(60)), [1, 1, 0])
+        # The first statement fails the CHECK, the second one is not applied either
+        with self.assertRaises(IntegrityError):
+            self.db.execute_many_statements(transfer(60))
+        self.assertEqual(self.db.fetch_all("SELECT balance FROM accounts ORDER BY id", [], typed=True), [[40], [60]])
+
+        # Inside a transaction, only the statements of the failing call are undone
+        self.db.begin()
+        self.db.execute_many_statements(transfer(10))
+        with self.assertRaises(RuntimeError):
+            self.db.execute_many_statements([("UPDATE accounts SET balance = 0", []), ("UPDATE accounts SET balance = ?", [])])
+        self.db.commit()
+        self.assertEqual(self.db.fetch_all("SELECT balance FROM accounts ORDER BY id", [], typed=True), [[30], [70]])
+        self.assertEqual(self.db.execute_many_statements([]), [])
+
+    def test_in_clause_expansion(self):
+        """Test that list, tuple and set parameters are expanded for IN clauses."""
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS numbers (n INTEGER)", [])
+        for n in range(5):
+            self.db.execute_raw_query("INSERT INTO numbers (n) VALUES (?)", [n])
+
+        self.db.execute_raw_query("DELETE FROM numbers WHERE n IN (?)", [(0, 1)])
+        self.assertEqual(
+            self.db.fetch_all("SELECT n FROM numbers WHERE n IN (?) AND n != ?", [[2, 3, 4], 4]),
+            [["2"], ["3"]],
+        )
+        self.assertEqual(self.db.fetch_all("SELECT n FROM numbers WHERE n IN (?)", [{4}]), [["4"]])
+        self.assertEqual(self.db.fetch_all("SELECT n FROM numbers WHERE n IN (?)", [[]]), [])
+
+    def test_blob_round_trip(self):
+        """Test that bytes are stored as BLOBs and fetched back unchanged."""
+        self.db.create_table("files", {"data": "BLOB"})
+        self.db.execute_raw_query("INSERT INTO files (data) VALUES (?)", [b"\x00hi\xff"])
+        self.assertEqual(self.db.fetch_all("SELECT data FROM files", []), [[b"\x00hi\xff"]])
+
+    def test_none_parameter(self):
+        """Test that None is bound as NULL in both execute_raw_query and fetch_all."""
+        self.db.create_table("nullable", {"id": int, "name": str})
+        self.db.execute_raw_query("INSERT INTO nullable (id, name) VALUES (?, ?)", [1, "rayan"])
+        self.db.execute_raw_query("UPDATE nullable SET name = ? WHERE id = ?", [None, 1])
+        self.assertEqual(self.db.fetch_all("SELECT name FROM nullable", []), [["NULL"]])
+        self.assertEqual(self.db.fetch_all("SELECT id FROM nullable WHERE name IS ?", [None]), [["1"]])
+
+    def test_fetch_all_with_columns(self):
+        """Test fetching the column names with the rows."""
+        self.db.create_table("people", {"name": str, "age": int})
+        self.db.insert("people", {"name": "rayan", "age": 27})
+        self.assertEqual(
+            self.db.fetch_all_with_columns("SELECT name, age AS years FROM people", []),
+            (["name", "years"], [["rayan", "27"]]),
+        )
+
+    def test_bytearray_and_memoryview_params(self):
+        """Test that bytearray and memoryview parameters are stored as BLOBs."""
+        self.db.create_table("files", {"data": "BLOB"})
+        self.db.execute_raw_query("INSERT INTO files VALUES (?)", [bytearray(b"abc")])
+        self.db.execute_raw_query("INSERT INTO files VALUES (?)", (memoryview(b"xabcx")[1:4],))
+        self.db.insert_many("files", [{"data": memoryview(bytearray(b"abc"))}])
+
+        self.assertEqual(self.db.fetch_all("SELECT data FROM files", []), [[b"abc"]] * 3)
+        self.assertEqual(self.db.fetch_all("SELECT COUNT(*) FROM files WHERE data = ?", bytearray(b"abc")), [["3"]])
+
+        with self.db.open_blob("files", "data", 1, read_only=False) as blob:
+            blob.write(bytearray(b"x"))
+            blob.write(memoryview(b"yz"))
+        self.assertEqual(self.db.fetch_all("SELECT data FROM files WHERE rowid = 1", []), [[b"xyz"]])
+
+    def test_enum_params(self):
+        """Test that enum members are stored as their value and can be converted back."""
+        class Status(enum.Enum):
+            ACTIVE = 1
+            BANNED = 2
+
+        class Role(enum.Enum):
+            ADMIN = "admin"
+
+        class Size(enum.Enum):
+            LARGE = 1.5
+
+        self.db.create_table("users", {"name": str, "status": int, "role": str})
+        self.db.insert("users", {"name": "Ray", "status": Status.ACTIVE, "role": Role.ADMIN})
+        self.db.execute_raw_query(
+            "INSERT INTO users (name, status, role) VALUES (?, ?, ?)", ["Tom", Status.BANNED, None]
+        )
+
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users WHERE role = ?", [Role.ADMIN]), [["Ray"]])
+        self.assertEqual(
+            self.db.fetch_all("SELECT name, status FROM users WHERE status = :status", {"status": Status.BANNED}),
+            [["Tom", "2"]],
+        )
+        self.assertEqual(
+            self.db.fetch_all("SELECT status, role FROM users", [], converters={"status": Status, "role": Role}),
+            [[Status.ACTIVE, Role.ADMIN], [Status.BANNED, None]],
+        )
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT ?", [Size.LARGE])
+
+    def test_number_params(self):
+        """Test that numbers of other types, like NumPy scalars, are stored as ints and floats."""
+        # Stands in for numpy.int64, which NumPy registers as a numbers.Integral
+        class Int64:
+            def __init__(self, value):
+                self.value = value
+
+            def __index__(self):
+                return self.value
+
+        numbers.Integral.register(Int64)
+
+        self.db.create_table("measures", {"count": int, "ratio": float})
+        self.db.insert("measures", {"count": Int64(3), "ratio": fractions.Fraction(1, 4)})
+        self.db.execute_raw_query("INSERT INTO measures (count, ratio) VALUES (?, ?)", [Int64(2**62), fractions.Fraction(1, 2)])
+
+        self.assertEqual(
+            self.db.fetch_all("SELECT count, ratio FROM measures", [], typed=True),
+            [[3, 0.25], [2**62, 0.5]],
+        )
+        self.assertEqual(self.db.fetch_all("SELECT ratio FROM measures WHERE count = ?", [Int64(3)]), [["0.25"]])
+
+        # A Decimal is not rounded to a float, and other objects are still refused
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT ?", [Decimal("0.1")])
+        with self.assertRaises(RuntimeError):
+            self.db.insert("measures", {"count": object()})
+
+    def test_datetime_params(self):
+        """Test that datetime, date and time objects are stored in SQLite's text formats."""
+        self.db.create_table("events", {"at": str, "day": str, "hour": str})
+        at = datetime.datetime(2025, 1, 2, 10, 30, 5, 250)
+        self.db.insert("events", {"at": at, "day": datetime.date(2025, 1, 2), "hour": datetime.time(10, 30)})
+        self.db.insert_many("events", [{"at": at.replace(microsecond=0), "day": None, "hour": datetime.time(8, 0, 0, 1)}])
+
+        self.assertEqual(
+            self.db.fetch_all("SELECT at, day, hour FROM events", []),
+            [
+                ["2025-01-02 10:30:05.000250", "2025-01-02", "10:30:00"],
+                ["2025-01-02 10:30:05", "NULL", "08:00:00.000001"],
+            ],
+        )
+        # The values work with SQLite's date and time functions
+        self.assertEqual(
+            self.db.fetch_all("SELECT date(?), time(?), datetime(?)", [datetime.date(2025, 1, 2), datetime.time(10, 30), at]),
+            [["2025-01-02", "10:30:00", "2025-01-02 10:30:05"]],
+        )
+        aware = datetime.datetime(2025, 1, 2, 10, 0, tzinfo=datetime.timezone(datetime.timedelta(hours=2)))
+        self.assertEqual(self.db.fetch_all("SELECT ?, datetime(?)", [aware, aware]), [["2025-01-02 10:00:00+02:00", "2025-01-02 08:00:00"]])
+        self.assertEqual(
+            self.db.fetch_all("SELECT day FROM events WHERE day = :day", {"day": datetime.date(2025, 1, 2)}),
+            [["2025-01-02"]],
+        )
+
+    def test_single_value_params(self):
+        """Test passing a single value instead of a list of parameters."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.execute_raw_query("INSERT INTO users (name, age) VALUES ('Ray', ?)", 27)
+        self.db.execute_raw_query("INSERT INTO users (name, age) VALUES (?, 12)", "Tom")
+
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users WHERE age = ?", 27), [["Ray"]])
+        self.assertEqual(self.db.fetch_all("SELECT age FROM users WHERE name = ?", "Tom"), [["12"]])
+        self.assertEqual(self.db.fetch_all("SELECT ? * 2", 1.25), [["2.5"]])
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT ?", {"a": 1})
+
+    def test_named_params(self):
+        """Test passing the parameters as a dict for named placeholders."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.execute_raw_query("INSERT INTO users (name, age) VALUES (:name, @age)", {"age": 27, "name": "Ray"})
+
+        self.assertEqual(
+            self.db.fetch_all("SELECT name, age FROM users WHERE age >= :min AND age < :min + $span", {"min": 20, "span": 10}),
+            [["Ray", "27"]],
+        )
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT name FROM users WHERE age = :age", {"other": 1})
+
+    def test_param_style(self):
+        """Test enforcing one kind of parameters."""
+        self.db.create_table("users", {"name": str})
+        self.assertIsNone(self.db.param_style)
+
+        self.db.param_style = "named"
+        self.db.execute_raw_query("INSERT INTO users (name) VALUES (:name)", {"name": "Ray"})
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT name FROM users WHERE name = ?", ["Ray"])
+
+        self.db.param_style = "qmark"
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users WHERE name = ?", ["Ray"]), [["Ray"]])
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_value("SELECT name FROM users WHERE name = :name", {"name": "Ray"})
+        # The internal queries are not affected
+        self.db.insert("users", {"name": "Tom"})
+
+        with self.assertRaises(RuntimeError):
+            self.db.param_style = "format"
+        with self.assertRaises(RuntimeError):
+            Database(TestRustSQLiteWrapper.TEST_DB_NAME, param_style="pyformat")
+        self.assertEqual(Database(TestRustSQLiteWrapper.TEST_DB_NAME, param_style="named").param_style, "named")
+
+    def test_set_trace(self):
+        """Test tracing the executed statements with their parameters."""
+        traced = []
+        self.db.set_trace(traced.append)
+        self.db.execute_raw_query("CREATE TABLE users (name TEXT)", [])
+        self.db.execute_raw_query("INSERT INTO users (name) VALUES (?)", ["Ray"])
+        self.assertEqual(traced, ["CREATE TABLE users (name TEXT)", "INSERT INTO users (name) VALUES ('Ray')"])
+
+        self.db.set_trace(None)
+        self.db.fetch_all("SELECT * FROM users", [])
+        self.assertEqual(len(traced), 2)
+
+    def test_set_authorizer(self):
+        """Test denying and ignoring actions with an authorizer."""
+        self.db.create_table("users", {"name": str, "secret": str})
+        self.db.insert("users", {"name": "Ray", "secret": "xyz"})
+        self.db.fetch_all("SELECT name, secret FROM users", [])
+
+        def authorizer(action, arg1, arg2, db_name, trigger_or_view):
+            if action == SQLITE_READ and arg2 == "secret":
+                return SQLITE_IGNORE
+            if action in (SQLITE_INSERT, SQLITE_ATTACH):
+                return SQLITE_DENY
+            return SQLITE_OK
+
+        self.db.set_authorizer(authorizer)
+        # The statement prepared before set_authorizer() is checked too
+        self.assertEqual(self.db.fetch_all("SELECT name, secret FROM users", []), [["Ray", "NULL"]])
+        with self.assertRaises(DatabaseError):
+            self.db.insert("users", {"name": "Tom", "secret": "abc"})
+        with self.assertRaises(DatabaseError):
+            self.db.execute_raw_query("ATTACH DATABASE ':memory:' AS other", [])
+
+        unraisable = []
+        sys.unraisablehook, hook = unraisable.append, sys.unraisablehook
+        try:
+            self.db.set_authorizer(lambda *args: 1 / 0)
+            with self.assertRaises(DatabaseError):
+                self.db.fetch_all("SELECT name FROM users", [])
+        finally:
+            sys.unraisablehook = hook
+        self.assertIsInstance(unraisable[0].exc_value, ZeroDivisionError)
+
+        self.db.set_authorizer(None)
+        self.db.insert("users", {"name": "Tom", "secret": "abc"})
+        self.assertEqual(self.db.fetch_value("SELECT secret FROM users WHERE name = 'Tom'", []), "abc")
+
+    def test_set_update_hook(self):
+        """Test being notified of the inserted, updated and deleted rows."""
+        self.db.create_table("users", {"name": str})
+        changes = []
+        self.db.set_update_hook(lambda *change: changes.append(change))
+
+        self.db.insert("users", {"name": "Ray"})
+        self.db.insert("users", {"name": "Tom"})
+        self.db.execute_raw_query("UPDATE users SET name = 'Tim' WHERE name = ?", "Tom")
+        self.db.execute_raw_query("DELETE FROM users WHERE rowid = ?", 1)
+        self.assertEqual(changes, [("INSERT", "users", 1), ("INSERT", "users", 2), ("UPDATE", "users", 2), ("DELETE", "users", 1)])
+
+        self.db.set_update_hook(None)
+        self.db.insert("users", {"name": "Ray"})
+        self.assertEqual(len(changes), 4)
+
+    def test_execute_returning(self):
+        """Test getting both the returned rows and the changed rows count."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert_many("users", [{"name": "Ray", "age": 27}, {"name": "Tom", "age": 12}, {"name": "Ann", "age": 15}])
+
+        rows, changes = self.db.execute_returning(
+            "UPDATE users SET age = age + 1 WHERE age < ? RETURNING name, age", [18], typed=True
+        )
+        self.assertEqual(sorted(rows), [["Ann", 16], ["Tom", 13]])
+        self.assertEqual(changes, 2)
+        self.assertEqual(self.db.execute_returning("DELETE FROM users WHERE age > 100 RETURNING name", []), ([], 0))
+        # A SELECT does not report the changes of the previous statement
+        self.assertEqual(self.db.execute_returning("SELECT name FROM users WHERE age = 27", []), ([["Ray"]], 0))
+
+    def test_execute_script(self):
+        """Test running several statements and counting the changed rows."""
+        changed = self.db.execute_script("""
+            CREATE TABLE users (name TEXT, age INTEGER);
+            CREATE TABLE log (name TEXT);
+            CREATE TRIGGER log_deletes AFTER DELETE ON users BEGIN INSERT INTO log VALUES (old.name); END;
+            INSERT INTO users VALUES ('Ray', 27), ('Tom', 12), ('Ann', 40);
+            UPDATE users SET age = age + 1 WHERE age > 20;
+            DELETE FROM users WHERE name = 'Tom';
+        """)
+        # 3 inserted, 2 updated, 1 deleted and 1 inserted by the trigger
+        self.assertEqual(changed, 7)
+        self.assertEqual(self.db.execute_script("SELECT 1; CREATE INDEX idx_age ON users (age)"), 0)
+
+        with self.assertRaises(OperationalError):
+            self.db.execute_script("DELETE FROM users; INSERT INTO missing VALUES (1);")
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM users", []), 0)
+
+    def test_total_changes(self):
+        """Test counting the rows changed since the database was opened."""
+        self.assertEqual(self.db.total_changes(), 0)
+        self.db.create_table("users", {"name": str})
+        self.db.insert_many("users", [{"name": "Ray"}, {"name": "Tom"}])
+        self.db.execute_raw_query("UPDATE users SET name = upper(name)", [])
+        self.db.fetch_all("SELECT * FROM users", [])
+        self.assertEqual(self.db.total_changes(), 4)
+
+    def test_fetch_all_batched(self):
+        """Test running the same query for several parameter sets."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert_many("users", [{"name": "Ray", "age": 27}, {"name": "Tom", "age": 12}])
+
+        query = "SELECT name FROM users WHERE age >= ? ORDER BY age"
+        self.assertEqual(self.db.fetch_all_batched(query, [[20], (10,), 30]), [[["Ray"]], [["Tom"], ["Ray"]], []])
+        self.assertEqual(
+            self.db.fetch_all_batched("SELECT age FROM users WHERE name IN (?)", [[["Ray", "Tom"]], [["Tom"]]], typed=True),
+            [[[27], [12]], [[12]]],
+        )
+        self.assertEqual(self.db.fetch_all_batched(query, []), [])
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all_batched(query, [[20], [20, 30]])
+
+    def test_fetch_value(self):
+        """Test fetching a single cell."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert_many("users", [{"name": "Ray", "age": 27}, {"name": "Tom", "age": 12}])
+
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM users", []), 2)
+        self.assertEqual(self.db.fetch_value("SELECT name, age FROM users ORDER BY age", [], row=1, column=1), 27)
+        self.assertEqual(self.db.fetch_value("SELECT name FROM users WHERE age < ?", 18), "Tom")
+        self.assertIsNone(self.db.fetch_value("SELECT name FROM users", [], row=5))
+        with self.assertRaises(IndexError):
+            self.db.fetch_value("SELECT name FROM users", [], column=1)
+
+    def test_fetch_columns(self):
+        """Test fetching the results by column."""
+        self.db.create_table("users", {"name": str, "age": int, "active": bool})
+        self.db.insert("users", {"name": "Ray", "age": 27, "active": True})
+        self.db.insert("users", {"name": "Tom", "age": 12, "active": False})
+
+        self.assertEqual(
+            self.db.fetch_columns("SELECT name, age, active FROM users ORDER BY age", []),
+            {"name": ["Tom", "Ray"], "age": ["12", "27"], "active": [False, True]},
+        )
+        self.assertEqual(self.db.fetch_columns("SELECT name FROM users WHERE age > ?", [99]), {"name": []})
+
+    def test_fetch_typed(self):
+        """Test converting the values from the declared types of the columns."""
+        self.db.create_table("users", {"name": str, "age": int, "height": float, "is_underage": bool, "data": "BLOB"})
+        self.db.execute_raw_query("INSERT INTO users VALUES (?, ?, ?, ?, ?)", ["Ray", 27, 2, False, b"x"])
+        self.db.execute_raw_query("INSERT INTO users (name) VALUES (?)", [123])
+
+        self.assertEqual(
+            self.db.fetch_all("SELECT name, age, height, is_underage, data, age * 2 FROM users", [], typed=True),
+            [["Ray", 27, 2.0, False, b"x", 54], ["123", None, None, None, None, None]],
+        )
+        self.assertEqual(
+            self.db.fetch_columns("SELECT age, height FROM users WHERE age IS NOT NULL", [], typed=True),
+            {"age": [27], "height": [2.0]},
+        )
+
+    def test_fetch_converters(self):
+        """Test converting the values of some columns with Python callables."""
+        from datetime import date
+        from decimal import Decimal
+
+        self.db.create_table("orders", {"day": str, "price": str, "quantity": int})
+        self.db.insert_many("orders", [{"day": "2025-01-31", "price": "9.99", "quantity": 3}, {"day": None, "price": "1", "quantity": 1}])
+
+        converters = {"day": date.fromisoformat, "price": Decimal, "quantity": lambda value: value * 10}
+        self.assertEqual(
+            self.db.fetch_all("SELECT day, price, quantity FROM orders", [], converters=converters),
+            [[date(2025, 1, 31), Decimal("9.99"), 30], [None, Decimal("1"), 10]],
+        )
+        self.assertEqual(
+            self.db.fetch_all_batched("SELECT price FROM orders WHERE quantity = ?", [[3], [1]], converters={"price": Decimal}),
+            [[[Decimal("9.99")]], [[Decimal("1")]]],
+        )
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT day FROM orders", [], converters={"missing": str})
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT day FROM orders", [], converters={"day": "not callable"})
+        with self.assertRaises(ValueError):
+            self.db.fetch_all("SELECT price FROM orders", [], converters={"price": int})
+
+    def test_fetch_max_rows(self):
+        """Test capping the number of fetched rows."""
+        self.db.create_table("users", {"name": str})
+        self.db.insert_many("users", [{"name": "Ray"}, {"name": "Tom"}])
+
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users", [], max_rows=2), [["Ray"], ["Tom"]])
+        self.assertFalse(self.db.last_fetch_truncated)
+        with self.assertRaises(RowLimitExceeded):
+            self.db.fetch_all("SELECT name FROM users", [], max_rows=1)
+
+        # The iteration stops early, so even an endless query returns
+        endless = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n) SELECT x FROM n"
+        self.assertEqual(self.db.fetch_all(endless, [], typed=True, max_rows=3, truncate=True), [[1], [2], [3]])
+        self.assertTrue(self.db.last_fetch_truncated)
+        self.db.fetch_all("SELECT name FROM users", [], max_rows=5, truncate=True)
+        self.assertFalse(self.db.last_fetch_truncated)
+
+    def test_cursor_fetchmany(self):
+        """Test fetching the rows of a query in batches with a cursor."""
+        self.db.create_table("users", {"name": str})
+        self.db.insert_many("users", [{"name": name} for name in ["Ray", "Tom", "Ana", "Bob", "Eve"]])
+        query = "SELECT name FROM users ORDER BY rowid"
+
+        cursor = self.db.cursor(query, [])
+        self.assertEqual(cursor.fetchmany(2), [["Ray"], ["Tom"]])
+        self.assertEqual(cursor.fetchmany(2), [["Ana"], ["Bob"]])
+        self.assertEqual(cursor.fetchmany(2), [["Eve"]])
+        self.assertEqual(cursor.fetchmany(2), [])
+        self.assertEqual(cursor.fetchmany(2), [])
+
+        # Each cursor has its own position, so they can be nested or abandoned
+        self.assertEqual(self.db.cursor(query, []).fetchmany(1), [["Ray"]])
+        pairs = []
+        outer = self.db.cursor(query, [])
+        while rows := outer.fetchmany(2):
+            inner = self.db.cursor("SELECT name FROM users WHERE name != ? ORDER BY rowid", [rows[0][0]])
+            while inner_rows := inner.fetchmany(3):
+                pairs.append((rows[0][0], len(inner_rows)))
+        self.assertEqual(pairs, [("Ray", 3), ("Ray", 1), ("Ana", 3), ("Ana", 1), ("Eve", 3), ("Eve", 1)])
+
+        with self.assertRaises(RuntimeError):
+            self.db.cursor(query, []).fetchmany(0)
+
+    def test_cursor_reads_each_batch_when_asked(self):
+        """Test that a cursor runs its query per batch instead of reading all the rows at once."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert_many("users", [{"name": name, "age": 20} for name in ["Ray", "Tom", "Ana"]])
+
+        cursor = self.db.cursor("SELECT name FROM users WHERE age >= :age ORDER BY rowid;", {"age": 18})
+        self.assertEqual(cursor.fetchmany(2), [["Ray"], ["Tom"]])
+        # A row added after the first batch is read by the next one
+        self.db.insert("users", {"name": "Bob", "age": 30})
+        self.assertEqual(cursor.fetchmany(2), [["Ana"], ["Bob"]])
+        self.db.insert("users", {"name": "Eve", "age": 40})
+        self.assertEqual(cursor.fetchmany(2), [["Eve"]])
+        self.assertEqual(cursor.fetchmany(2), [])
+
+        # The query can end with a comment
+        cursor = self.db.cursor("SELECT name FROM users ORDER BY rowid -- all of them", [])
+        self.assertEqual(cursor.fetchmany(10), [["Ray"], ["Tom"], ["Ana"], ["Bob"], ["Eve"]])
+
+    def test_row_factory(self):
+        """Test shaping the fetched rows with a row factory."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert("users", {"name": "Ray", "age": 27})
+        self.assertIsNone(self.db.row_factory)
+
+        self.db.row_factory = lambda columns, values: dict(zip(columns, values))
+        self.assertEqual(self.db.fetch_all("SELECT name, age FROM users", []), [{"name": "Ray", "age": "27"}])
+        self.assertEqual(
+            self.db.fetch_all_with_columns("SELECT name FROM users", []),
+            (["name"], [{"name": "Ray"}]),
+        )
+
+        # The factory can use the database itself
+        self.db.row_factory = lambda columns, values: (self.db.ping(), values[0])
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users", []), [(True, "Ray")])
+
+        self.db.row_factory = None
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users", []), [["Ray"]])
+
     ##### END EXECUTE_RAW_QUERY #####
 
     ##### INSERT #####
@@ -54,8 +537,1357 @@ class TestRustSQLiteWrapper(unittest.TestCase):
             "new": bool
         })
 
+    def test_bool_round_trip(self):
+        """Test that BOOLEAN columns are fetched back as Python booleans."""
+        self.db.create_table("flags", {"name": str, "active": bool})
+        self.db.insert("flags", {"name": "on", "active": True})
+        self.db.insert("flags", {"name": "off", "active": False})
+
+        self.assertEqual(
+            self.db.fetch_all("SELECT name, active FROM flags", []),
+            [["on", True], ["off", False]],
+        )
+
+    def test_insert_column_names_must_be_str(self):
+        """Test that a dict key which is not a str raises a TypeError."""
+        self.db.create_table("users", {"name": str})
+        with self.assertRaises(TypeError):
+            self.db.insert("users", {1: "Ray"})
+
+    def test_insert_returning(self):
+        """Test getting generated and default values back from an insertion."""
+        self.db.create_table("users", {"id": "INTEGER PRIMARY KEY", "name": str, "role": "TEXT DEFAULT 'member'"})
+
+        self.assertEqual(self.db.insert_returning("users", {"name": "Ray"}, ["id", "role"]), [["1", "member"]])
+        self.assertEqual(self.db.insert_returning("users", {"name": "Tom"}, ["*"]), [["2", "Tom", "member"]])
+
+        with self.assertRaises(RuntimeError):
+            self.db.insert_returning("users", {"name": "Ann"}, [])
+        with self.assertRaises(RuntimeError):
+            self.db.insert_returning("users", {"name": "Ann"}, ["id; DROP TABLE users"])
+
+        # The values are bound like in insert(), so a list is refused
+        with self.assertRaises(RuntimeError):
+            self.db.insert("users", {"name": [7]})
+        with self.assertRaises(RuntimeError):
+            self.db.insert_returning("users", {"id": 5, "name": [7]}, ["id"])
+        self.assertEqual(self.db.fetch_all("SELECT COUNT(*) FROM users", []), [["2"]])
+
+    def test_insert_many(self):
+        """Test inserting a list of rows at once."""
+        self.db.create_table("users", {"name": str, "age": int})
+
+        inserted = self.db.insert_many("users", [{"name": "Ray", "age": 27}, {"age": 12, "name": "Tom"}])
+        self.assertEqual(inserted, 2)
+        self.assertEqual(self.db.insert_many("users", []), 0)
+        self.assertEqual(
+            self.db.fetch_all("SELECT name, age FROM users ORDER BY age", []),
+            [["Tom", "12"], ["Ray", "27"]],
+        )
+
+    def test_insert_many_is_atomic(self):
+        """Test that no row is inserted when the rows are inconsistent or one fails."""
+        self.db.execute_raw_query("CREATE TABLE uniques (name TEXT UNIQUE)", [])
+
+        with self.assertRaises(RuntimeError):
+            self.db.insert_many("uniques", [{"name": "a"}, {"other": "b"}])
+        with self.assertRaises(IntegrityError):
+            self.db.insert_many("uniques", [{"name": "a"}, {"name": "a"}])
+        self.assertEqual(self.db.fetch_all("SELECT COUNT(*) FROM uniques", []), [["0"]])
+
+    def test_insert_many_in_transaction(self):
+        """Test that a failing insert_many() only undoes its own rows inside a transaction."""
+        self.db.execute_raw_query("CREATE TABLE uniques (name TEXT UNIQUE)", [])
+
+        self.db.begin()
+        self.db.insert("uniques", {"name": "a"})
+        with self.assertRaises(IntegrityError):
+            self.db.insert_many("uniques", [{"name": "b"}, {"name": "a"}])
+        self.assertEqual(self.db.insert_many("uniques", [{"name": "c"}, {"name": "d"}]), 2)
+        self.db.commit()
+
+        self.assertEqual(self.db.fetch_all("SELECT name FROM uniques ORDER BY name", []), [["a"], ["c"], ["d"]])
+
+    def test_upsert_many(self):
+        """Test inserting new rows and updating the existing ones in one call."""
+        self.db.execute_raw_query("CREATE TABLE users (email TEXT UNIQUE, name TEXT, age INTEGER)", [])
+        self.db.insert("users", {"email": "ray@example.com", "name": "Ray", "age": 27})
+
+        counts = self.db.upsert_many(
+            "users",
+            [
+                {"email": "ray@example.com", "name": "Rayan", "age": 28},
+                {"age": 12, "name": "Tom", "email": "tom@example.com"},
+            ],
+            ["email"],
+        )
+        self.assertEqual(counts, (1, 1))
+        self.assertEqual(
+            self.db.fetch_all("SELECT email, name, age FROM users ORDER BY email", []),
+            [["ray@example.com", "Rayan", "28"], ["tom@example.com", "Tom", "12"]],
+        )
+        # Only the conflict columns: the existing rows are left as they are
+        self.assertEqual(self.db.upsert_many("users", [{"email": "ray@example.com"}], ["email"]), (0, 0))
+        self.assertEqual(self.db.upsert_many("users", [], ["email"]), (0, 0))
+
+        # A failing row undoes the whole call
+        self.db.execute_raw_query("CREATE TABLE checked (code TEXT UNIQUE, qty INTEGER CHECK (qty >= 0))", [])
+        with self.assertRaises(IntegrityError):
+            self.db.upsert_many("checked", [{"code": "a", "qty": 1}, {"code": "b", "qty": -1}], ["code"])
+        self.assertEqual(self.db.fetch_all("SELECT COUNT(*) FROM checked", []), [["0"]])
+
+        with self.assertRaises(RuntimeError):
+            self.db.upsert_many("users", [{"email": "x", "name": "X"}], ["age"])
+        with self.assertRaises(RuntimeError):
+            self.db.upsert_many("users", [{"email": "x"}], [])
+        with self.assertRaises(OperationalError):
+            self.db.upsert_many("users", [{"email": "x", "name": "X"}], ["name"])
+
+    def test_select(self):
+        """Test the select() builder, with its sorting and pagination validated or bound."""
+        self.db.create_table("users", {"name": str, "age": int, "city": str})
+        self.db.insert_many("users", [
+            {"name": "Ray", "age": 27, "city": "Paris"},
+            {"name": "Tom", "age": 12, "city": "Paris"},
+            {"name": "Ann", "age": 35, "city": "Lyon"},
+        ])
+
+        self.assertEqual(len(self.db.select("users")), 3)
+        self.assertEqual(
+            self.db.select("users", ["name"], {"city": "Paris"}, order_by=[("age", "asc")]),
+            [["Tom"], ["Ray"]],
+        )
+        self.assertEqual(
+            self.db.select("users", ["name", "age"], order_by=[("age", "DESC")], limit=2),
+            [["Ann", "35"], ["Ray", "27"]],
+        )
+        self.assertEqual(self.db.select("users", ["name"], order_by=[("age", "DESC")], limit=1, offset=2), [["Tom"]])
+        self.assertEqual(self.db.select("users", ["name"], order_by=[("age", "DESC")], offset=1), [["Ray"], ["Tom"]])
+        self.assertEqual(self.db.select("users", ["name"], {"city": None}), [])
+
+        with self.assertRaises(RuntimeError):
+            self.db.select("users", order_by=[("age", "DESC; DROP TABLE users")])
+        with self.assertRaises(RuntimeError):
+            self.db.select("users", order_by=[("age DESC, name", "ASC")])
+        with self.assertRaises(RuntimeError):
+            self.db.select("users", [])
+        with self.assertRaises(OperationalError):
+            self.db.select("users", order_by=[("missing", "ASC")])
+        with self.assertRaises(OverflowError):
+            self.db.select("users", limit=-1)
+
+    def test_update_and_delete(self):
+        """Test updating and deleting the rows matching a dict, with and without RETURNING."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert_many("users", [
+            {"name": "Ray", "age": 27}, {"name": "Tom", "age": 12}, {"name": "Ann", "age": None},
+        ])
+
+        self.assertEqual(self.db.update("users", {"age": 28}, {"name": "Ray"}), 1)
+        self.assertEqual(self.db.update("users", {"age": 13}, {"name": "Nobody"}), 0)
+        self.assertEqual(
+            self.db.update("users", {"age": 40}, {"age": None}, returning=["name", "age"]),
+            [["Ann", "40"]],
+        )
+        # An empty dict matches every row
+        self.assertEqual(self.db.update("users", {"name": "X"}, {}, returning=["age"]), [["28"], ["12"], ["40"]])
+
+        self.assertEqual(self.db.delete("users", {"age": 12}, returning=["*"]), [["X", "12"]])
+        self.assertEqual(self.db.delete("users", {"age": 12}, returning=["*"]), [])
+        self.assertEqual(self.db.delete("users", {"age": 28}), 1)
+        self.assertEqual(self.db.fetch_all("SELECT name, age FROM users", []), [["X", "40"]])
+
+        with self.assertRaises(RuntimeError):
+            self.db.update("users", {}, {"age": 40})
+        with self.assertRaises(RuntimeError):
+            self.db.delete("users", {"age": 40}, returning=[])
+        with self.assertRaises(RuntimeError):
+            self.db.update("users", {"age; DROP TABLE users": 1}, {})
+        with self.assertRaises(OperationalError):
+            self.db.delete("users", {"missing": 1})
+
+    def test_update_and_delete_refuse_lists(self):
+        """Test that a list value is not expanded like an IN clause parameter but refused."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert("users", {"name": "Ray", "age": 27})
+
+        for values in [{"age": []}, {"age": [1]}]:
+            with self.assertRaises(RuntimeError):
+                self.db.update("users", values, {"name": "Ray"})
+        with self.assertRaises(RuntimeError):
+            self.db.update("users", {"age": 1}, {"name": ["Ray"]})
+        with self.assertRaises(RuntimeError):
+            self.db.delete("users", {"age": (27, 28)})
+        self.assertEqual(self.db.fetch_all("SELECT name, age FROM users", []), [["Ray", "27"]])
+
+    def test_dry_run(self):
+        """Test that the CRUD helpers return their SQL and parameters with dry_run, without running it."""
+        self.db.create_table("users", {"name": str, "age": int})
+
+        self.assertEqual(
+            self.db.insert("users", {"name": "Ray", "age": 27}, dry_run=True),
+            ('INSERT INTO "users" ("name", "age") VALUES (?, ?)', ["Ray", "27"]),
+        )
+        self.assertEqual(
+            self.db.update("users", {"age": 28}, {"name": "Ray", "age": None}, returning=["age"], dry_run=True),
+            ('UPDATE "users" SET "age" = ? WHERE "users"."name" = ? AND "users"."age" IS NULL RETURNING age', [28, "Ray"]),
+        )
+        self.assertEqual(
+            self.db.delete("users", {"name": "Ray"}, dry_run=True),
+            ('DELETE FROM "users" WHERE "users"."name" = ?', ["Ray"]),
+        )
+        self.assertEqual(
+            self.db.select("users", ["name"], order_by=[("age", "desc")], offset=5, dry_run=True),
+            ('SELECT "users"."name" FROM "users" ORDER BY "users"."age" DESC LIMIT ? OFFSET ?', [-1, 5]),
+        )
+        self.assertEqual(self.db.fetch_all("SELECT * FROM users", []), [])
+
+        # The values are validated like for a real run
+        with self.assertRaises(RuntimeError):
+            self.db.insert("users", {"name": object()}, dry_run=True)
+
+    def test_dry_run_accepts_what_a_real_run_accepts(self):
+        """Test that dry_run=True accepts and refuses the same values as a real run."""
+        self.db.create_table("users", {"name": str, "age": int})
+        calls = {
+            "insert": lambda value, dry_run: self.db.insert("users", {"name": "Ray", "age": value}, dry_run=dry_run),
+            "update": lambda value, dry_run: self.db.update("users", {"age": value}, {"name": "Ray"}, dry_run=dry_run),
+            "delete": lambda value, dry_run: self.db.delete("users", {"age": value}, dry_run=dry_run),
+            "select": lambda value, dry_run: self.db.select("users", where={"age": value}, dry_run=dry_run),
+        }
+        for name, call in calls.items():
+            for value in [28, "28", 2.5, [], [1], (1, 2), {1}, object()]:
+                outcomes = []
+                for dry_run in (True, False):
+                    try:
+                        call(value, dry_run)
+                        outcomes.append(None)
+                    except Exception as error:
+                        outcomes.append(type(error))
+                self.assertEqual(outcomes[0], outcomes[1], f"{name}() with {value!r}")
+
+    def test_copy_rows(self):
+        """Test copying the rows matching a dict from a table into another one."""
+        self.db.create_table("orders", {"item": str, "status": str})
+        self.db.create_table("archive", {"item": str, "status": str})
+        self.db.insert_many("orders", [
+            {"item": "a", "status": "done"}, {"item": "b", "status": "open"}, {"item": "c", "status": "done"},
+        ])
+
+        self.assertEqual(self.db.copy_rows("orders", "archive", {"status": "done"}), 2)
+        self.assertEqual(self.db.fetch_all("SELECT item FROM archive", []), [["a"], ["c"]])
+        self.assertEqual(self.db.copy_rows("orders", "archive"), 3)
+        self.assertEqual(self.db.copy_rows("orders", "archive", {"status": "lost"}), 0)
+
+        # Tables with a different number of columns are reported by SQLite
+        self.db.create_table("names", {"item": str})
+        with self.assertRaises(OperationalError):
+            self.db.copy_rows("orders", "names")
+        with self.assertRaises(OperationalError):
+            self.db.copy_rows("orders", "missing")
+        with self.assertRaises(RuntimeError):
+            self.db.copy_rows("orders", "archive; DROP TABLE orders")
+
+    def test_truncate(self):
+        """Test emptying a table, with and without resetting its AUTOINCREMENT counter."""
+        # Without any AUTOINCREMENT table there is no sqlite_sequence to reset
+        self.db.create_table("users", {"name": str})
+        self.assertEqual(self.db.truncate("users", reset_sequence=True), 0)
+
+        self.db.execute_raw_query("CREATE TABLE logs (id INTEGER PRIMARY KEY AUTOINCREMENT, message TEXT)", [])
+        self.db.insert_many("logs", [{"message": "a"}, {"message": "b"}])
+
+        self.assertEqual(self.db.truncate("logs"), 2)
+        self.db.insert("logs", {"message": "c"})
+        self.assertEqual(self.db.fetch_all("SELECT id FROM logs", []), [["3"]])
+
+        self.assertEqual(self.db.truncate("logs", reset_sequence=True), 1)
+        self.db.insert("logs", {"message": "d"})
+        self.assertEqual(self.db.fetch_all("SELECT id FROM logs", []), [["1"]])
+
+        with self.assertRaises(OperationalError):
+            self.db.truncate("missing")
+        with self.assertRaises(RuntimeError):
+            self.db.truncate("logs; DROP TABLE logs")
+
     ##### END INSERT #####
 
+    ##### BACKUP #####
+
+    def test_backup(self):
+        """Test copying the live database into another file."""
+        backup_path = "test_backup.sqlite"
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS backed_up (name TEXT)", [])
+        self.db.execute_raw_query("INSERT INTO backed_up (name) VALUES (?)", ["rayan"])
+
+        try:
+            self.db.backup(backup_path)
+            backup = Database(backup_path)
+            self.assertEqual(backup.fetch_all("SELECT name FROM backed_up", []), [["rayan"]])
+        finally:
+            if os.path.exists(backup_path):
+                os.remove(backup_path)
+
+    def test_backup_progress_cannot_use_the_database(self):
+        """Test that a progress callback using the database raises instead of waiting forever."""
+        backup_path = "test_backup_progress.sqlite"
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS backed_up (name TEXT)", [])
+        try:
+            with self.assertRaises(DatabaseError):
+                self.db.backup(backup_path, progress=lambda remaining, total: self.db.fetch_all("SELECT 1", []))
+            # The lock is released after the failure
+            self.assertEqual(self.db.fetch_all("SELECT 1", []), [["1"]])
+        finally:
+            if os.path.exists(backup_path):
+                os.remove(backup_path)
+
+    def test_backup_timeout(self):
+        """Test that a backup into a locked destination gives up after its timeout."""
+        backup_path = "test_backup_locked.sqlite"
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS backed_up (name TEXT)", [])
+        locker = Database(backup_path)
+        try:
+            locker.execute_raw_query("BEGIN EXCLUSIVE", [])
+            start = time.monotonic()
+            with self.assertRaises(OperationalError):
+                self.db.backup(backup_path, timeout_ms=100)
+            self.assertLess(time.monotonic() - start, 2)
+        finally:
+            locker.close()
+            if os.path.exists(backup_path):
+                os.remove(backup_path)
+
+    def test_serialize_and_from_bytes(self):
+        """Test copying a database through bytes, without touching the filesystem."""
+        self.db.create_table("users", {"name": str})
+        self.db.insert("users", {"name": "Ray"})
+
+        data = self.db.serialize()
+        self.assertTrue(data.startswith(b"SQLite format 3\x00"))
+
+        copy = Database.from_bytes(data)
+        self.assertEqual(copy.path, ":memory:")
+        copy.insert("users", {"name": "Tom"})
+        self.assertEqual(copy.fetch_all("SELECT name FROM users", []), [["Ray"], ["Tom"]])
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users", []), [["Ray"]])
+        self.assertEqual(Database.from_bytes(copy.serialize()).fetch_value("SELECT COUNT(*) FROM users", []), 2)
+        copy.close()
+
+        read_only = Database.from_bytes(bytearray(data), read_only=True)
+        with self.assertRaises(OperationalError):
+            read_only.insert("users", {"name": "Tom"})
+        read_only.close()
+
+        self.assertEqual(Database.from_bytes(b"").fetch_all("SELECT name FROM sqlite_schema", []), [])
+        with self.assertRaises(DatabaseError):
+            Database.from_bytes(b"not a database" * 100)
+        with self.assertRaises(RuntimeError):
+            Database.from_bytes("not bytes")
+
+    ##### END BACKUP #####
+
+    ##### MAINTENANCE #####
+
+    def test_vacuum_and_analyze(self):
+        """Test running the maintenance statements."""
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS maintained (name TEXT)", [])
+        self.db.execute_raw_query("DELETE FROM maintained", [])
+        try:
+            self.db.vacuum()
+            self.db.analyze()
+        except Exception as e:
+            self.fail(f"An error occurred during maintenance: {e}")
+
+    def test_vacuum_inside_transaction(self):
+        """Test that VACUUM is refused while a transaction is open."""
+        self.db.execute_raw_query("BEGIN", [])
+        try:
+            with self.assertRaises(RuntimeError):
+                self.db.vacuum()
+        finally:
+            self.db.execute_raw_query("ROLLBACK", [])
+
+    def test_vacuum_into(self):
+        """Test writing a compacted copy of the database to a new file."""
+        snapshot_path = "test_snapshot.sqlite"
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS snapshotted (name TEXT)", [])
+        self.db.execute_raw_query("INSERT INTO snapshotted (name) VALUES (?)", ["rayan"])
+
+        try:
+            self.db.vacuum_into(snapshot_path)
+            snapshot = Database(snapshot_path)
+            self.assertEqual(snapshot.fetch_all("SELECT name FROM snapshotted", []), [["rayan"]])
+            snapshot.close()
+
+            with self.assertRaises(RuntimeError) as ctx:
+                self.db.vacuum_into(snapshot_path)
+            self.assertIn("already exists", str(ctx.exception))
+        finally:
+            if os.path.exists(snapshot_path):
+                os.remove(snapshot_path)
+
+    def test_integrity_check(self):
+        """Test that a healthy database passes the integrity check."""
+        self.assertIs(self.db.integrity_check(), True)
+
+    def test_wal_checkpoint(self):
+        """Test checkpointing the WAL file."""
+        self.db.fetch_all("PRAGMA journal_mode=WAL", [])
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS logged (name TEXT)", [])
+        busy, log, checkpointed = self.db.wal_checkpoint("truncate")
+        self.assertEqual(busy, 0)
+        self.assertEqual(log, checkpointed)
+
+        with self.assertRaises(RuntimeError):
+            self.db.wal_checkpoint("SOMETIMES")
+
+    def test_wal_autocheckpoint(self):
+        """Test tuning or disabling the automatic WAL checkpoints."""
+        self.db.fetch_all("PRAGMA journal_mode=WAL", [])
+        self.assertEqual(self.db.wal_autocheckpoint(0), 0)
+        self.assertEqual(self.db.fetch_all("PRAGMA wal_autocheckpoint", [], typed=True), [[0]])
+
+        # Without automatic checkpoints, the WAL keeps all the pages until a manual one
+        self.db.create_table("logs", {"message": str})
+        self.db.insert_many("logs", [{"message": "x" * 1000} for _ in range(100)])
+        _, log, _ = self.db.wal_checkpoint("PASSIVE")
+        self.assertGreater(log, 25)
+
+        self.assertEqual(self.db.wal_autocheckpoint(500), 500)
+        with self.assertRaises(RuntimeError):
+            self.db.wal_autocheckpoint(-1)
+
+    def test_database_size(self):
+        """Test reading the page count, the page size and the database size, WAL included."""
+        self.db.fetch_all("PRAGMA journal_mode=WAL", [])
+        self.db.create_table("logs", {"message": str})
+        pages = self.db.page_count()
+        self.assertEqual(self.db.database_size(), pages * self.db.page_size())
+
+        self.db.insert_many("logs", [{"message": "x" * 1000} for _ in range(100)])
+        self.assertGreater(self.db.page_count(), pages)
+        # The new pages are still in the WAL, not in the database file
+        self.assertGreater(self.db.database_size(), os.path.getsize(TestRustSQLiteWrapper.TEST_DB_NAME))
+        self.assertEqual(self.db.database_size(), self.db.page_count() * self.db.page_size())
+
+    def test_explain(self):
+        """Test reading the query plan of a query."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.execute_raw_query("CREATE INDEX idx_users_name ON users (name)", [])
+
+        plan = self.db.explain("SELECT age FROM users WHERE name = ?", ["Ray"])
+        self.assertEqual(set(plan[0].keys()), {"id", "parent", "notused", "detail"})
+        self.assertTrue(any("idx_users_name" in step["detail"] for step in plan))
+        self.assertEqual(self.db.fetch_all("SELECT COUNT(*) FROM users", []), [["0"]])
+
+    def test_list_foreign_keys(self):
+        """Test listing the foreign keys of a table."""
+        self.db.execute_script(
+            """
+            CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE products (code TEXT, version INTEGER, PRIMARY KEY (code, version));
+            CREATE TABLE orders (
+                user_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+                buyer_id INTEGER REFERENCES users,
+                product_code TEXT,
+                product_version INTEGER,
+                FOREIGN KEY (product_code, product_version) REFERENCES products (code, version) ON UPDATE SET NULL
+            );
+            """
+        )
+
+        keys = sorted(self.db.list_foreign_keys("orders"), key=lambda key: key["columns"])
+        self.assertEqual(keys, [
+            {"referenced_table": "users", "columns": ["buyer_id"], "referenced_columns": [None],
+             "on_update": "NO ACTION", "on_delete": "NO ACTION", "match": "NONE"},
+            {"referenced_table": "products", "columns": ["product_code", "product_version"],
+             "referenced_columns": ["code", "version"], "on_update": "SET NULL", "on_delete": "NO ACTION", "match": "NONE"},
+            {"referenced_table": "users", "columns": ["user_id"], "referenced_columns": ["id"],
+             "on_update": "NO ACTION", "on_delete": "CASCADE", "match": "NONE"},
+        ])
+        self.assertEqual(self.db.list_foreign_keys("users"), [])
+        with self.assertRaises(RuntimeError):
+            self.db.list_foreign_keys("missing")
+
+    def test_foreign_key_check(self):
+        """Test finding the rows violating a foreign key."""
+        self.db.execute_script(
+            """
+            PRAGMA foreign_keys = OFF;
+            CREATE TABLE users (id INTEGER PRIMARY KEY);
+            CREATE TABLE teams (id INTEGER PRIMARY KEY);
+            CREATE TABLE members (user_id INTEGER REFERENCES users (id), team_id INTEGER REFERENCES teams (id));
+            INSERT INTO users (id) VALUES (1);
+            INSERT INTO teams (id) VALUES (1);
+            INSERT INTO members (rowid, user_id, team_id) VALUES (1, 1, 1), (2, 2, 1), (3, 1, 9);
+            """
+        )
+
+        # fk_index is the position of the key in list_foreign_keys()
+        referenced = [key["referenced_table"] for key in self.db.list_foreign_keys("members")]
+        violations = sorted(self.db.foreign_key_check("members"), key=lambda v: v["rowid"])
+        self.assertEqual(violations, [
+            {"table": "members", "rowid": 2, "referenced_table": "users", "fk_index": referenced.index("users")},
+            {"table": "members", "rowid": 3, "referenced_table": "teams", "fk_index": referenced.index("teams")},
+        ])
+        self.assertEqual(len(self.db.foreign_key_check()), 2)
+        self.assertEqual(self.db.foreign_key_check("users"), [])
+
+        self.db.execute_raw_query("DELETE FROM members WHERE rowid > 1", [])
+        self.assertEqual(self.db.foreign_key_check(), [])
+        with self.assertRaises(DatabaseError):
+            self.db.foreign_key_check("missing")
+
+    def test_interrupt(self):
+        """Test cancelling a running query from another thread."""
+        slow_query = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT COUNT(*) FROM n"
+        timer = threading.Timer(0.1, self.db.interrupt)
+        timer.start()
+        try:
+            with self.assertRaises(OperationalError):
+                self.db.fetch_all(slow_query, [])
+        finally:
+            timer.cancel()
+
+        # The database can be used again after the interruption
+        self.db.interrupt()
+        self.assertEqual(self.db.fetch_all("SELECT 1", []), [["1"]])
+
+    def test_query_timeout(self):
+        """Test that a query running longer than its timeout is cancelled."""
+        slow_query = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT COUNT(*) FROM n"
+        with self.assertRaises(QueryTimeout):
+            self.db.fetch_all(slow_query, [], timeout_ms=50)
+        with self.assertRaises(QueryTimeout):
+            self.db.execute_raw_query("CREATE TABLE slow AS " + slow_query, [], timeout_ms=50)
+        self.assertTrue(issubclass(QueryTimeout, OperationalError))
+
+        # A fast query is not affected, and the timer does not outlive it
+        start = time.time()
+        self.assertEqual(self.db.fetch_all("SELECT 1", [], timeout_ms=10000), [["1"]])
+        self.assertLess(time.time() - start, 1)
+
+    def test_query_timeout_does_not_interrupt_other_threads(self):
+        """Test that a timeout only cancels the query of its own call, not one of another thread."""
+        slow_query = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 3000000) SELECT COUNT(*) FROM n"
+        results = []
+        worker = threading.Thread(target=lambda: results.append(self.db.fetch_all(slow_query, [])))
+        worker.start()
+        time.sleep(0.2)
+
+        # This call waits for the slow query, the wait is not counted in its timeout
+        self.assertEqual(self.db.fetch_all("SELECT 1", [], timeout_ms=100), [["1"]])
+        worker.join()
+        self.assertEqual(results, [[["3000000"]]])
+
+    def test_set_performance_profile(self):
+        """Test applying the performance profiles."""
+        self.assertEqual(self.db.set_performance_profile("fast"), "wal")
+        self.assertEqual(self.db.fetch_all("PRAGMA synchronous", []), [["1"]])  # NORMAL
+        self.assertEqual(self.db.fetch_all("PRAGMA cache_size", []), [["-65536"]])
+
+        self.assertEqual(self.db.set_performance_profile("bulk"), "wal")
+        self.assertEqual(self.db.fetch_all("PRAGMA synchronous", []), [["0"]])  # OFF
+
+        self.assertEqual(self.db.set_performance_profile("safe"), "delete")
+        self.assertEqual(self.db.fetch_all("PRAGMA synchronous", []), [["2"]])  # FULL
+
+        with self.assertRaises(RuntimeError):
+            self.db.set_performance_profile("turbo")
+
+    ##### END MAINTENANCE #####
+
+    ##### CUSTOM FUNCTIONS #####
+
+    def test_create_function(self):
+        """Test calling a Python function from SQL."""
+        self.db.create_function("reverse", 1, lambda s: s[::-1])
+        self.assertEqual(self.db.fetch_all("SELECT reverse(?)", ["rayan"]), [["nayar"]])
+
+    def test_create_function_raising(self):
+        """Test that an exception raised by the Python function fails the query."""
+        def fail(_):
+            raise ValueError("boom")
+
+        self.db.create_function("fail", 1, fail)
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT fail(1)", [])
+
+    def test_create_function_cannot_use_the_database(self):
+        """Test that a function using the database of its query raises instead of waiting forever."""
+        self.db.create_function("count_users", 1, lambda _: len(self.db.fetch_all("SELECT 1", [])))
+        with self.assertRaisesRegex(RuntimeError, "a callback cannot use the database"):
+            self.db.fetch_all("SELECT count_users(1)", [])
+        self.assertEqual(self.db.fetch_all("SELECT 1", []), [["1"]])
+
+    def test_create_function_with_other_threads(self):
+        """Test that a method called while another thread runs a Python function in SQL does not deadlock."""
+        self.db.create_table("numbers", {"x": int})
+        self.db.insert_many("numbers", [{"x": i} for i in range(5)])
+        started = threading.Event()
+
+        def slow(x):
+            started.set()
+            time.sleep(0.02)
+            return x
+
+        self.db.create_function("slow", 1, slow)
+        results = []
+        worker = threading.Thread(
+            target=lambda: results.append(self.db.fetch_all("SELECT slow(x) FROM numbers", []))
+        )
+        worker.start()
+        started.wait()
+
+        # These wait for the connection while the worker's query needs the GIL for slow()
+        self.assertEqual(self.db.get_user_version(), 0)
+        self.db.insert("numbers", {"x": 5})
+        worker.join(timeout=10)
+        self.assertFalse(worker.is_alive())
+        self.assertEqual(results, [[["0"], ["1"], ["2"], ["3"], ["4"]]])
+
+    def test_create_collation(self):
+        """Test sorting with a collation defined in Python."""
+        self.db.create_table("files", {"name": str})
+        self.db.insert_many("files", [{"name": "file10"}, {"name": "File2"}, {"name": "file1"}])
+
+        def natural(a, b):
+            key = lambda s: (s.rstrip("0123456789").lower(), int(s[len(s.rstrip("0123456789")):] or 0))
+            return (key(a) > key(b)) - (key(a) < key(b))
+
+        self.db.create_collation("natural_sort", natural)
+        self.assertEqual(
+            self.db.fetch_all("SELECT name FROM files ORDER BY name COLLATE natural_sort", []),
+            [["file1"], ["File2"], ["file10"]],
+        )
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM files WHERE name = 'file02' COLLATE natural_sort", []), 1)
+        with self.assertRaises(RuntimeError):
+            self.db.create_collation("broken", "not callable")
+
+    def test_create_collation_cannot_use_the_database(self):
+        """Test that a collation using the database of its query reports an error instead of waiting forever."""
+        self.db.create_table("files", {"name": str})
+        self.db.insert_many("files", [{"name": "b"}, {"name": "a"}])
+
+        def compare(a, b):
+            self.db.fetch_all("SELECT 1", [])
+            return (a > b) - (a < b)
+
+        self.db.create_collation("with_query", compare)
+        unraisable = []
+        sys.unraisablehook, hook = unraisable.append, sys.unraisablehook
+        try:
+            self.db.fetch_all("SELECT name FROM files ORDER BY name COLLATE with_query", [])
+        finally:
+            sys.unraisablehook = hook
+        self.assertIsInstance(unraisable[0].exc_value, DatabaseError)
+
+    def test_create_aggregate(self):
+        """Test an aggregate function defined by a Python class."""
+        self.db.create_table("orders", {"customer": str, "price": float, "quantity": int})
+        self.db.insert_many("orders", [
+            {"customer": "rayan", "price": 10.0, "quantity": 1},
+            {"customer": "rayan", "price": 20.0, "quantity": 3},
+            {"customer": "tom", "price": 5.0, "quantity": 2},
+        ])
+
+        class WeightedAverage:
+            def __init__(self):
+                self.total, self.weights = 0, 0
+
+            def step(self, value, weight):
+                self.total += value * weight
+                self.weights += weight
+
+            def finalize(self):
+                return self.total / self.weights if self.weights else None
+
+        self.db.create_aggregate("weighted_avg", 2, WeightedAverage)
+        self.assertEqual(
+            self.db.fetch_all(
+                "SELECT customer, weighted_avg(price, quantity) FROM orders GROUP BY customer ORDER BY customer",
+                [], typed=True,
+            ),
+            [["rayan", 17.5], ["tom", 5.0]],
+        )
+        # Without rows finalize() is still called on a new instance
+        self.assertIsNone(self.db.fetch_value("SELECT weighted_avg(price, quantity) FROM orders WHERE price > 100", []))
+
+        class Broken:
+            def step(self, value):
+                raise ValueError("boom")
+
+            def finalize(self):
+                return 0
+
+        self.db.create_aggregate("broken", 1, Broken)
+        with self.assertRaises(OperationalError):
+            self.db.fetch_all("SELECT broken(price) FROM orders", [])
+        with self.assertRaises(RuntimeError):
+            self.db.create_aggregate("invalid", 1, "not a class")
+
+    def test_create_aggregate_cannot_use_the_database(self):
+        """Test that an aggregate using the database of its query raises instead of waiting forever."""
+        db = self.db
+
+        class Counter:
+            def step(self, value):
+                pass
+
+            def finalize(self):
+                return len(db.fetch_all("SELECT 1", []))
+
+        self.db.create_aggregate("counter", 1, Counter)
+        with self.assertRaisesRegex(OperationalError, "a callback cannot use the database"):
+            self.db.fetch_all("SELECT counter(1)", [])
+        self.assertEqual(self.db.fetch_all("SELECT 1", []), [["1"]])
+
+    ##### END CUSTOM FUNCTIONS #####
+
+    ##### READ ONLY #####
+
+    def test_read_only(self):
+        """Test that a read-only connection can read but not write."""
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS readable (name TEXT)", [])
+        self.db.execute_raw_query("INSERT INTO readable (name) VALUES (?)", ["rayan"])
+
+        reader = Database(TestRustSQLiteWrapper.TEST_DB_NAME, read_only=True)
+        self.assertEqual(reader.fetch_all("SELECT name FROM readable", []), [["rayan"]])
+        with self.assertRaises(RuntimeError):
+            reader.execute_raw_query("INSERT INTO readable (name) VALUES (?)", ["other"])
+
+    def test_create_dirs(self):
+        """Test that create_dirs creates the missing directories of the database path."""
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "data", "nested", "app.sqlite")
+            with self.assertRaises(RuntimeError):
+                Database(path)
+
+            db = Database(path, create_dirs=True)
+            db.create_table("users", {"name": str})
+            db.close()
+            self.assertTrue(os.path.isfile(path))
+
+            # The directories already existing is fine
+            Database(path, create_dirs=True).close()
+
+    def test_encryption_key_needs_sqlcipher(self):
+        """Test that a key is refused by a build without SQLCipher instead of being ignored."""
+        with self.assertRaisesRegex(RuntimeError, "encryption support"):
+            Database(TestRustSQLiteWrapper.TEST_DB_NAME, key="secret")
+        with self.assertRaisesRegex(RuntimeError, "encryption support"):
+            self.db.rekey("secret")
+
+    def test_connect_retries(self):
+        """Test that opening is retried before giving up."""
+        start = time.monotonic()
+        with self.assertRaises(RuntimeError):
+            Database("missing.sqlite", read_only=True, connect_retries=2, connect_retry_delay_ms=20)
+        self.assertGreaterEqual(time.monotonic() - start, 0.04)
+
+    ##### END READ ONLY #####
+
+    ##### ERRORS #####
+
+    def test_integrity_error(self):
+        """Test that a constraint violation raises IntegrityError."""
+        self.db.execute_raw_query("CREATE TABLE IF NOT EXISTS uniques (name TEXT UNIQUE)", [])
+        self.db.execute_raw_query("INSERT INTO uniques (name) VALUES (?)", ["rayan"])
+        with self.assertRaises(IntegrityError):
+            self.db.execute_raw_query("INSERT INTO uniques (name) VALUES (?)", ["rayan"])
+
+    def test_operational_error(self):
+        """Test that querying a missing table raises OperationalError."""
+        with self.assertRaises(OperationalError):
+            self.db.fetch_all("SELECT * FROM non_existing_table", [])
+
+    def test_error_hierarchy(self):
+        """Test that the exceptions keep RuntimeError as a base class."""
+        self.assertTrue(issubclass(IntegrityError, DatabaseError))
+        self.assertTrue(issubclass(OperationalError, DatabaseError))
+        self.assertTrue(issubclass(DatabaseError, RuntimeError))
+
+    def test_error_codes(self):
+        """Test that the SQLite result codes are attached to the exceptions."""
+        self.db.execute_raw_query("CREATE TABLE uniques (name TEXT UNIQUE)", [])
+        self.db.execute_raw_query("INSERT INTO uniques (name) VALUES (?)", ["rayan"])
+        with self.assertRaises(IntegrityError) as context:
+            self.db.execute_raw_query("INSERT INTO uniques (name) VALUES (?)", ["rayan"])
+        self.assertEqual(context.exception.sqlite_errorcode, 2067)  # SQLITE_CONSTRAINT_UNIQUE
+        self.assertEqual(context.exception.sqlite_primary_errorcode, 19)  # SQLITE_CONSTRAINT
+
+    def test_wrong_parameter_count(self):
+        """Test that a wrong number of parameters raises a clear error."""
+        self.db.create_table("users", {"name": str, "age": int})
+        query = "INSERT INTO users (name, age) VALUES (?, ?)"
+
+        with self.assertRaisesRegex(RuntimeError, "The query expects 2 parameters but 1 was given"):
+            self.db.execute_raw_query(query, ["Ray"])
+        with self.assertRaisesRegex(RuntimeError, "The query expects 2 parameters but 3 were given"):
+            self.db.execute_raw_query(query, ["Ray", 27, 1])
+        with self.assertRaisesRegex(RuntimeError, "The query expects 1 parameter but 0 were given"):
+            self.db.fetch_all("SELECT name FROM users WHERE name = '?' OR age = ?", [])
+
+    ##### END ERRORS #####
+
+    ##### USER VERSION #####
+
+    def test_user_version(self):
+        """Test reading and writing the user_version pragma."""
+        self.assertEqual(self.db.get_user_version(), 0)
+        self.db.set_user_version(3)
+        self.assertEqual(self.db.get_user_version(), 3)
+
+    def test_migrate(self):
+        """Test applying migrations in order and skipping the applied ones."""
+        migrations = [
+            (2, "ALTER TABLE migrated ADD COLUMN age INTEGER"),
+            (1, "CREATE TABLE migrated (name TEXT)"),
+        ]
+        self.assertEqual(self.db.migrate(migrations), [1, 2])
+        self.assertEqual(self.db.get_user_version(), 2)
+        self.assertEqual(self.db.migrate(migrations), [])
+
+    def test_migrate_failure_rolls_back(self):
+        """Test that a failing migration is rolled back and stops the following ones."""
+        migrations = [
+            (1, "CREATE TABLE migrated (name TEXT)"),
+            (2, "CREATE TABLE half_done (id INTEGER); INSERT INTO missing_table VALUES (1)"),
+            (3, "CREATE TABLE never_created (id INTEGER)"),
+        ]
+        with self.assertRaises(OperationalError):
+            self.db.migrate(migrations)
+
+        self.assertEqual(self.db.get_user_version(), 1)
+        self.assertEqual(
+            self.db.fetch_all("SELECT name FROM sqlite_master WHERE type = 'table'", []),
+            [["migrated"]],
+        )
+
+    ##### END USER VERSION #####
+
+    ##### CLOSE #####
+
+    def test_close(self):
+        """Test that a closed database refuses queries and can be closed twice."""
+        self.db.close()
+        with self.assertRaises(DatabaseError):
+            self.db.fetch_all("SELECT 1", [])
+        self.db.close()
+
+    def test_context_manager(self):
+        """Test that the with-block closes the database, even on error."""
+        with Database(TestRustSQLiteWrapper.TEST_DB_NAME) as db:
+            self.assertEqual(db.fetch_all("SELECT 1", []), [["1"]])
+        self.assertIn("open=False", repr(db))
+
+        with self.assertRaises(ValueError):
+            with Database(TestRustSQLiteWrapper.TEST_DB_NAME) as db:
+                raise ValueError("boom")
+        self.assertIn("open=False", repr(db))
+
+    def test_ping(self):
+        """Test that ping() is True while the database is open and False after close()."""
+        self.assertTrue(self.db.ping())
+        self.db.close()
+        self.assertFalse(self.db.ping())
+
+    ##### END CLOSE #####
+
+    ##### EXTENSIONS #####
+
+    def test_load_extension_disabled(self):
+        """Test that extensions cannot be loaded without opting in."""
+        with self.assertRaises(RuntimeError):
+            self.db.load_extension("./missing_extension")
+
+    def test_load_extension_missing(self):
+        """Test that a missing extension raises a clear error."""
+        db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, allow_extensions=True)
+        with self.assertRaises(OperationalError):
+            db.load_extension("./missing_extension")
+        db.close()
+
+    ##### END EXTENSIONS #####
+
+    ##### TRANSACTIONS #####
+
+    def test_transaction_commit_and_rollback(self):
+        """Test committing and rolling back a transaction."""
+        self.db.create_table("accounts", {"name": str})
+
+        self.assertFalse(self.db.in_transaction())
+        self.db.begin("immediate")
+        self.assertTrue(self.db.in_transaction())
+        self.db.insert("accounts", {"name": "kept"})
+        self.db.commit()
+        self.assertFalse(self.db.in_transaction())
+
+        self.db.begin()
+        self.db.insert("accounts", {"name": "dropped"})
+        self.db.rollback()
+
+        self.assertEqual(self.db.fetch_all("SELECT name FROM accounts", []), [["kept"]])
+
+    def test_transactional(self):
+        """Test wrapping functions in transactions with a decorator."""
+        self.db.create_table("accounts", {"name": str})
+
+        @self.db.transactional
+        def add(name, fail=False):
+            self.db.insert("accounts", {"name": name})
+            if fail:
+                raise ValueError("boom")
+            return name
+
+        @self.db.transactional(mode="IMMEDIATE")
+        def add_both(first, second):
+            add(first)
+            self.assertTrue(self.db.in_transaction())
+            try:
+                add(second, fail=True)
+            except ValueError:
+                pass
+
+        self.assertEqual(add("kept"), "kept")
+        with self.assertRaises(ValueError):
+            add("dropped", fail=True)
+        # The failing inner call only rolls back its own savepoint
+        add_both("outer", "inner")
+        self.assertEqual(self.db.fetch_all("SELECT name FROM accounts", []), [["kept"], ["outer"]])
+        self.assertEqual(add.__wrapped__("direct"), "direct")
+
+        class Repository:
+            @self.db.transactional
+            def count(repo):
+                return self.db.fetch_value("SELECT COUNT(*) FROM accounts", [])
+
+        self.assertEqual(Repository().count(), 3)
+        with self.assertRaises(RuntimeError):
+            self.db.transactional(mode="LAZY")
+
+    def test_transaction_context(self):
+        """Test running blocks in transactions, nested ones in savepoints."""
+        self.db.create_table("accounts", {"name": str})
+
+        with self.db.transaction():
+            self.db.insert("accounts", {"name": "kept"})
+        with self.assertRaises(ValueError):
+            with self.db.transaction(mode="IMMEDIATE"):
+                self.db.insert("accounts", {"name": "dropped"})
+                raise ValueError("boom")
+        self.assertFalse(self.db.in_transaction())
+
+        def add(name, fail=False):
+            with self.db.transaction():
+                self.db.insert("accounts", {"name": name})
+                if fail:
+                    raise ValueError("boom")
+
+        # The inner block is rolled back, the outer one is committed
+        with self.db.transaction():
+            add("outer")
+            try:
+                add("inner", fail=True)
+            except ValueError:
+                pass
+            with self.db.transaction():
+                add("deeply nested")
+            self.assertTrue(self.db.in_transaction())
+        self.assertFalse(self.db.in_transaction())
+        self.assertEqual(
+            self.db.fetch_all("SELECT name FROM accounts", []),
+            [["kept"], ["outer"], ["deeply nested"]],
+        )
+
+        # A rolled back outer block also drops the nested blocks that succeeded
+        with self.assertRaises(ValueError):
+            with self.db.transaction():
+                add("released")
+                raise ValueError("boom")
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM accounts", []), 3)
+
+    def test_bulk_load(self):
+        """Test that a bulk load applies the speed pragmas and restores them, even on error."""
+        self.db.fetch_all("PRAGMA journal_mode=WAL", [])
+        self.db.execute_script(
+            """
+            PRAGMA foreign_keys = ON;
+            CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users (id));
+            """
+        )
+        pragmas = lambda: self.db.fetch_all("SELECT * FROM pragma_journal_mode(), pragma_synchronous()", [])
+        self.assertEqual(pragmas(), [["wal", "2"]])
+
+        # The foreign keys are only checked at the commit, so children can come first
+        with self.db.bulk_load():
+            self.assertEqual(pragmas(), [["memory", "0"]])
+            self.db.insert_many("posts", [{"user_id": 1}, {"user_id": 2}])
+            self.db.insert_many("users", [{"name": "Ray"}, {"name": "Tom"}])
+        self.assertEqual(pragmas(), [["wal", "2"]])
+        self.assertFalse(self.db.in_transaction())
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM posts", []), 2)
+
+        with self.assertRaises(ValueError):
+            with self.db.bulk_load():
+                self.db.insert("users", {"name": "dropped"})
+                raise ValueError("boom")
+        # A foreign key still missing at the end fails the commit
+        with self.assertRaises(IntegrityError):
+            with self.db.bulk_load():
+                self.db.insert("posts", {"user_id": 42})
+        self.assertEqual(pragmas(), [["wal", "2"]])
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM users", []), 2)
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM posts", []), 2)
+
+        with self.db.transaction():
+            with self.assertRaises(RuntimeError):
+                with self.db.bulk_load():
+                    pass
+
+    def test_read_transaction(self):
+        """Test that a read transaction sees one snapshot and refuses writes."""
+        self.db.fetch_all("PRAGMA journal_mode=WAL", [])
+        self.db.create_table("orders", {"amount": int})
+        self.db.insert("orders", {"amount": 10})
+        writer = Database(TestRustSQLiteWrapper.TEST_DB_NAME)
+
+        with self.db.read_transaction():
+            # Written by another connection after the snapshot, so not seen
+            writer.insert("orders", {"amount": 20})
+            self.assertEqual(self.db.fetch_value("SELECT SUM(amount) FROM orders", []), 10)
+            with self.assertRaises(DatabaseError):
+                self.db.insert("orders", {"amount": 30})
+        writer.close()
+
+        self.assertFalse(self.db.in_transaction())
+        self.assertEqual(self.db.fetch_value("SELECT SUM(amount) FROM orders", []), 30)
+        # Writes work again after the block, even when it raised
+        with self.assertRaises(ValueError):
+            with self.db.read_transaction():
+                raise ValueError("boom")
+        self.db.insert("orders", {"amount": 30})
+        self.assertEqual(self.db.fetch_value("SELECT COUNT(*) FROM orders", []), 3)
+
+        transaction = self.db.transaction()
+        with transaction:
+            with self.assertRaises(RuntimeError):
+                transaction.__enter__()
+        with self.assertRaises(RuntimeError):
+            self.db.transaction(mode="LAZY")
+
+    def test_begin_wrong_mode(self):
+        """Test that an unknown transaction mode is rejected."""
+        with self.assertRaises(RuntimeError):
+            self.db.begin("LAZY")
+
+    def test_commit_and_rollback_hooks(self):
+        """Test reacting to commits and rollbacks, and refusing a commit."""
+        self.db.create_table("accounts", {"name": str})
+        events = []
+        self.db.set_commit_hook(lambda: events.append("commit"))
+        self.db.set_rollback_hook(lambda: events.append("rollback"))
+
+        self.db.begin()
+        self.db.insert("accounts", {"name": "kept"})
+        self.db.commit()
+        self.db.begin()
+        self.db.insert("accounts", {"name": "dropped"})
+        self.db.rollback()
+        self.assertEqual(events, ["commit", "rollback"])
+
+        self.db.set_commit_hook(lambda: True)
+        with self.assertRaises(IntegrityError):
+            self.db.insert("accounts", {"name": "refused"})
+        self.assertEqual(events, ["commit", "rollback", "rollback"])
+
+        self.db.set_commit_hook(None)
+        self.db.set_rollback_hook(None)
+        self.db.insert("accounts", {"name": "autocommit"})
+        self.assertEqual(len(events), 3)
+        self.assertEqual(self.db.fetch_all("SELECT name FROM accounts", []), [["kept"], ["autocommit"]])
+
+    def test_progress_handler(self):
+        """Test reporting the progress of a query and cancelling it from a handler."""
+        calls = []
+        self.db.set_progress_handler(100, lambda: calls.append(1))
+        counting = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 10000) SELECT COUNT(*) FROM n"
+        self.assertEqual(self.db.fetch_value(counting, []), 10000)
+        self.assertGreater(len(calls), 10)
+
+        endless = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT COUNT(*) FROM n"
+        self.db.set_progress_handler(1000, lambda: len(calls) > 50 or calls.append(1))
+        with self.assertRaises(OperationalError):
+            self.db.fetch_all(endless, [])
+
+        unraisable = []
+        sys.unraisablehook, hook = unraisable.append, sys.unraisablehook
+        try:
+            self.db.set_progress_handler(1000, lambda: 1 / 0)
+            with self.assertRaises(OperationalError):
+                self.db.fetch_all(counting, [])
+        finally:
+            sys.unraisablehook = hook
+        self.assertIsInstance(unraisable[0].exc_value, ZeroDivisionError)
+
+        calls.clear()
+        self.db.set_progress_handler(0, None)
+        self.assertEqual(self.db.fetch_value(counting, []), 10000)
+        self.assertEqual(calls, [])
+        with self.assertRaises(RuntimeError):
+            self.db.set_progress_handler(0, lambda: False)
+
+    def test_retry_when_busy(self):
+        """Test retrying the queries of a second connection while the database is locked."""
+        self.db.create_table("users", {"name": str})
+        other = Database(TestRustSQLiteWrapper.TEST_DB_NAME, max_retries=6, retry_backoff_ms=20)
+        impatient = Database(TestRustSQLiteWrapper.TEST_DB_NAME)
+        # Without SQLite's own wait, the lock is reported right away
+        other.fetch_all("PRAGMA busy_timeout = 0", [])
+        impatient.fetch_all("PRAGMA busy_timeout = 0", [])
+        try:
+            self.db.begin("exclusive")
+            with self.assertRaises(OperationalError):
+                impatient.insert("users", {"name": "Tom"})
+
+            threading.Timer(0.1, self.db.commit).start()
+            other.insert("users", {"name": "Ray"})
+            self.assertEqual(other.fetch_all("SELECT name FROM users", []), [["Ray"]])
+        finally:
+            other.close()
+            impatient.close()
+
+    ##### END TRANSACTIONS #####
+
+    ##### EXPORT #####
+
+    def test_fetch_json(self):
+        """Test getting the rows of a query as JSON text."""
+        import json
+
+        self.db.create_table("exported", {"name": str, "age": int, "score": float, "data": "BLOB"})
+        self.db.execute_raw_query("INSERT INTO exported VALUES (?, ?, ?, ?)", ['say "hi"\n', 27, 1.5, b"hi"])
+        self.db.execute_raw_query("INSERT INTO exported (name, score) VALUES (?, ?)", ["Tom", 2.0])
+
+        text = self.db.fetch_json("SELECT * FROM exported ORDER BY age DESC", [])
+        self.assertEqual(json.loads(text), [
+            {"name": 'say "hi"\n', "age": 27, "score": 1.5, "data": "aGk="},
+            {"name": "Tom", "age": None, "score": 2.0, "data": None},
+        ])
+        self.assertIsInstance(json.loads(text)[1]["score"], float)
+        # The keys keep the order of the columns
+        self.assertEqual(list(json.loads(text)[0]), ["name", "age", "score", "data"])
+        self.assertEqual(self.db.fetch_json("SELECT * FROM exported WHERE age > ?", [100]), "[]")
+
+    def test_export_csv(self):
+        """Test exporting the results of a query to a CSV file."""
+        csv_path = "test_export.csv"
+        self.db.create_table("exported", {"name": str, "age": int, "data": "BLOB"})
+        self.db.execute_raw_query("INSERT INTO exported VALUES (?, ?, ?)", ["ray, an", 27, b"hi"])
+        self.db.execute_raw_query("INSERT INTO exported (name) VALUES (?)", ['say "hi"'])
+
+        try:
+            written = self.db.export_csv("SELECT * FROM exported", [], csv_path)
+            self.assertEqual(written, 2)
+            with open(csv_path) as f:
+                self.assertEqual(
+                    f.read(),
+                    'name,age,data\n"ray, an",27,aGk=\n"say ""hi""",,\n',
+                )
+        finally:
+            if os.path.exists(csv_path):
+                os.remove(csv_path)
+
+    def test_dump_and_load_dump(self):
+        """Test recreating a database from its SQL dump."""
+        restored_path = "test_restored.sqlite"
+        self.db.execute_raw_query("CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, score REAL, avatar BLOB)", [])
+        self.db.execute_raw_query("CREATE TABLE \"odd \"\"name\"\"\" (value, doubled GENERATED ALWAYS AS (value * 2))", [])
+        self.db.execute_raw_query("CREATE INDEX idx_name ON users (name)", [])
+        self.db.create_view("named_users", "SELECT name FROM users WHERE name IS NOT NULL")
+        self.db.insert_many("users", [{"name": "it's\nRay", "score": 3.0, "avatar": b"\x00\xff"}])
+        self.db.execute_raw_query("INSERT INTO users (score) VALUES (-1e999)", [])
+        self.db.execute_raw_query("INSERT INTO \"odd \"\"name\"\"\" (value) VALUES (21)", [])
+
+        dump = self.db.dump()
+        self.assertTrue(dump.startswith("PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n"))
+        self.assertTrue(dump.endswith("COMMIT;\n"))
+
+        if os.path.exists(restored_path):
+            os.remove(restored_path)
+        restored = Database(restored_path)
+        try:
+            restored.load_dump(dump)
+            self.assertEqual(restored.dump(), dump)
+            self.assertEqual(
+                restored.fetch_all("SELECT id, name, score, avatar FROM users", [], typed=True),
+                [[1, "it's\nRay", 3.0, b"\x00\xff"], [2, None, float("-inf"), None]],
+            )
+            self.assertEqual(restored.fetch_value("SELECT doubled FROM \"odd \"\"name\"\"\"", []), 42)
+            self.assertEqual(restored.fetch_all("SELECT * FROM named_users", []), [["it's\nRay"]])
+            restored.insert("users", {"name": "Tom"})
+            self.assertEqual(restored.fetch_value("SELECT MAX(id) FROM users", []), 3)
+
+            # A failing script is rolled back entirely
+            with self.assertRaises(OperationalError):
+                restored.load_dump("BEGIN TRANSACTION;\nCREATE TABLE other (a);\nINSERT INTO missing VALUES(1);\nCOMMIT;\n")
+            self.assertEqual(restored.fetch_value("SELECT COUNT(*) FROM sqlite_schema WHERE name = 'other'", []), 0)
+            restored.insert("users", {"name": "Ann"})
+        finally:
+            restored.close()
+            if os.path.exists(restored_path):
+                os.remove(restored_path)
+
+    ##### END EXPORT #####
+
+    ##### BLOB #####
+
+    def test_blob_read_write_seek(self):
+        """Test streaming a BLOB with incremental I/O."""
+        self.db.create_table("files", {"data": "BLOB"})
+        self.db.execute_raw_query("INSERT INTO files (data) VALUES (zeroblob(?))", [10])
+        rowid = int(self.db.fetch_all("SELECT last_insert_rowid()", [])[0][0])
+
+        with self.db.open_blob("files", "data", rowid, read_only=False) as blob:
+            self.assertEqual(len(blob), 10)
+            self.assertEqual(blob.write(b"hello"), 5)
+            self.assertEqual(blob.tell(), 5)
+            blob.write(b"world")
+            with self.assertRaises(DatabaseError):
+                blob.write(b"!")
+
+            self.assertEqual(blob.seek(0), 0)
+            self.assertEqual(blob.read(5), b"hello")
+            self.assertEqual(blob.read(), b"world")
+            self.assertEqual(blob.read(), b"")
+            blob.seek(-3, 2)
+            self.assertEqual(blob.read(), b"rld")
+
+        with self.assertRaises(RuntimeError):
+            blob.read()
+        self.assertEqual(self.db.fetch_all("SELECT data FROM files", []), [[b"helloworld"]])
+
+    def test_blob_read_only(self):
+        """Test that a read-only BLOB cannot be written and missing rows fail to open."""
+        self.db.create_table("files", {"data": "BLOB"})
+        self.db.execute_raw_query("INSERT INTO files (data) VALUES (?)", [b"abc"])
+
+        blob = self.db.open_blob("files", "data", 1)
+        self.assertEqual(blob.read(), b"abc")
+        with self.assertRaises(RuntimeError):
+            blob.write(b"x")
+        with self.assertRaises(DatabaseError):
+            self.db.open_blob("files", "data", 42)
+
+    def test_get_rowid(self):
+        """Test finding the rowid of the most recent row matching some values."""
+        self.db.create_table("files", {"name": str, "folder": str, "data": "BLOB"})
+        self.db.insert_many("files", [
+            {"name": "a.txt", "folder": "docs", "data": b"first"},
+            {"name": "b.txt", "folder": None, "data": b"other"},
+            {"name": "a.txt", "folder": "docs", "data": b"second"},
+        ])
+
+        rowid = self.db.get_rowid("files", {"name": "a.txt", "folder": "docs"})
+        self.assertEqual(rowid, 3)
+        with self.db.open_blob("files", "data", rowid) as blob:
+            self.assertEqual(blob.read(), b"second")
+        self.assertEqual(self.db.get_rowid("files", {"folder": None}), 2)
+        self.assertEqual(self.db.get_rowid("files", {}), 3)
+        self.assertIsNone(self.db.get_rowid("files", {"name": "missing.txt"}))
+
+        with self.assertRaises(RuntimeError):
+            self.db.get_rowid("files", {"name; --": "a.txt"})
+        with self.assertRaises(OperationalError):
+            self.db.get_rowid("files", {"size": 1})
+
+    ##### END BLOB #####
+
+    ##### JSON #####
+
+    def test_insert_and_fetch_json(self):
+        """Test storing dicts and lists as JSON and parsing them back."""
+        self.db.create_table("users", {"name": str, "settings": "TEXT", "tags": "TEXT"})
+        self.db.insert_json("users", {"name": "Ray", "settings": {"theme": "dark", "size": 2}, "tags": ["a", "b"]})
+        self.db.insert_json("users", {"name": "Ann", "settings": None, "tags": []})
+
+        rows = self.db.fetch_all("SELECT name, settings, tags FROM users ORDER BY name", [], json_columns=["settings", "tags"])
+        self.assertEqual(rows, [["Ann", None, []], ["Ray", {"theme": "dark", "size": 2}, ["a", "b"]]])
+
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT name FROM users", [], json_columns=["settings"])
+
+    def test_json_extract(self):
+        """Test building json_extract() expressions."""
+        self.assertEqual(Database.json_extract("settings", "theme"), "json_extract(settings, '$.theme')")
+        self.assertEqual(Database.json_extract("settings", "$.tags[0]"), "json_extract(settings, '$.tags[0]')")
+
+        self.db.create_table("users", {"name": str, "settings": "TEXT"})
+        self.db.insert_json("users", {"name": "Ray", "settings": {"theme": "dark"}})
+        query = "SELECT name FROM users WHERE " + Database.json_extract("settings", "theme") + " = ?"
+        self.assertEqual(self.db.fetch_all(query, ["dark"]), [["Ray"]])
+
+    ##### END JSON #####
+
+    ##### VIEWS #####
+
+    def test_create_and_drop_view(self):
+        """Test creating a view, querying it and dropping it."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert("users", {"name": "Ray", "age": 27})
+        self.db.insert("users", {"name": "Tom", "age": 12})
+
+        self.db.create_view("adults", "SELECT name FROM users WHERE age >= 18")
+        self.db.create_view("adults", "SELECT name FROM users WHERE age >= 18")
+        self.assertEqual(self.db.fetch_all("SELECT name FROM adults", []), [["Ray"]])
+        with self.assertRaises(OperationalError):
+            self.db.create_view("adults", "SELECT 1", if_not_exists=False)
+
+        self.db.drop_view("adults")
+        self.db.drop_view("adults")
+        with self.assertRaises(OperationalError):
+            self.db.drop_view("adults", if_exists=False)
+
+    def test_view_name_is_validated(self):
+        """Test that invalid view names are rejected."""
+        for name in ["", "1view", "v; DROP TABLE users", "my view"]:
+            with self.assertRaises(RuntimeError):
+                self.db.create_view(name, "SELECT 1")
+
+    def test_view_name_is_quoted(self):
+        """Test that a view can be named after an SQL keyword."""
+        self.db.create_view("order", "SELECT 1 AS one")
+        self.assertEqual(self.db.fetch_all('SELECT one FROM "order"', []), [["1"]])
+        self.db.drop_view("order")
+
+    def test_create_view_refuses_extra_statements(self):
+        """Test that create_view() does not run a statement stacked after the query."""
+        self.db.create_table("users", {"name": str})
+        self.db.insert("users", {"name": "Ray"})
+        with self.assertRaises(DatabaseError):
+            self.db.create_view("v", "SELECT 1; DROP TABLE users")
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users", []), [["Ray"]])
+
+    def test_with_cte(self):
+        """Test running a query with common table expressions."""
+        self.db.create_table("users", {"name": str, "age": int})
+        self.db.insert_many("users", [{"name": "Ray", "age": 27}, {"name": "Tom", "age": 12}, {"name": "Ann", "age": 40}])
+
+        ctes = {
+            "adults": "SELECT name, age FROM users WHERE age >= ?",
+            "seniors": "SELECT name FROM adults WHERE age >= ?",
+        }
+        self.assertEqual(self.db.with_cte(ctes, "SELECT name FROM seniors", [18, 30]), [["Ann"]])
+
+        numbers = {"numbers": "SELECT 1 AS x UNION ALL SELECT x + 1 FROM numbers WHERE x < 3"}
+        self.assertEqual(self.db.with_cte(numbers, "SELECT x FROM numbers", [], typed=True), [[1], [2], [3]])
+        with self.assertRaises(RuntimeError):
+            self.db.with_cte({"x) AS (SELECT 1); DROP TABLE users; --": "SELECT 1"}, "SELECT 1", [])
+        self.assertEqual(self.db.with_cte({"group": "SELECT 7 AS x"}, 'SELECT x FROM "group"', [], typed=True), [[7]])
+
+    ##### END VIEWS #####
+
     ##### GLOBAL #####
 
     def test_create_table(self):
@@ -69,6 +1901,313 @@ class TestRustSQLiteWrapper(unittest.TestCase):
         except Exception as e:
             self.fail(f"An error occurred while creating the table: {e}")
 
+    def test_cache_size(self):
+        """Test setting the page cache size when opening the database."""
+        for cache_size in [-65536, 500]:
+            db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, cache_size=cache_size)
+            try:
+                self.assertEqual(db.fetch_value("PRAGMA cache_size", []), cache_size)
+            finally:
+                db.close()
+        self.assertEqual(self.db.fetch_value("PRAGMA cache_size", []), -2000)
+
+    def test_sqlite_version(self):
+        """Test reading the version of the SQLite library."""
+        version = Database.sqlite_version()
+        self.assertEqual(self.db.fetch_value("SELECT sqlite_version()", []), version)
+        major, minor, patch = (int(part) for part in version.split("."))
+        self.assertEqual(Database.sqlite_version_number(), major * 1000000 + minor * 1000 + patch)
+
+    def test_create_strict_and_without_rowid_tables(self):
+        """Test the STRICT and WITHOUT ROWID table options."""
+        self.db.create_table("users", {"id": "INTEGER PRIMARY KEY", "name": str, "age": int}, strict=True)
+        self.db.insert("users", {"name": "Ray", "age": 27})
+        with self.assertRaises(IntegrityError):
+            self.db.insert("users", {"name": "Tom", "age": "twelve"})
+        with self.assertRaises(RuntimeError):
+            self.db.create_table("flags", {"active": bool}, strict=True)
+        with self.assertRaises(RuntimeError):
+            self.db.create_table("names", {"name": "VARCHAR(10)"}, strict=True)
+
+        self.db.create_table("settings", {"key": "TEXT PRIMARY KEY", "value": str}, without_rowid=True, strict=True)
+        self.assertEqual(
+            self.db.fetch_value("SELECT sql FROM sqlite_schema WHERE name = 'settings'", []),
+            'CREATE TABLE "settings" ("key" TEXT PRIMARY KEY, "value" TEXT) STRICT, WITHOUT ROWID',
+        )
+        with self.assertRaises(OperationalError):
+            self.db.create_table("no_key", {"value": str}, without_rowid=True)
+
+    def test_quote_identifier_and_literal(self):
+        """Test quoting names and values for dynamic SQL."""
+        self.assertEqual(Database.quote_identifier("users"), '"users"')
+        self.assertEqual(Database.quote_identifier('a"b'), '"a""b"')
+        self.assertEqual(Database.quote_literal("it's"), "'it''s'")
+        self.assertEqual(Database.quote_literal(None), "NULL")
+        self.assertEqual(Database.quote_literal(2.0), "2.0")
+        self.assertEqual(Database.quote_literal(True), "1")
+        self.assertEqual(Database.quote_literal(b"\x00\xff"), "X'00ff'")
+        with self.assertRaises(RuntimeError):
+            Database.quote_literal(float("nan"))
+
+        # The helpers quote their names too, so keywords and odd names work
+        self.db.create_table("order", {"group": str, 'say "hi"': int})
+        self.db.insert("order", {"group": "a", 'say "hi"': 1})
+        self.db.insert_many("order", [{"group": "b", 'say "hi"': 2}])
+        self.assertEqual(
+            self.db.fetch_all(f"SELECT {Database.quote_identifier('group')} FROM {Database.quote_identifier('order')}", []),
+            [["a"], ["b"]],
+        )
+        query = 'SELECT COUNT(*) FROM "order" WHERE "group" = '
+        self.assertEqual(self.db.fetch_value(query + Database.quote_literal("b"), []), 1)
+        self.assertEqual(self.db.fetch_value(query + Database.quote_literal("b' OR 1 = 1 --"), []), 0)
+
+    def test_path(self):
+        """Test reading the database path."""
+        self.assertEqual(self.db.path, TestRustSQLiteWrapper.TEST_DB_NAME)
+
+    def test_repr(self):
+        """Test that the representation shows the database path."""
+        self.assertEqual(repr(self.db), f"Database(path='{TestRustSQLiteWrapper.TEST_DB_NAME}', open=True)")
+        self.db.close()
+        self.assertEqual(repr(self.db), f"Database(path='{TestRustSQLiteWrapper.TEST_DB_NAME}', open=False)")
+
+    def test_allowed_extensions(self):
+        """Test that .sqlite3 is accepted and other extensions can be allowed."""
+        for path in ["test_ext.sqlite3", "test_ext.data"]:
+            if os.path.exists(path):
+                os.remove(path)
+
+        try:
+            Database("test_ext.sqlite3").close()
+            with self.assertRaises(RuntimeError):
+                Database("test_ext.data")
+            Database("test_ext.data", extra_extensions=[".data"]).close()
+        finally:
+            for path in ["test_ext.sqlite3", "test_ext.data"]:
+                if os.path.exists(path):
+                    os.remove(path)
+
+    def test_uri(self):
+        """Test opening databases with URI filenames."""
+        self.db.create_table("users", {"name": str})
+        self.db.insert("users", {"name": "Ray"})
+
+        ro = Database(f"file:{TestRustSQLiteWrapper.TEST_DB_NAME}?mode=ro", uri=True)
+        self.assertEqual(ro.fetch_all("SELECT name FROM users", []), [["Ray"]])
+        with self.assertRaises(OperationalError):
+            ro.execute_raw_query("DELETE FROM users", [])
+        ro.close()
+
+        # Two connections sharing the same in-memory database
+        first = Database("file:shared_test?mode=memory&cache=shared", uri=True)
+        second = Database("file:shared_test?mode=memory&cache=shared", uri=True)
+        first.execute_raw_query("CREATE TABLE shared (value INTEGER)", [])
+        first.execute_raw_query("INSERT INTO shared VALUES (1)", [])
+        self.assertEqual(second.fetch_all("SELECT value FROM shared", []), [["1"]])
+        first.close()
+        second.close()
+
+        with self.assertRaises(RuntimeError):
+            Database("file:data.db?mode=ro")
+        with self.assertRaises(RuntimeError):
+            Database("data.db", uri=True)
+
+    def test_non_finite_floats(self):
+        """Test the handling of NaN and infinite floats."""
+        self.db.create_table("numbers", {"value": float})
+        with self.assertRaises(RuntimeError):
+            self.db.execute_raw_query("INSERT INTO numbers VALUES (?)", [float("nan")])
+        with self.assertRaises(RuntimeError):
+            self.db.insert("numbers", {"value": float("inf")})
+        with self.assertRaises(RuntimeError):
+            Database(TestRustSQLiteWrapper.TEST_DB_NAME, non_finite_floats="zero")
+        self.db.close()
+
+        self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, non_finite_floats="text")
+        self.db.execute_raw_query("INSERT INTO numbers VALUES (?), (?)", [float("nan"), float("-inf")])
+        self.db.insert("numbers", {"value": float("inf")})
+        self.assertEqual(self.db.fetch_all("SELECT value FROM numbers", []), [["nan"], ["-inf"], ["inf"]])
+        self.db.close()
+
+        self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, non_finite_floats="null")
+        self.db.execute_raw_query("DELETE FROM numbers", [])
+        self.db.execute_raw_query("INSERT INTO numbers VALUES (?)", [float("nan")])
+        self.db.insert("numbers", {"value": float("inf")})
+        self.assertEqual(self.db.fetch_all("SELECT value IS NULL FROM numbers", []), [["1"], ["1"]])
+
+    def test_large_ints(self):
+        """Test the handling of ints that do not fit in 64 bits."""
+        # Without a declared type, SQLite keeps the TEXT values as they are
+        self.db.execute_raw_query("CREATE TABLE numbers (value)", [])
+        self.db.execute_raw_query("INSERT INTO numbers VALUES (?)", [2**63 - 1])
+        with self.assertRaisesRegex(RuntimeError, "9223372036854775807"):
+            self.db.execute_raw_query("INSERT INTO numbers VALUES (?)", [2**63])
+        with self.assertRaises(RuntimeError):
+            self.db.insert("numbers", {"value": -(2**64)})
+        with self.assertRaises(RuntimeError):
+            Database(TestRustSQLiteWrapper.TEST_DB_NAME, large_ints="float")
+        self.db.close()
+
+        self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, large_ints="text")
+        self.db.execute_raw_query("INSERT INTO numbers VALUES (?)", [2**64])
+        self.db.insert("numbers", {"value": -(10**30)})
+        self.assertEqual(
+            self.db.fetch_all("SELECT value, typeof(value) FROM numbers", [], typed=True),
+            [[2**63 - 1, "integer"], [str(2**64), "text"], [str(-(10**30)), "text"]],
+        )
+
+    def test_surrogates_option(self):
+        """Test the handling of strings holding lone surrogates."""
+        name = "report\udcff.txt"
+        self.db.create_table("files", {"name": str})
+        with self.assertRaisesRegex(RuntimeError, "surrogate"):
+            self.db.execute_raw_query("INSERT INTO files (name) VALUES (?)", [name])
+        with self.assertRaisesRegex(RuntimeError, "udcff"):
+            self.db.insert("files", {"name": name})
+        with self.assertRaises(RuntimeError):
+            self.db.fetch_all("SELECT name FROM files WHERE name = :name", {"name": name})
+        with self.assertRaises(RuntimeError):
+            Database(TestRustSQLiteWrapper.TEST_DB_NAME, surrogates="ignore")
+        self.db.close()
+
+        self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, surrogates="replace")
+        self.db.insert("files", {"name": name})
+        self.assertEqual(self.db.fetch_all("SELECT name FROM files", []), [["report\ufffd.txt"]])
+        self.db.close()
+
+        self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, surrogates="blob")
+        self.db.execute_raw_query("DELETE FROM files", [])
+        self.db.execute_raw_query("INSERT INTO files (name) VALUES (?)", [name])
+        self.db.insert("files", {"name": "plain.txt"})
+        stored = self.db.fetch_all("SELECT name FROM files", [])
+        self.assertEqual(stored[0][0].decode("utf-8", "surrogatepass"), name)
+        self.assertEqual(stored[1], ["plain.txt"])
+
+    def test_reals_option(self):
+        """Test returning the REAL values of typed fetches as Decimal or text."""
+        self.db.create_table("prices", {"amount": float, "label": str})
+        self.db.insert_many("prices", [{"amount": 0.1, "label": "a"}, {"amount": 0.2, "label": "b"}])
+        self.db.execute_raw_query("INSERT INTO prices (amount, label) VALUES (3, 'c')", [])
+        query = "SELECT amount FROM prices"
+
+        self.assertEqual(self.db.fetch_all(query, [], typed=True), [[0.1], [0.2], [3.0]])
+        self.db.close()
+
+        self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, reals="decimal")
+        rows = self.db.fetch_all(query, [], typed=True)
+        self.assertEqual(rows, [[Decimal("0.1")], [Decimal("0.2")], [Decimal("3")]])
+        self.assertEqual(sum(row[0] for row in rows[:2]), Decimal("0.3"))
+        self.assertEqual(self.db.fetch_value("SELECT SUM(amount) FROM prices", []), Decimal("3.3"))
+        # Untyped fetches and the text columns are not affected
+        self.assertEqual(self.db.fetch_all("SELECT amount, label FROM prices LIMIT 1", []), [["0.1", "a"]])
+        self.db.close()
+
+        self.db = Database(TestRustSQLiteWrapper.TEST_DB_NAME, reals="text")
+        self.assertEqual(self.db.fetch_all("SELECT amount + 0.2 FROM prices LIMIT 1", [], typed=True), [["0.30000000000000004"]])
+        with self.assertRaises(RuntimeError):
+            Database(TestRustSQLiteWrapper.TEST_DB_NAME, reals="double")
+
+    def test_create_table_with_sql_types(self):
+        """Test creating a table with SQL types given as strings."""
+        self.db.create_table("typed", {"id": "INTEGER PRIMARY KEY", "data": "BLOB", "name": str})
+        self.db.execute_raw_query("INSERT INTO typed (name) VALUES (?)", ["rayan"])
+        self.assertEqual(self.db.fetch_all("SELECT id, name FROM typed", []), [["1", "rayan"]])
+
+    def test_create_table_wrong_type(self):
+        """Test that an unsupported Python type is rejected."""
+        with self.assertRaises(RuntimeError):
+            self.db.create_table("wrong", {"data": list})
+
+    def test_create_table_from_class(self):
+        """Test creating a table from a class with type annotations."""
+        class Player:
+            name: str
+            score: float
+            active: bool
+
+        self.db.create_table_from_class(Player)
+        self.db.insert("player", {"name": "rayan", "score": 1.5, "active": True})
+        self.assertEqual(
+            self.db.fetch_all("SELECT name, score, active FROM player", []),
+            [["rayan", "1.5", True]],
+        )
+
+    def test_rename_table_and_add_column(self):
+        """Test renaming a table and adding columns to it."""
+        self.db.create_table("users", {"name": str})
+        self.db.insert("users", {"name": "Ray"})
+
+        self.db.rename_table("users", "customers")
+        self.db.add_column("customers", "vip", bool)
+        self.db.add_column("customers", "score", "INTEGER NOT NULL DEFAULT 0")
+        self.assertEqual(self.db.fetch_all("SELECT name, vip, score FROM customers", []), [["Ray", "NULL", "0"]])
+        with self.assertRaises(OperationalError):
+            self.db.fetch_all("SELECT * FROM users", [])
+
+        with self.assertRaises(OperationalError):
+            self.db.rename_table("users", "clients")
+        with self.assertRaises(RuntimeError):
+            self.db.rename_table("customers", "clients; DROP TABLE customers")
+        with self.assertRaises(RuntimeError):
+            self.db.add_column("customers", "tags", list)
+
+    def test_add_and_drop_column(self):
+        """Test adding a column with constraints and dropping it."""
+        self.db.create_table("users", {"name": str})
+        self.db.insert("users", {"name": "Ray"})
+
+        self.db.add_column("users", "score", int, constraints="NOT NULL DEFAULT 5")
+        self.assertEqual(self.db.fetch_all("SELECT name, score FROM users", []), [["Ray", "5"]])
+        with self.assertRaises(IntegrityError):
+            self.db.execute_raw_query("INSERT INTO users (name, score) VALUES (?, NULL)", ["Tom"])
+        with self.assertRaises(DatabaseError):
+            self.db.add_column("users", "level", int, constraints="DEFAULT 0; DROP TABLE users")
+        self.assertEqual(self.db.fetch_all("SELECT name FROM users", []), [["Ray"]])
+
+        self.db.drop_column("users", "score")
+        self.assertEqual(self.db.fetch_all("SELECT * FROM users", []), [["Ray"]])
+        with self.assertRaises(OperationalError):
+            self.db.drop_column("users", "score")
+        with self.assertRaises(RuntimeError):
+            self.db.drop_column("users", "name; --")
+
+    def test_create_index(self):
+        """Test creating plain, partial and expression indexes."""
+        self.db.create_table("users", {"name": str, "email": str, "deleted": bool})
+        self.db.create_index("users", ["name"])
+        self.db.create_index("users", ["email"], unique=True, where="deleted = 0")
+        self.db.create_index("users", ["lower(email)"], name="idx_users_email_nocase")
+        indexes = self.db.fetch_all(
+            "SELECT name FROM sqlite_schema WHERE type = 'index' ORDER BY name", []
+        )
+        self.assertEqual(indexes, [["idx_users_email"], ["idx_users_email_nocase"], ["idx_users_name"]])
+
+        # The partial unique index only applies to the rows that are not deleted
+        self.db.insert("users", {"name": "Ray", "email": "ray@example.com", "deleted": True})
+        self.db.insert("users", {"name": "Ray", "email": "ray@example.com", "deleted": False})
+        with self.assertRaises(IntegrityError):
+            self.db.insert("users", {"name": "Ray", "email": "ray@example.com", "deleted": False})
+
+        plan = self.db.fetch_all(
+            "EXPLAIN QUERY PLAN SELECT name FROM users WHERE lower(email) = ?", ["ray@example.com"]
+        )
+        self.assertIn("idx_users_email_nocase", " ".join(row[-1] for row in plan))
+
+        self.db.create_index("users", ["name"])
+        with self.assertRaises(OperationalError):
+            self.db.create_index("users", ["name"], if_not_exists=False)
+        with self.assertRaises(RuntimeError):
+            self.db.create_index("users", ["lower(name)"])
+        with self.assertRaises(RuntimeError):
+            self.db.create_index("users", [])
+        with self.assertRaises(RuntimeError):
+            self.db.create_index("users; --", ["name"])
+        with self.assertRaises(DatabaseError):
+            self.db.create_index("users", ["name"], name="idx_evil", where="1); DROP TABLE users; --")
+        with self.assertRaises(DatabaseError):
+            self.db.create_index("users", ["name"], name="idx_evil", where="1; DROP TABLE users")
+        self.assertEqual(self.db.fetch_all("SELECT COUNT(*) FROM users", []), [["2"]])
+
     def test_table_does_not_exist(self):
         """Test querying a non-existing table."""
         with self.assertRaises(RuntimeError):